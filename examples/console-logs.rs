@@ -2,7 +2,7 @@
 
 use std::time::Duration;
 
-use chromiumoxide::{cdp::js_protocol::runtime::EventConsoleApiCalled, BrowserConfig};
+use chromiumoxide::BrowserConfig;
 use futures::StreamExt;
 
 const TARGET: &str = "https://www.microsoft.com/";
@@ -27,16 +27,16 @@ async fn main() {
         .await
         .expect("failed to create page");
 
-    let mut console_events = page
-        .event_listener::<EventConsoleApiCalled>()
+    let mut console_messages = page
+        .console_messages()
         .await
         .expect("Failed to add event listener");
     let logs_handle = tokio::spawn(async move {
-        while let Some(event) = console_events.next().await {
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&*event).expect("Failed to serialize event")
-            );
+        while let Some(message) = console_messages.next().await {
+            match message.location {
+                Some(location) => println!("[{:?}] {} ({location})", message.level, message.text),
+                None => println!("[{:?}] {}", message.level, message.text),
+            }
         }
     });
 
@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+
+use crate::adblock::is_subdomain_of;
+
+/// An element hiding filter, like `##.ad` or `example.com,~shop.example.com##.banner`
+#[derive(Debug, Clone)]
+pub(crate) struct CosmeticFilter {
+    /// Whether this is an exception (`#@#`)
+    pub exception: bool,
+    /// The css selector of the elements to hide
+    pub selector: String,
+    /// The domains of the page the filter is restricted to
+    include_domains: Vec<String>,
+    /// The domains of the page the filter doesn't apply to
+    exclude_domains: Vec<String>,
+}
+
+impl CosmeticFilter {
+    /// Parses a single element hiding filter.
+    ///
+    /// Returns `None` for extended syntax like procedural filters (`#?#`),
+    /// scriptlets (`##+js(...)`) or html filters (`##^`), which can't be
+    /// expressed in plain css.
+    pub fn parse(line: &str) -> Option<Self> {
+        let (domains, selector, exception) = if let Some(idx) = line.find("#@#") {
+            (&line[..idx], &line[idx + 3..], true)
+        } else {
+            let idx = line.find("##")?;
+            (&line[..idx], &line[idx + 2..], false)
+        };
+        let selector = selector.trim();
+        if selector.is_empty()
+            || selector.starts_with('+')
+            || selector.starts_with('^')
+            || selector.contains(":-abp-")
+            || selector.contains(":has-text(")
+            || selector.contains(":style(")
+            || selector.contains('{')
+            // a `#?#`, `#$#` etc. filter whose marker was split at `##`
+            || domains.ends_with('#')
+            || domains.ends_with('?')
+            || domains.ends_with('$')
+        {
+            return None;
+        }
+        let mut filter = CosmeticFilter {
+            exception,
+            selector: selector.to_string(),
+            include_domains: Vec::new(),
+            exclude_domains: Vec::new(),
+        };
+        for domain in domains.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match domain.strip_prefix('~') {
+                Some(domain) => filter.exclude_domains.push(domain.to_ascii_lowercase()),
+                None => filter.include_domains.push(domain.to_ascii_lowercase()),
+            }
+        }
+        Some(filter)
+    }
+
+    /// Whether the filter applies to all pages that are not excluded
+    pub fn is_generic(&self) -> bool {
+        self.include_domains.is_empty()
+    }
+
+    /// Whether the filter applies to pages on the `host`
+    pub fn matches(&self, host: &str) -> bool {
+        if self
+            .exclude_domains
+            .iter()
+            .any(|domain| is_subdomain_of(host, domain))
+        {
+            return false;
+        }
+        self.is_generic()
+            || self
+                .include_domains
+                .iter()
+                .any(|domain| is_subdomain_of(host, domain))
+    }
+}
+
+/// All element hiding filters of an engine
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CosmeticFilters {
+    filters: Vec<CosmeticFilter>,
+    exceptions: Vec<CosmeticFilter>,
+}
+
+impl CosmeticFilters {
+    pub fn insert(&mut self, filter: CosmeticFilter) {
+        if filter.exception {
+            self.exceptions.push(filter)
+        } else {
+            self.filters.push(filter)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.len() + self.exceptions.len()
+    }
+
+    /// The selectors of the elements to hide on pages on the `host`
+    pub fn selectors(&self, host: &str, generic: bool) -> Vec<&str> {
+        let excepted = self
+            .exceptions
+            .iter()
+            .filter(|ex| ex.matches(host))
+            .map(|ex| ex.selector.as_str())
+            .collect::<HashSet<_>>();
+        let mut seen = HashSet::new();
+        self.filters
+            .iter()
+            .filter(|f| (generic || !f.is_generic()) && f.matches(host))
+            .map(|f| f.selector.as_str())
+            .filter(|selector| !excepted.contains(selector) && seen.insert(*selector))
+            .collect()
+    }
+}
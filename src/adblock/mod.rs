@@ -0,0 +1,763 @@
+//! Request blocking and element hiding based on EasyList/uBlock Origin style
+//! filter lists.
+//!
+//! Filter lists are parsed and matched entirely within the crate, so a list
+//! can be loaded from a local file without any network access:
+//!
+//! ```no_run
+//! # use chromiumoxide::adblock::AdblockEngine;
+//! # use chromiumoxide::browser::BrowserConfig;
+//! # fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let engine = AdblockEngine::from_file("easylist.txt")?;
+//! let config = BrowserConfig::builder()
+//!     .adblock(engine.into_options().cosmetic_filtering(true))
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Supported are network filters with `||`, `|`, `*` and `^`, regular
+//! expressions, `@@` exceptions and the options `$third-party`, `$domain=`,
+//! `$match-case`, `$important`, the resource types and `$document`,
+//! `$elemhide` and `$generichide` for exceptions. Element hiding filters
+//! (`##`, `#@#`) are supported as far as they are plain css. Filters with
+//! any other option or syntax are skipped.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::ResourceType;
+
+use crate::adblock::cosmetic::{CosmeticFilter, CosmeticFilters};
+use crate::adblock::network::{url_tokens, FilterIndex, NetworkFilter};
+
+mod cosmetic;
+mod network;
+
+/// The type of a request as distinguished by filter options
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestType {
+    /// The document of the main frame
+    Document,
+    /// The document of a child frame
+    Subdocument,
+    Stylesheet,
+    Script,
+    Image,
+    Font,
+    Media,
+    Object,
+    Xhr,
+    WebSocket,
+    Ping,
+    Other,
+}
+
+impl RequestType {
+    const ALL: [RequestType; 12] = [
+        RequestType::Document,
+        RequestType::Subdocument,
+        RequestType::Stylesheet,
+        RequestType::Script,
+        RequestType::Image,
+        RequestType::Font,
+        RequestType::Media,
+        RequestType::Object,
+        RequestType::Xhr,
+        RequestType::WebSocket,
+        RequestType::Ping,
+        RequestType::Other,
+    ];
+
+    /// The type of a request for a resource of the `resource_type`.
+    ///
+    /// Documents are `Subdocument`s if they are loaded in a child frame.
+    pub fn new(resource_type: &ResourceType, subdocument: bool) -> Self {
+        match resource_type {
+            ResourceType::Document if subdocument => RequestType::Subdocument,
+            ResourceType::Document => RequestType::Document,
+            ResourceType::Stylesheet => RequestType::Stylesheet,
+            ResourceType::Image => RequestType::Image,
+            ResourceType::Media => RequestType::Media,
+            ResourceType::Font => RequestType::Font,
+            ResourceType::Script => RequestType::Script,
+            ResourceType::Xhr | ResourceType::Fetch | ResourceType::EventSource => RequestType::Xhr,
+            ResourceType::WebSocket => RequestType::WebSocket,
+            ResourceType::Ping | ResourceType::CspViolationReport => RequestType::Ping,
+            _ => RequestType::Other,
+        }
+    }
+
+    /// The request types of a filter option like `$script`
+    fn from_option(option: &str) -> Option<Vec<RequestType>> {
+        let ty = match option {
+            "document" | "doc" => RequestType::Document,
+            "subdocument" | "frame" => RequestType::Subdocument,
+            "stylesheet" | "css" => RequestType::Stylesheet,
+            "script" => RequestType::Script,
+            "image" => RequestType::Image,
+            "font" => RequestType::Font,
+            "media" => RequestType::Media,
+            "object" | "object-subrequest" => RequestType::Object,
+            "xmlhttprequest" | "xhr" => RequestType::Xhr,
+            "websocket" => RequestType::WebSocket,
+            "ping" | "beacon" => RequestType::Ping,
+            "other" => RequestType::Other,
+            _ => return None,
+        };
+        Some(vec![ty])
+    }
+}
+
+/// A request to check against the filters of an [`AdblockEngine`]
+#[derive(Debug, Clone, Copy)]
+pub struct AdblockRequest<'a> {
+    /// The url of the request
+    pub url: &'a str,
+    /// The url of the document that issued the request
+    pub source_url: &'a str,
+    /// The type of the request
+    pub request_type: RequestType,
+}
+
+impl<'a> AdblockRequest<'a> {
+    pub fn new(url: &'a str, source_url: &'a str, request_type: RequestType) -> Self {
+        Self {
+            url,
+            source_url,
+            request_type,
+        }
+    }
+}
+
+/// A request prepared for matching
+#[derive(Debug)]
+pub(crate) struct RequestContext<'a> {
+    url: &'a str,
+    url_lowercase: String,
+    host: String,
+    source_host: String,
+    third_party: bool,
+    request_type: RequestType,
+}
+
+/// Blocks requests and hides elements based on filter lists.
+#[derive(Debug, Clone, Default)]
+pub struct AdblockEngine {
+    filters: FilterIndex,
+    exceptions: FilterIndex,
+    /// Exceptions that allow all requests of a page (`$document`)
+    document_exceptions: Vec<NetworkFilter>,
+    /// Exceptions that disable element hiding on a page (`$elemhide`,
+    /// `$generichide`)
+    hide_exceptions: Vec<NetworkFilter>,
+    cosmetic: CosmeticFilters,
+}
+
+impl AdblockEngine {
+    /// Creates an engine from the filters of a list, one per line
+    pub fn from_rules(rules: &str) -> Self {
+        let mut engine = Self::default();
+        engine.add_rules(rules);
+        engine
+    }
+
+    /// Creates an engine from the filters of a local list file
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from_rules(&std::fs::read_to_string(path)?))
+    }
+
+    /// Adds the filters of a list, one per line, and returns the number of
+    /// filters that were added.
+    ///
+    /// Comments and filters with unsupported syntax are skipped.
+    pub fn add_rules(&mut self, rules: &str) -> usize {
+        rules.lines().filter(|line| self.add_rule(line)).count()
+    }
+
+    /// Adds a single filter, returns `false` if it is a comment or uses
+    /// unsupported syntax
+    pub fn add_rule(&mut self, rule: &str) -> bool {
+        let rule = rule.trim();
+        if rule.is_empty() || rule.starts_with('!') || rule.starts_with('[') {
+            return false;
+        }
+        let is_cosmetic = ["##", "#@#", "#?#", "#$#", "#%#"]
+            .iter()
+            .any(|marker| rule.contains(marker));
+        if is_cosmetic {
+            if let Some(filter) = CosmeticFilter::parse(rule) {
+                self.cosmetic.insert(filter);
+                return true;
+            }
+            return false;
+        }
+        match NetworkFilter::parse(rule) {
+            Some(filter) if filter.exception => {
+                if filter.applies_to_documents() {
+                    self.document_exceptions.push(filter.clone());
+                }
+                if filter.elemhide || filter.generichide {
+                    self.hide_exceptions.push(filter.clone());
+                }
+                self.exceptions.insert(filter)
+            }
+            Some(filter) => self.filters.insert(filter),
+            None => return false,
+        }
+        true
+    }
+
+    /// The number of network filters, including exceptions
+    pub fn network_filters(&self) -> usize {
+        self.filters.len() + self.exceptions.len()
+    }
+
+    /// The number of element hiding filters, including exceptions
+    pub fn cosmetic_filters(&self) -> usize {
+        self.cosmetic.len()
+    }
+
+    /// Whether the request should be blocked
+    pub fn should_block(&self, request: &AdblockRequest<'_>) -> bool {
+        let ctx = match RequestContext::new(request) {
+            Some(ctx) => ctx,
+            None => return false,
+        };
+        let tokens = url_tokens(&ctx.url_lowercase);
+        let mut blocked = false;
+        for filter in self.filters.candidates(&tokens) {
+            if filter.matches(&ctx) {
+                if filter.important {
+                    return true;
+                }
+                blocked = true;
+            }
+        }
+        blocked && !self.is_excepted(request, &ctx, &tokens)
+    }
+
+    fn is_excepted(
+        &self,
+        request: &AdblockRequest<'_>,
+        ctx: &RequestContext<'_>,
+        tokens: &HashSet<&str>,
+    ) -> bool {
+        if self
+            .exceptions
+            .candidates(tokens)
+            .any(|filter| filter.matches(ctx))
+        {
+            return true;
+        }
+        // `$document` exceptions allow all requests of a page
+        let page = AdblockRequest::new(
+            request.source_url,
+            request.source_url,
+            RequestType::Document,
+        );
+        match RequestContext::new(&page) {
+            Some(page) => self
+                .document_exceptions
+                .iter()
+                .any(|filter| filter.matches_url(&page)),
+            None => false,
+        }
+    }
+
+    /// The css selectors of the elements to hide on the page at `url`
+    pub fn hidden_selectors(&self, url: &str) -> Vec<&str> {
+        let page = AdblockRequest::new(url, url, RequestType::Document);
+        let ctx = match RequestContext::new(&page) {
+            Some(ctx) => ctx,
+            None => return Vec::new(),
+        };
+        let mut elemhide = false;
+        let mut generichide = false;
+        for filter in &self.hide_exceptions {
+            if filter.matches_url(&ctx) {
+                elemhide |= filter.elemhide;
+                generichide |= filter.generichide;
+            }
+        }
+        if elemhide {
+            return Vec::new();
+        }
+        self.cosmetic.selectors(&ctx.host, !generichide)
+    }
+
+    /// A stylesheet that hides the elements matched by the element hiding
+    /// filters on the page at `url`, or `None` if there is nothing to hide
+    pub fn cosmetic_css(&self, url: &str) -> Option<String> {
+        let selectors = self.hidden_selectors(url);
+        if selectors.is_empty() {
+            return None;
+        }
+        // one rule per selector, since a single invalid selector invalidates
+        // the entire rule
+        Some(
+            selectors
+                .into_iter()
+                .map(|selector| format!("{selector} {{ display: none !important; }}\n"))
+                .collect(),
+        )
+    }
+
+    /// Wraps the engine in [`AdblockOptions`] that block requests, but don't
+    /// hide elements
+    pub fn into_options(self) -> AdblockOptions {
+        AdblockOptions::new(self)
+    }
+}
+
+/// How pages use an [`AdblockEngine`]
+#[derive(Debug, Clone)]
+pub struct AdblockOptions {
+    /// The engine that decides which requests are blocked
+    pub engine: Arc<AdblockEngine>,
+    /// Whether the elements matched by element hiding filters are hidden
+    pub cosmetic_filtering: bool,
+}
+
+impl AdblockOptions {
+    /// Blocks requests with the engine, without hiding elements
+    pub fn new(engine: impl Into<Arc<AdblockEngine>>) -> Self {
+        Self {
+            engine: engine.into(),
+            cosmetic_filtering: false,
+        }
+    }
+
+    /// Whether to also hide the elements matched by element hiding filters
+    pub fn cosmetic_filtering(mut self, enabled: bool) -> Self {
+        self.cosmetic_filtering = enabled;
+        self
+    }
+}
+
+impl From<AdblockEngine> for AdblockOptions {
+    fn from(engine: AdblockEngine) -> Self {
+        Self::new(engine)
+    }
+}
+
+impl From<Arc<AdblockEngine>> for AdblockOptions {
+    fn from(engine: Arc<AdblockEngine>) -> Self {
+        Self::new(engine)
+    }
+}
+
+impl<'a> RequestContext<'a> {
+    fn new(request: &AdblockRequest<'a>) -> Option<Self> {
+        let host = host_of(request.url)?;
+        let source_host = host_of(request.source_url).unwrap_or_default();
+        let third_party =
+            !source_host.is_empty() && base_domain(&host) != base_domain(&source_host);
+        Some(Self {
+            url: request.url,
+            url_lowercase: request.url.to_ascii_lowercase(),
+            host,
+            source_host,
+            third_party,
+            request_type: request.request_type,
+        })
+    }
+
+    /// The url with its scheme and host in lowercase, for filters that
+    /// match the case of the rest of the url
+    fn url_with_lowercase_host(&self) -> String {
+        match self.url_lowercase.find(&self.host) {
+            Some(start) if !self.host.is_empty() => {
+                let end = start + self.host.len();
+                format!("{}{}", &self.url_lowercase[..end], &self.url[end..])
+            }
+            _ => self.url.to_string(),
+        }
+    }
+}
+
+/// The lowercase host of an absolute url
+fn host_of(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = if host.starts_with('[') {
+        host.split_inclusive(']').next()?
+    } else {
+        host.split(':').next()?
+    };
+    Some(host.to_ascii_lowercase())
+}
+
+/// Whether `host` is `domain` or one of its subdomains
+pub(crate) fn is_subdomain_of(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.')
+}
+
+/// An approximation of the registrable domain of a host, without a public
+/// suffix list: the last two labels, or three for common second-level
+/// suffixes like `co.uk`
+fn base_domain(host: &str) -> &str {
+    if host.starts_with('[') || host.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+        return host;
+    }
+    let labels = host.rsplit('.').collect::<Vec<_>>();
+    let count = match labels.as_slice() {
+        [tld, sld, _, ..]
+            if tld.len() == 2
+                && matches!(
+                    *sld,
+                    "co" | "com" | "net" | "org" | "gov" | "edu" | "ac" | "or" | "ne" | "go"
+                ) =>
+        {
+            3
+        }
+        _ => 2,
+    };
+    if labels.len() <= count {
+        return host;
+    }
+    let len = labels[..count].iter().map(|l| l.len() + 1).sum::<usize>() - 1;
+    &host[host.len() - len..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASYLIST: &str = include_str!("../../tests/fixtures/adblock/easylist.txt");
+
+    fn engine() -> AdblockEngine {
+        AdblockEngine::from_rules(EASYLIST)
+    }
+
+    fn blocked(engine: &AdblockEngine, url: &str, source: &str, ty: RequestType) -> bool {
+        engine.should_block(&AdblockRequest::new(url, source, ty))
+    }
+
+    #[test]
+    fn parses_fixture() {
+        let engine = engine();
+        assert_eq!(engine.network_filters(), 19);
+        assert_eq!(engine.cosmetic_filters(), 7);
+    }
+
+    #[test]
+    fn blocks_hostname_anchored() {
+        let engine = engine();
+        let page = "https://news.example/article";
+        assert!(blocked(
+            &engine,
+            "https://ads.tracker.test/pixel.gif",
+            page,
+            RequestType::Image
+        ));
+        assert!(blocked(
+            &engine,
+            "https://cdn.ads.tracker.test/lib.js",
+            page,
+            RequestType::Script
+        ));
+        assert!(!blocked(
+            &engine,
+            "https://notads.tracker.test/lib.js",
+            page,
+            RequestType::Script
+        ));
+        // `^` matches the end of the url
+        assert!(blocked(
+            &engine,
+            "https://doubleclick.test",
+            page,
+            RequestType::Script
+        ));
+        // documents are only blocked by filters with `$document`
+        assert!(!blocked(
+            &engine,
+            "https://ads.tracker.test/",
+            page,
+            RequestType::Document
+        ));
+    }
+
+    #[test]
+    fn blocks_wildcards_and_separators() {
+        let engine = engine();
+        let page = "https://news.example/";
+        assert!(blocked(
+            &engine,
+            "https://news.example/banner/top/ad.png",
+            page,
+            RequestType::Image
+        ));
+        assert!(blocked(
+            &engine,
+            "https://news.example/js/adframe.js?v=2",
+            page,
+            RequestType::Script
+        ));
+        assert!(!blocked(
+            &engine,
+            "https://news.example/js/adframe.json",
+            page,
+            RequestType::Script
+        ));
+        assert!(blocked(
+            &engine,
+            "https://news.example/static/ad_300x250.jpg",
+            page,
+            RequestType::Image
+        ));
+        assert!(blocked(
+            &engine,
+            "https://tracker.example/collect.php",
+            page,
+            RequestType::Xhr
+        ));
+        assert!(!blocked(
+            &engine,
+            "https://tracker.example/collect.php5",
+            page,
+            RequestType::Xhr
+        ));
+    }
+
+    #[test]
+    fn applies_third_party_option() {
+        let engine = engine();
+        let url = "https://widgets.social.test/like.js";
+        assert!(blocked(
+            &engine,
+            url,
+            "https://news.example/",
+            RequestType::Script
+        ));
+        assert!(!blocked(
+            &engine,
+            url,
+            "https://www.social.test/",
+            RequestType::Script
+        ));
+        // `$~third-party`
+        let url = "https://shop.example/promo/sale.js";
+        assert!(blocked(
+            &engine,
+            url,
+            "https://www.shop.example/",
+            RequestType::Script
+        ));
+        assert!(!blocked(
+            &engine,
+            url,
+            "https://news.example/",
+            RequestType::Script
+        ));
+        // registrable domains with a second-level suffix
+        let url = "https://static.metrics.co.uk/m.js";
+        assert!(!blocked(
+            &engine,
+            url,
+            "https://www.metrics.co.uk/",
+            RequestType::Script
+        ));
+        assert!(blocked(
+            &engine,
+            url,
+            "https://www.other.co.uk/",
+            RequestType::Script
+        ));
+    }
+
+    #[test]
+    fn applies_domain_option() {
+        let engine = engine();
+        let url = "https://cdn.example/popunder.js";
+        assert!(blocked(
+            &engine,
+            url,
+            "https://video.test/watch",
+            RequestType::Script
+        ));
+        assert!(blocked(
+            &engine,
+            url,
+            "https://m.video.test/watch",
+            RequestType::Script
+        ));
+        assert!(!blocked(
+            &engine,
+            url,
+            "https://safe.video.test/",
+            RequestType::Script
+        ));
+        assert!(!blocked(
+            &engine,
+            url,
+            "https://news.example/",
+            RequestType::Script
+        ));
+    }
+
+    #[test]
+    fn applies_resource_type_options() {
+        let engine = engine();
+        let page = "https://news.example/";
+        let url = "https://media.example/sponsor/clip.mp4";
+        assert!(blocked(&engine, url, page, RequestType::Media));
+        assert!(blocked(&engine, url, page, RequestType::Image));
+        assert!(!blocked(&engine, url, page, RequestType::Script));
+        // `$~script`
+        let url = "https://news.example/track/pixel";
+        assert!(blocked(&engine, url, page, RequestType::Image));
+        assert!(!blocked(&engine, url, page, RequestType::Script));
+        // `$subdocument`
+        let url = "https://frames.example/embed/ad.html";
+        assert!(blocked(&engine, url, page, RequestType::Subdocument));
+        assert!(!blocked(&engine, url, page, RequestType::Image));
+    }
+
+    #[test]
+    fn applies_exceptions() {
+        let engine = engine();
+        let page = "https://news.example/";
+        assert!(!blocked(
+            &engine,
+            "https://ads.tracker.test/allowed/lib.js",
+            page,
+            RequestType::Script
+        ));
+        // `$important` overrides exceptions
+        assert!(blocked(
+            &engine,
+            "https://ads.tracker.test/allowed/miner.js",
+            page,
+            RequestType::Script
+        ));
+        // `$document` exceptions allow all requests of a page
+        let page = "https://trusted.example/home";
+        assert!(!blocked(
+            &engine,
+            "https://ads.tracker.test/pixel.gif",
+            page,
+            RequestType::Image
+        ));
+    }
+
+    #[test]
+    fn matches_regex_and_case() {
+        let engine = engine();
+        let page = "https://news.example/";
+        assert!(blocked(
+            &engine,
+            "https://news.example/ad123.gif",
+            page,
+            RequestType::Image
+        ));
+        assert!(!blocked(
+            &engine,
+            "https://news.example/adx.gif",
+            page,
+            RequestType::Image
+        ));
+        assert!(blocked(
+            &engine,
+            "https://news.example/BigAd.png",
+            page,
+            RequestType::Image
+        ));
+        assert!(!blocked(
+            &engine,
+            "https://news.example/bigad.png",
+            page,
+            RequestType::Image
+        ));
+    }
+
+    #[test]
+    fn anchors_hosts_of_mixed_case_urls() {
+        let engine =
+            AdblockEngine::from_rules("||example.com/Banner$match-case\n||ads.tracker.test^");
+        let page = "https://news.example/";
+        assert!(blocked(
+            &engine,
+            "https://WWW.Example.COM/Banner.gif",
+            page,
+            RequestType::Image
+        ));
+        assert!(!blocked(
+            &engine,
+            "https://WWW.Example.COM/banner.gif",
+            page,
+            RequestType::Image
+        ));
+        assert!(blocked(
+            &engine,
+            "HTTPS://Ads.Tracker.TEST/Pixel.gif",
+            page,
+            RequestType::Image
+        ));
+    }
+
+    #[test]
+    fn skips_unsupported_filters() {
+        let engine = engine();
+        let page = "https://news.example/";
+        // `$redirect=` and `$popup`
+        assert!(!blocked(
+            &engine,
+            "https://news.example/redirected.js",
+            page,
+            RequestType::Script
+        ));
+        assert!(!blocked(
+            &engine,
+            "https://popups.example/",
+            page,
+            RequestType::Document
+        ));
+    }
+
+    #[test]
+    fn hides_elements() {
+        let engine = engine();
+        let selectors = engine.hidden_selectors("https://www.news.example/article");
+        assert_eq!(
+            selectors,
+            vec![
+                ".ad-banner",
+                "#sponsored",
+                "div[id^=\"ad-\"]",
+                ".news-promo"
+            ]
+        );
+        let selectors = engine.hidden_selectors("https://shop.example/");
+        assert_eq!(selectors, vec![".ad-banner", "#sponsored", ".old-ad"]);
+        // `$generichide`
+        let selectors = engine.hidden_selectors("https://forum.example/");
+        assert_eq!(selectors, vec![".forum-ad"]);
+        // `$elemhide`
+        assert!(engine
+            .hidden_selectors("https://trusted.example/")
+            .is_empty());
+        let css = engine.cosmetic_css("https://shop.example/").unwrap();
+        assert_eq!(
+            css,
+            ".ad-banner { display: none !important; }\n\
+             #sponsored { display: none !important; }\n\
+             .old-ad { display: none !important; }\n"
+        );
+    }
+
+    #[test]
+    fn approximates_base_domain() {
+        assert_eq!(base_domain("www.example.com"), "example.com");
+        assert_eq!(base_domain("example.com"), "example.com");
+        assert_eq!(base_domain("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(base_domain("127.0.0.1"), "127.0.0.1");
+    }
+}
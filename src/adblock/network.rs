@@ -0,0 +1,410 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::adblock::{is_subdomain_of, RequestContext, RequestType};
+
+/// A filter that blocks, or with `@@` allows, requests
+#[derive(Debug, Clone)]
+pub(crate) struct NetworkFilter {
+    /// Whether this is an exception filter (`@@`)
+    pub exception: bool,
+    /// Whether this filter takes precedence over exceptions (`$important`)
+    pub important: bool,
+    pattern: Pattern,
+    /// `|` at the start of the pattern
+    left_anchor: bool,
+    /// `||` at the start of the pattern
+    hostname_anchor: bool,
+    /// `|` at the end of the pattern
+    right_anchor: bool,
+    match_case: bool,
+    /// `Some(true)` for `$third-party`, `Some(false)` for `$~third-party`
+    third_party: Option<bool>,
+    /// Domains of the page the filter is restricted to (`$domain=`)
+    include_domains: Vec<String>,
+    /// Domains of the page the filter doesn't apply to (`$domain=~`)
+    exclude_domains: Vec<String>,
+    /// The request types the filter applies to, `None` for all but documents
+    request_types: Option<HashSet<RequestType>>,
+    /// Whether this exception disables cosmetic filtering (`$elemhide`)
+    pub elemhide: bool,
+    /// Whether this exception disables generic cosmetic filtering
+    /// (`$generichide`)
+    pub generichide: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    /// A pattern with `*` wildcards and `^` separators
+    Plain(Vec<PatternToken>),
+    /// A regular expression (`/.../`)
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatternToken {
+    Byte(u8),
+    /// `*`
+    Wildcard,
+    /// `^`
+    Separator,
+}
+
+impl NetworkFilter {
+    /// Parses a single network filter line.
+    ///
+    /// Returns `None` for filters that use unsupported syntax or options, so
+    /// that they don't block more than intended.
+    pub fn parse(line: &str) -> Option<Self> {
+        let (exception, line) = match line.strip_prefix("@@") {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (pattern, options) = split_options(line);
+
+        let mut filter = NetworkFilter {
+            exception,
+            important: false,
+            pattern: Pattern::Plain(Vec::new()),
+            left_anchor: false,
+            hostname_anchor: false,
+            right_anchor: false,
+            match_case: false,
+            third_party: None,
+            include_domains: Vec::new(),
+            exclude_domains: Vec::new(),
+            request_types: None,
+            elemhide: false,
+            generichide: false,
+        };
+        if let Some(options) = options {
+            filter.parse_options(options)?;
+        }
+
+        if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+            let re = RegexBuilder::new(&pattern[1..pattern.len() - 1])
+                .case_insensitive(!filter.match_case)
+                .build()
+                .ok()?;
+            filter.pattern = Pattern::Regex(re);
+            return Some(filter);
+        }
+
+        let mut pattern = pattern;
+        if let Some(p) = pattern.strip_prefix("||") {
+            filter.hostname_anchor = true;
+            pattern = p;
+        } else if let Some(p) = pattern.strip_prefix('|') {
+            filter.left_anchor = true;
+            pattern = p;
+        }
+        if let Some(p) = pattern.strip_suffix('|') {
+            filter.right_anchor = true;
+            pattern = p;
+        }
+        let pattern = if filter.match_case {
+            pattern.to_string()
+        } else {
+            pattern.to_ascii_lowercase()
+        };
+        let mut tokens = Vec::with_capacity(pattern.len());
+        for b in pattern.bytes() {
+            match b {
+                // consecutive wildcards are redundant
+                b'*' if tokens.last() == Some(&PatternToken::Wildcard) => {}
+                b'*' => tokens.push(PatternToken::Wildcard),
+                b'^' => tokens.push(PatternToken::Separator),
+                b => tokens.push(PatternToken::Byte(b)),
+            }
+        }
+        filter.pattern = Pattern::Plain(tokens);
+        Some(filter)
+    }
+
+    fn parse_options(&mut self, options: &str) -> Option<()> {
+        let mut types = HashSet::new();
+        let mut excluded_types = HashSet::new();
+        for option in options.split(',') {
+            let (negated, name) = match option.strip_prefix('~') {
+                Some(name) => (true, name),
+                None => (false, option),
+            };
+            if let Some(domains) = name.strip_prefix("domain=") {
+                if negated {
+                    return None;
+                }
+                for domain in domains.split('|') {
+                    match domain.strip_prefix('~') {
+                        Some(domain) => self.exclude_domains.push(domain.to_ascii_lowercase()),
+                        None => self.include_domains.push(domain.to_ascii_lowercase()),
+                    }
+                }
+                continue;
+            }
+            match name {
+                "third-party" | "3p" => self.third_party = Some(!negated),
+                "first-party" | "1p" => self.third_party = Some(negated),
+                "match-case" => self.match_case = true,
+                "important" => self.important = true,
+                "elemhide" | "ehide" if self.exception => self.elemhide = true,
+                "generichide" | "ghide" if self.exception => self.generichide = true,
+                "all" => types.extend(RequestType::ALL),
+                // popups are not requests that can be blocked
+                "popup" => return None,
+                name => {
+                    let request_types = RequestType::from_option(name)?;
+                    if negated {
+                        excluded_types.extend(request_types);
+                    } else {
+                        types.extend(request_types);
+                    }
+                }
+            }
+        }
+        if self.elemhide || self.generichide {
+            // such exceptions only apply to cosmetic filters, unless they also
+            // list request types
+            if types.is_empty() && excluded_types.is_empty() {
+                self.request_types = Some(HashSet::new());
+                return Some(());
+            }
+        }
+        if !types.is_empty() || !excluded_types.is_empty() {
+            if types.is_empty() {
+                types.extend(
+                    RequestType::ALL
+                        .into_iter()
+                        .filter(|ty| *ty != RequestType::Document),
+                );
+            }
+            types.retain(|ty| !excluded_types.contains(ty));
+            self.request_types = Some(types);
+        }
+        Some(())
+    }
+
+    /// Whether this filter applies to pages, i.e. `@@||example.com^$document`
+    pub fn applies_to_documents(&self) -> bool {
+        self.request_types
+            .as_ref()
+            .map(|types| types.contains(&RequestType::Document))
+            .unwrap_or_default()
+    }
+
+    /// Whether this filter matches the request
+    pub fn matches(&self, request: &RequestContext<'_>) -> bool {
+        let type_matches = match self.request_types.as_ref() {
+            Some(types) => types.contains(&request.request_type),
+            None => request.request_type != RequestType::Document,
+        };
+        type_matches && self.matches_url(request)
+    }
+
+    /// Whether this filter matches the request, regardless of its type
+    pub fn matches_url(&self, request: &RequestContext<'_>) -> bool {
+        if let Some(third_party) = self.third_party {
+            if third_party != request.third_party {
+                return false;
+            }
+        }
+        if !self.matches_source_domain(&request.source_host) {
+            return false;
+        }
+        // hosts are case insensitive, so they are compared in lowercase
+        let url = if self.match_case {
+            Cow::Owned(request.url_with_lowercase_host())
+        } else {
+            Cow::Borrowed(request.url_lowercase.as_str())
+        };
+        match &self.pattern {
+            Pattern::Regex(re) => re.is_match(&url),
+            Pattern::Plain(tokens) => self.matches_plain(tokens, &url, &request.host),
+        }
+    }
+
+    fn matches_source_domain(&self, source_host: &str) -> bool {
+        if self
+            .exclude_domains
+            .iter()
+            .any(|domain| is_subdomain_of(source_host, domain))
+        {
+            return false;
+        }
+        self.include_domains.is_empty()
+            || self
+                .include_domains
+                .iter()
+                .any(|domain| is_subdomain_of(source_host, domain))
+    }
+
+    fn matches_plain(&self, tokens: &[PatternToken], url: &str, host: &str) -> bool {
+        let text = url.as_bytes();
+        let mut starts = vec![false; text.len() + 1];
+        if self.hostname_anchor {
+            // the pattern starts at the beginning of the host or of one of its
+            // labels
+            if let Some(host_start) = url.find(host).filter(|_| !host.is_empty()) {
+                starts[host_start] = true;
+                for (i, b) in host.bytes().enumerate() {
+                    if b == b'.' {
+                        starts[host_start + i + 1] = true;
+                    }
+                }
+            }
+        } else if self.left_anchor {
+            starts[0] = true;
+        } else {
+            starts.iter_mut().for_each(|start| *start = true);
+        }
+        match_tokens(tokens, text, starts, self.right_anchor)
+    }
+
+    /// The token requests need to contain for this filter to match, used to
+    /// quickly find candidate filters for a request
+    pub fn token(&self) -> Option<String> {
+        let tokens = match &self.pattern {
+            Pattern::Plain(tokens) => tokens,
+            Pattern::Regex(_) => return None,
+        };
+        let mut best: Option<String> = None;
+        let mut i = 0;
+        while i < tokens.len() {
+            if !is_token_byte(&tokens[i]) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < tokens.len() && is_token_byte(&tokens[i]) {
+                i += 1;
+            }
+            // a token next to a wildcard or at an unanchored end of the
+            // pattern may only be part of a token of the url
+            let bounded_start = if start == 0 {
+                self.hostname_anchor || self.left_anchor
+            } else {
+                tokens[start - 1] != PatternToken::Wildcard
+            };
+            let bounded_end = if i == tokens.len() {
+                self.right_anchor
+            } else {
+                tokens[i] != PatternToken::Wildcard
+            };
+            if !bounded_start || !bounded_end || i - start < 2 {
+                continue;
+            }
+            let token = tokens[start..i]
+                .iter()
+                .filter_map(|t| match t {
+                    PatternToken::Byte(b) => Some(b.to_ascii_lowercase() as char),
+                    _ => None,
+                })
+                .collect::<String>();
+            if best.as_ref().map(|b| token.len() > b.len()).unwrap_or(true) {
+                best = Some(token);
+            }
+        }
+        best
+    }
+}
+
+fn is_token_byte(token: &PatternToken) -> bool {
+    matches!(token, PatternToken::Byte(b) if b.is_ascii_alphanumeric() || *b == b'%')
+}
+
+/// Splits a filter into its pattern and its options after the last `$`
+fn split_options(line: &str) -> (&str, Option<&str>) {
+    if let Some(idx) = line.rfind('$') {
+        let (pattern, options) = (&line[..idx], &line[idx + 1..]);
+        // a `$` inside of a regular expression, like `/ad$/`
+        let is_regex_end = pattern.starts_with('/') && options.contains('/');
+        if !options.is_empty() && !is_regex_end {
+            return (pattern, Some(options));
+        }
+    }
+    (line, None)
+}
+
+/// Whether the byte is a separator as matched by `^`
+fn is_separator(b: u8) -> bool {
+    !(b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'%'))
+}
+
+/// Matches the pattern against `text`, starting at any position that is set
+/// in `starts`
+fn match_tokens(
+    tokens: &[PatternToken],
+    text: &[u8],
+    starts: Vec<bool>,
+    right_anchor: bool,
+) -> bool {
+    // `matched[i]` is whether the tokens so far match up to position `i`
+    let mut matched = starts;
+    for token in tokens {
+        let mut next = vec![false; text.len() + 1];
+        for i in 0..=text.len() {
+            next[i] = match token {
+                PatternToken::Byte(b) => i > 0 && matched[i - 1] && text[i - 1] == *b,
+                PatternToken::Wildcard => matched[i] || (i > 0 && next[i - 1]),
+                PatternToken::Separator => {
+                    (i > 0 && matched[i - 1] && is_separator(text[i - 1]))
+                        || (i == text.len() && matched[i])
+                }
+            };
+        }
+        if !next.iter().any(|m| *m) {
+            return false;
+        }
+        matched = next;
+    }
+    if right_anchor {
+        matched[text.len()]
+    } else {
+        matched.iter().any(|m| *m)
+    }
+}
+
+/// Splits a url into the tokens filters are indexed by
+pub(crate) fn url_tokens(url: &str) -> HashSet<&str> {
+    url.split(|c: char| !(c.is_ascii_alphanumeric() || c == '%'))
+        .filter(|token| token.len() >= 2)
+        .collect()
+}
+
+/// Filters indexed by the token they require the url to contain
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FilterIndex {
+    filters: Vec<NetworkFilter>,
+    by_token: HashMap<String, Vec<usize>>,
+    /// Filters without a token, which are checked for every request
+    untokenized: Vec<usize>,
+}
+
+impl FilterIndex {
+    pub fn insert(&mut self, filter: NetworkFilter) {
+        let idx = self.filters.len();
+        match filter.token() {
+            Some(token) => self.by_token.entry(token).or_default().push(idx),
+            None => self.untokenized.push(idx),
+        }
+        self.filters.push(filter);
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// All filters that may match a url with the `tokens`
+    pub fn candidates<'a>(
+        &'a self,
+        tokens: &'a HashSet<&'a str>,
+    ) -> impl Iterator<Item = &'a NetworkFilter> + 'a {
+        tokens
+            .iter()
+            .filter_map(|token| self.by_token.get(*token))
+            .flatten()
+            .chain(self.untokenized.iter())
+            .map(|idx| &self.filters[*idx])
+    }
+}
@@ -0,0 +1,134 @@
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+
+use crate::error::{CdpError, Result};
+
+/// A decoded image with 8 bit RGBA pixels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels row by row, 4 bytes each
+    pub data: Vec<u8>,
+}
+
+impl Bitmap {
+    /// Decodes a png of any color type into RGBA
+    pub fn from_png(png: &[u8]) -> Result<Self> {
+        let mut decoder = Decoder::new(png);
+        decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(CdpError::image)?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(CdpError::image)?;
+        buf.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            ColorType::Rgba => buf,
+            ColorType::Rgb => buf
+                .chunks_exact(3)
+                .flat_map(|px| [px[0], px[1], px[2], u8::MAX])
+                .collect(),
+            ColorType::GrayscaleAlpha => buf
+                .chunks_exact(2)
+                .flat_map(|px| [px[0], px[0], px[0], px[1]])
+                .collect(),
+            ColorType::Grayscale => buf.iter().flat_map(|&g| [g, g, g, u8::MAX]).collect(),
+            ColorType::Indexed => {
+                return Err(CdpError::msg("Indexed png was not expanded"));
+            }
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    /// Encodes the bitmap as RGBA png
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(CdpError::image)?;
+        writer
+            .write_image_data(&self.data)
+            .map_err(CdpError::image)?;
+        writer.finish().map_err(CdpError::image)?;
+        Ok(png)
+    }
+
+    /// The RGBA value of the pixel, `None` if it is out of bounds
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let idx = (y as usize * self.width as usize + x as usize) * 4;
+        self.data[idx..idx + 4].try_into().ok()
+    }
+
+    /// Stacks the bitmaps on top of each other, all of which must have the
+    /// same width
+    pub fn stack(tiles: impl IntoIterator<Item = Bitmap>) -> Result<Self> {
+        let mut stacked: Option<Bitmap> = None;
+        for tile in tiles {
+            match stacked.as_mut() {
+                Some(stacked) => {
+                    if stacked.width != tile.width {
+                        return Err(CdpError::msg(format!(
+                            "Tile width {} differs from {}",
+                            tile.width, stacked.width
+                        )));
+                    }
+                    stacked.height += tile.height;
+                    stacked.data.extend_from_slice(&tile.data);
+                }
+                None => stacked = Some(tile),
+            }
+        }
+        stacked.ok_or(CdpError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(width: u32, height: u32, px: [u8; 4]) -> Bitmap {
+        Bitmap {
+            width,
+            height,
+            data: px.repeat((width * height) as usize),
+        }
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let bitmap = bitmap(3, 2, [10, 20, 30, 255]);
+        let png = bitmap.to_png().unwrap();
+        assert_eq!(Bitmap::from_png(&png).unwrap(), bitmap);
+    }
+
+    #[test]
+    fn expands_rgb() {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, 2, 1);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[1, 2, 3, 4, 5, 6]).unwrap();
+        writer.finish().unwrap();
+
+        let bitmap = Bitmap::from_png(&png).unwrap();
+        assert_eq!(bitmap.data, vec![1, 2, 3, 255, 4, 5, 6, 255]);
+    }
+
+    #[test]
+    fn stacks_tiles() {
+        let stacked =
+            Bitmap::stack([bitmap(2, 1, [1, 1, 1, 1]), bitmap(2, 3, [2, 2, 2, 2])]).unwrap();
+        assert_eq!(stacked.height, 4);
+        assert_eq!(&stacked.data[..8], &[1; 8]);
+        assert_eq!(&stacked.data[8..], &[2; 24]);
+        assert!(Bitmap::stack([bitmap(2, 1, [0; 4]), bitmap(3, 1, [0; 4])]).is_err());
+    }
+}
@@ -22,19 +22,24 @@ use chromiumoxide_cdp::cdp::browser_protocol::target::{
 use chromiumoxide_cdp::cdp::{CdpEventMessage, IntoEventKind};
 use chromiumoxide_types::*;
 
+use crate::adblock::AdblockOptions;
 use crate::async_process::{self, Child, ExitStatus, Stdio};
 use crate::cmd::{to_command_response, CommandMessage};
 use crate::conn::Connection;
+use crate::cookies::{CookieFormat, CookieRecord};
 use crate::detection::{self, DetectionOptions};
 use crate::error::{BrowserStderr, CdpError, Result};
 use crate::handler::browser::BrowserContext;
+use crate::handler::network::ResourceFilter;
 use crate::handler::viewport::Viewport;
 use crate::handler::{Handler, HandlerConfig, HandlerMessage, REQUEST_TIMEOUT};
 use crate::listeners::{EventListenerRequest, EventStream};
 use crate::page::Page;
 use crate::utils;
 use chromiumoxide_cdp::cdp::browser_protocol::browser::{
-    BrowserContextId, CloseReturns, GetVersionParams, GetVersionReturns,
+    BrowserContextId, CloseReturns, GetVersionParams, GetVersionReturns, GrantPermissionsParams,
+    PermissionDescriptor, PermissionSetting, PermissionType, ResetPermissionsParams,
+    SetPermissionParams,
 };
 
 /// Default `Browser::launch` timeout in MS
@@ -216,6 +221,13 @@ impl Browser {
             request_timeout: config.request_timeout,
             request_intercept: config.request_intercept,
             cache_enabled: config.cache_enabled,
+            trace_console: config.trace_console,
+            extra_headers: config.extra_headers.clone(),
+            blocked_urls: config.blocked_urls.clone(),
+            resource_filters: config.resource_filters.clone(),
+            adblock: config.adblock.clone(),
+            permissions: config.permissions.clone(),
+            response_buffer_size: config.response_buffer_size,
         };
 
         let fut = Handler::new(conn, rx, handler_config);
@@ -386,6 +398,11 @@ impl Browser {
         self.is_incognito_configured() || self.browser_context.is_incognito()
     }
 
+    /// The browser context new pages are created in
+    pub fn browser_context(&self) -> &BrowserContext {
+        &self.browser_context
+    }
+
     /// The config of the spawned chromium instance if any.
     pub fn config(&self) -> Option<&BrowserConfig> {
         self.config.as_ref()
@@ -467,12 +484,26 @@ impl Browser {
     }
 
     /// Creates a new empty browser context.
+    ///
+    /// The permissions of `BrowserConfigBuilder::permissions` are granted in
+    /// the new context.
     pub async fn create_browser_context(
         &self,
         params: CreateBrowserContextParams,
     ) -> Result<BrowserContextId> {
         let response = self.execute(params).await?;
-        Ok(response.result.browser_context_id)
+        let browser_context_id = response.result.browser_context_id;
+        if let Some(config) = self.config.as_ref() {
+            if !config.permissions.is_empty() {
+                self.grant_permissions_to(
+                    Some(browser_context_id.clone()),
+                    None,
+                    config.permissions.clone(),
+                )
+                .await?;
+            }
+        }
+        Ok(browser_context_id)
     }
 
     /// Deletes a browser context.
@@ -486,6 +517,158 @@ impl Browser {
         Ok(())
     }
 
+    /// Grants the permissions to the `origin`, e.g. `https://example.com`, in
+    /// the context of the browser, so that prompts for them resolve without
+    /// user interaction. Permissions that are not listed are denied.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::browser::Browser;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide_cdp::cdp::browser_protocol::browser::PermissionType;
+    /// # async fn demo(browser: Browser) -> Result<()> {
+    ///     browser
+    ///         .grant_permissions(
+    ///             "https://example.com",
+    ///             [PermissionType::Geolocation, PermissionType::Notifications],
+    ///         )
+    ///         .await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn grant_permissions(
+        &self,
+        origin: impl Into<String>,
+        permissions: impl IntoIterator<Item = PermissionType>,
+    ) -> Result<&Self> {
+        self.grant_permissions_to(
+            self.browser_context.id().cloned(),
+            Some(origin.into()),
+            permissions,
+        )
+        .await
+    }
+
+    /// Grants the permissions to all origins in the context of the browser
+    pub async fn grant_permissions_for_all_origins(
+        &self,
+        permissions: impl IntoIterator<Item = PermissionType>,
+    ) -> Result<&Self> {
+        self.grant_permissions_to(self.browser_context.id().cloned(), None, permissions)
+            .await
+    }
+
+    /// Grants the permissions to the `origin` in the browser context with the
+    /// id, like one created with `Browser::create_browser_context`
+    pub async fn grant_permissions_in_context(
+        &self,
+        browser_context_id: impl Into<BrowserContextId>,
+        origin: impl Into<String>,
+        permissions: impl IntoIterator<Item = PermissionType>,
+    ) -> Result<&Self> {
+        self.grant_permissions_to(
+            Some(browser_context_id.into()),
+            Some(origin.into()),
+            permissions,
+        )
+        .await
+    }
+
+    pub(crate) async fn grant_permissions_to(
+        &self,
+        browser_context_id: Option<BrowserContextId>,
+        origin: Option<String>,
+        permissions: impl IntoIterator<Item = PermissionType>,
+    ) -> Result<&Self> {
+        self.execute(GrantPermissionsParams {
+            permissions: permissions.into_iter().collect(),
+            origin,
+            browser_context_id,
+        })
+        .await?;
+        Ok(self)
+    }
+
+    /// Overrides the setting of a single permission, like `geolocation` or
+    /// `clipboard-read`, for the `origin` in the context of the browser
+    pub async fn set_permission(
+        &self,
+        origin: impl Into<String>,
+        permission: impl Into<PermissionDescriptor>,
+        setting: PermissionSetting,
+    ) -> Result<&Self> {
+        self.set_permission_to(
+            self.browser_context.id().cloned(),
+            origin.into(),
+            permission.into(),
+            setting,
+        )
+        .await
+    }
+
+    /// Overrides the setting of a single permission for the `origin` in the
+    /// browser context with the id
+    pub async fn set_permission_in_context(
+        &self,
+        browser_context_id: impl Into<BrowserContextId>,
+        origin: impl Into<String>,
+        permission: impl Into<PermissionDescriptor>,
+        setting: PermissionSetting,
+    ) -> Result<&Self> {
+        self.set_permission_to(
+            Some(browser_context_id.into()),
+            origin.into(),
+            permission.into(),
+            setting,
+        )
+        .await
+    }
+
+    async fn set_permission_to(
+        &self,
+        browser_context_id: Option<BrowserContextId>,
+        origin: String,
+        permission: PermissionDescriptor,
+        setting: PermissionSetting,
+    ) -> Result<&Self> {
+        self.execute(SetPermissionParams {
+            permission,
+            setting,
+            origin: Some(origin),
+            embedding_origin: None,
+            browser_context_id,
+        })
+        .await?;
+        Ok(self)
+    }
+
+    /// Resets all permissions that were granted or set in the context of the
+    /// browser
+    pub async fn clear_permissions(&self) -> Result<&Self> {
+        self.clear_permissions_of(self.browser_context.id().cloned())
+            .await
+    }
+
+    /// Resets all permissions that were granted or set in the browser context
+    /// with the id
+    pub async fn clear_permissions_in_context(
+        &self,
+        browser_context_id: impl Into<BrowserContextId>,
+    ) -> Result<&Self> {
+        self.clear_permissions_of(Some(browser_context_id.into()))
+            .await
+    }
+
+    pub(crate) async fn clear_permissions_of(
+        &self,
+        browser_context_id: Option<BrowserContextId>,
+    ) -> Result<&Self> {
+        self.execute(ResetPermissionsParams { browser_context_id })
+            .await?;
+        Ok(self)
+    }
+
     /// Clears cookies.
     pub async fn clear_cookies(&self) -> Result<()> {
         self.execute(ClearCookiesParams::default()).await?;
@@ -512,6 +695,34 @@ impl Browser {
         self.execute(SetCookiesParams::new(cookies)).await?;
         Ok(self)
     }
+
+    /// Sets the cookies of a file, either in the Netscape `cookies.txt`
+    /// format or, if the file has a `.json` extension, in the JSON form of
+    /// [`CookieRecord`]s.
+    pub async fn import_cookies(&self, path: impl AsRef<Path>) -> Result<&Self> {
+        let path = path.as_ref();
+        let content = String::from_utf8(utils::read(path).await?)
+            .map_err(|_| CdpError::msg("Cookie file is not valid utf-8"))?;
+        let cookies = CookieFormat::from_path(path).parse(&content)?;
+        self.set_cookies(cookies.into_iter().map(Into::into).collect())
+            .await
+    }
+
+    /// Writes all browser cookies to a file, in the Netscape `cookies.txt`
+    /// format or, if the file has a `.json` extension, in the JSON form of
+    /// [`CookieRecord`]s.
+    pub async fn export_cookies(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let cookies = self
+            .get_cookies()
+            .await?
+            .iter()
+            .map(CookieRecord::from)
+            .collect::<Vec<_>>();
+        let content = CookieFormat::from_path(path).format(&cookies)?;
+        utils::write(path, content).await?;
+        Ok(())
+    }
 }
 
 impl Drop for Browser {
@@ -661,6 +872,29 @@ pub struct BrowserConfig {
     /// Whether to enable cache
     pub cache_enabled: bool,
 
+    /// Whether to emit console messages and page errors as `tracing` events
+    pub trace_console: bool,
+
+    /// Additional HTTP headers sent with every request of every page
+    pub extra_headers: HashMap<String, String>,
+
+    /// Url patterns that are blocked for every page, `*` is a wildcard
+    pub blocked_urls: Vec<String>,
+
+    /// Requests of every page matching any of these filters are blocked
+    pub resource_filters: Vec<ResourceFilter>,
+
+    /// Requests of every page blocked by the adblock engine are aborted
+    pub adblock: Option<AdblockOptions>,
+
+    /// Permissions granted to all origins of the browser and of incognito
+    /// contexts started with `Browser::start_incognito_context`
+    pub permissions: Vec<PermissionType>,
+
+    /// The size of the buffer in which chromium keeps the response bodies of
+    /// every page, see `BrowserConfigBuilder::response_buffer_size`
+    pub response_buffer_size: Option<usize>,
+
     /// Avoid easy bot detection by setting `navigator.webdriver` to false
     hidden: bool,
 }
@@ -686,6 +920,13 @@ pub struct BrowserConfigBuilder {
     disable_default_args: bool,
     request_intercept: bool,
     cache_enabled: bool,
+    trace_console: bool,
+    extra_headers: HashMap<String, String>,
+    blocked_urls: Vec<String>,
+    resource_filters: Vec<ResourceFilter>,
+    adblock: Option<AdblockOptions>,
+    permissions: Vec<PermissionType>,
+    response_buffer_size: Option<usize>,
     hidden: bool,
 }
 
@@ -721,6 +962,13 @@ impl Default for BrowserConfigBuilder {
             disable_default_args: false,
             request_intercept: false,
             cache_enabled: true,
+            trace_console: false,
+            extra_headers: Default::default(),
+            blocked_urls: Vec::new(),
+            resource_filters: Vec::new(),
+            adblock: None,
+            permissions: Vec::new(),
+            response_buffer_size: None,
             hidden: false,
         }
     }
@@ -885,6 +1133,79 @@ impl BrowserConfigBuilder {
         self
     }
 
+    /// Emits the console messages and uncaught errors of all pages as
+    /// `tracing` events with the targets `chromiumoxide::console` and
+    /// `chromiumoxide::page_error`.
+    pub fn trace_console(mut self) -> Self {
+        self.trace_console = true;
+        self
+    }
+
+    /// Sends the given HTTP header with every request of every page
+    pub fn extra_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra_headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sends the given HTTP headers with every request of every page
+    pub fn extra_headers(
+        mut self,
+        headers: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.extra_headers.extend(
+            headers
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into())),
+        );
+        self
+    }
+
+    /// Blocks all requests of every page whose url matches the pattern, in
+    /// which `*` is a wildcard
+    pub fn block_url(mut self, pattern: impl Into<String>) -> Self {
+        self.blocked_urls.push(pattern.into());
+        self
+    }
+
+    /// Keeps up to `size` bytes of the response bodies of every page in
+    /// chromium's buffers, also across cross-process navigations.
+    ///
+    /// Chromium evicts the oldest bodies once its default buffers are full,
+    /// after which `Page::response_body` fails for them, so pages loading
+    /// many or large resources should use a buffer large enough for the
+    /// bodies that are read.
+    pub fn response_buffer_size(mut self, size: usize) -> Self {
+        self.response_buffer_size = Some(size);
+        self
+    }
+
+    /// Blocks all requests of every page that match the filter, like all
+    /// images or fonts
+    pub fn resource_filter(mut self, filter: impl Into<ResourceFilter>) -> Self {
+        self.resource_filters.push(filter.into());
+        self
+    }
+
+    /// Blocks all requests of every page that the adblock engine blocks and
+    /// optionally hides ads via element hiding filters
+    pub fn adblock(mut self, adblock: impl Into<AdblockOptions>) -> Self {
+        self.adblock = Some(adblock.into());
+        self
+    }
+
+    /// Grants the permission to all origins, e.g. so that geolocation or
+    /// notification prompts resolve in headless mode
+    pub fn permission(mut self, permission: PermissionType) -> Self {
+        self.permissions.push(permission);
+        self
+    }
+
+    /// Grants the permissions to all origins
+    pub fn permissions(mut self, permissions: impl IntoIterator<Item = PermissionType>) -> Self {
+        self.permissions.extend(permissions);
+        self
+    }
+
     pub fn hide(mut self) -> Self {
         self.hidden = true;
         self
@@ -916,6 +1237,13 @@ impl BrowserConfigBuilder {
             disable_default_args: self.disable_default_args,
             request_intercept: self.request_intercept,
             cache_enabled: self.cache_enabled,
+            trace_console: self.trace_console,
+            extra_headers: self.extra_headers,
+            blocked_urls: self.blocked_urls,
+            resource_filters: self.resource_filters,
+            adblock: self.adblock,
+            permissions: self.permissions,
+            response_buffer_size: self.response_buffer_size,
             hidden: self.hidden,
         })
     }
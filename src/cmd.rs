@@ -42,6 +42,11 @@ pub struct CommandMessage<T = Result<Response>> {
     pub params: serde_json::Value,
     #[serde(skip_serializing)]
     pub sender: OneshotSender<T>,
+    /// How long to wait for the response before failing with
+    /// `CdpError::Timeout`, the handler's request timeout if `None`.
+    /// `Duration::MAX` waits forever.
+    #[serde(skip_serializing)]
+    pub timeout: Option<Duration>,
 }
 
 impl<T> CommandMessage<T> {
@@ -51,9 +56,16 @@ impl<T> CommandMessage<T> {
             session_id: None,
             params: serde_json::to_value(cmd)?,
             sender,
+            timeout: None,
         })
     }
 
+    /// Overrides the handler's request timeout for this command
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     /// Whether this command is a navigation
     pub fn is_navigation(&self) -> bool {
         self.method.as_ref() == NavigateParams::IDENTIFIER
@@ -69,6 +81,7 @@ impl<T> CommandMessage<T> {
             session_id,
             params: serde_json::to_value(cmd)?,
             sender,
+            timeout: None,
         })
     }
 
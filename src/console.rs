@@ -0,0 +1,476 @@
+//! Typed console messages and uncaught page errors.
+
+use std::fmt;
+
+use chromiumoxide_cdp::cdp::js_protocol::runtime::{
+    ConsoleApiCalledType, EventConsoleApiCalled, EventExceptionThrown, ObjectPreview,
+    ObjectPreviewSubtype, PropertyPreview, PropertyPreviewSubtype, PropertyPreviewType,
+    RemoteObject, RemoteObjectSubtype, RemoteObjectType, StackTrace,
+};
+
+/// The severity of a console message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConsoleLevel {
+    Debug,
+    Log,
+    Info,
+    Warning,
+    Error,
+}
+
+impl From<&ConsoleApiCalledType> for ConsoleLevel {
+    fn from(kind: &ConsoleApiCalledType) -> Self {
+        match kind {
+            ConsoleApiCalledType::Debug => ConsoleLevel::Debug,
+            ConsoleApiCalledType::Info => ConsoleLevel::Info,
+            ConsoleApiCalledType::Warning => ConsoleLevel::Warning,
+            ConsoleApiCalledType::Error | ConsoleApiCalledType::Assert => ConsoleLevel::Error,
+            _ => ConsoleLevel::Log,
+        }
+    }
+}
+
+/// Where in the page's scripts a console message or error originated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub url: String,
+    /// 0-based line number
+    pub line_number: i64,
+    /// 0-based column number
+    pub column_number: i64,
+}
+
+impl SourceLocation {
+    fn from_stack(stack: &StackTrace) -> Option<Self> {
+        stack.call_frames.first().map(|frame| Self {
+            url: frame.url.clone(),
+            line_number: frame.line_number,
+            column_number: frame.column_number,
+        })
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.url,
+            self.line_number + 1,
+            self.column_number + 1
+        )
+    }
+}
+
+/// A message logged via the page's `console` API
+#[derive(Debug, Clone)]
+pub struct ConsoleMessage {
+    /// The severity of the message
+    pub level: ConsoleLevel,
+    /// The `console` function that was called
+    pub kind: ConsoleApiCalledType,
+    /// The message as rendered by the browser's console, with format
+    /// specifiers like `%s` applied
+    pub text: String,
+    /// The arguments of the call as JSON, objects are resolved from their
+    /// previews
+    pub args: Vec<serde_json::Value>,
+    /// Where the message was logged
+    pub location: Option<SourceLocation>,
+    /// The stack trace of the call, if available
+    pub stack: Option<StackTrace>,
+}
+
+impl ConsoleMessage {
+    /// Emits this message as `tracing` event with a matching level
+    pub fn trace(&self) {
+        let location = self
+            .location
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        match self.level {
+            ConsoleLevel::Debug => {
+                tracing::debug!(target: "chromiumoxide::console", %location, "{}", self.text)
+            }
+            ConsoleLevel::Log | ConsoleLevel::Info => {
+                tracing::info!(target: "chromiumoxide::console", %location, "{}", self.text)
+            }
+            ConsoleLevel::Warning => {
+                tracing::warn!(target: "chromiumoxide::console", %location, "{}", self.text)
+            }
+            ConsoleLevel::Error => {
+                tracing::error!(target: "chromiumoxide::console", %location, "{}", self.text)
+            }
+        }
+    }
+}
+
+impl From<&EventConsoleApiCalled> for ConsoleMessage {
+    fn from(event: &EventConsoleApiCalled) -> Self {
+        Self {
+            level: ConsoleLevel::from(&event.r#type),
+            kind: event.r#type.clone(),
+            text: format_console_args(&event.args),
+            args: event.args.iter().map(remote_object_to_json).collect(),
+            location: event
+                .stack_trace
+                .as_ref()
+                .and_then(SourceLocation::from_stack),
+            stack: event.stack_trace.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ConsoleMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// An uncaught exception thrown in the page
+#[derive(Debug, Clone)]
+pub struct PageError {
+    /// The class of the thrown value, like `TypeError`
+    pub name: Option<String>,
+    /// The message of the exception
+    pub message: String,
+    /// Where the exception was thrown
+    pub location: Option<SourceLocation>,
+    /// The stack trace of the exception, if available
+    pub stack: Option<StackTrace>,
+}
+
+impl PageError {
+    /// Emits this error as `tracing` event
+    pub fn trace(&self) {
+        let location = self
+            .location
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        tracing::error!(target: "chromiumoxide::page_error", %location, "{}", self);
+    }
+}
+
+impl From<&EventExceptionThrown> for PageError {
+    fn from(event: &EventExceptionThrown) -> Self {
+        let details = &event.exception_details;
+        let exception = details.exception.as_ref();
+        let is_error = exception
+            .and_then(|obj| obj.subtype.as_ref())
+            .map(|subtype| subtype == &RemoteObjectSubtype::Error)
+            .unwrap_or_default();
+
+        let (name, message) = match exception {
+            Some(obj) if is_error => {
+                // the description of an error is its `stack`: `<name>: <message>\n    at ...`
+                let description = obj.description.as_deref().unwrap_or(&details.text);
+                let first_line = description.lines().next().unwrap_or_default();
+                let name = obj.class_name.clone();
+                let message = name
+                    .as_deref()
+                    .and_then(|name| first_line.strip_prefix(name))
+                    .and_then(|rest| rest.strip_prefix(": "))
+                    .unwrap_or(first_line)
+                    .to_string();
+                (name, message)
+            }
+            Some(obj) => (None, format_arg(obj)),
+            None => (None, details.text.clone()),
+        };
+
+        let location = details
+            .stack_trace
+            .as_ref()
+            .and_then(SourceLocation::from_stack)
+            .or_else(|| {
+                details.url.clone().map(|url| SourceLocation {
+                    url,
+                    line_number: details.line_number,
+                    column_number: details.column_number,
+                })
+            });
+
+        Self {
+            name,
+            message,
+            location,
+            stack: details.stack_trace.clone(),
+        }
+    }
+}
+
+impl fmt::Display for PageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = self.name.as_ref() {
+            write!(f, "{name}: ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for PageError {}
+
+/// Renders the arguments of a console call like the browser's console,
+/// applying the format specifiers of a leading string argument.
+fn format_console_args(args: &[RemoteObject]) -> String {
+    let mut parts = Vec::with_capacity(args.len());
+    let mut rest = args.iter();
+
+    if let Some(first) = args.first() {
+        if first.r#type == RemoteObjectType::String {
+            rest.next();
+            let format = first
+                .value
+                .as_ref()
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            let mut out = String::with_capacity(format.len());
+            let mut chars = format.chars().peekable();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    out.push(c);
+                    continue;
+                }
+                match chars.peek().copied() {
+                    Some('%') => {
+                        chars.next();
+                        out.push('%');
+                    }
+                    Some(spec @ ('s' | 'd' | 'i' | 'f' | 'o' | 'O' | 'c')) => match rest.next() {
+                        Some(arg) => {
+                            chars.next();
+                            match spec {
+                                'd' | 'i' => out.push_str(&format_number(arg, true)),
+                                'f' => out.push_str(&format_number(arg, false)),
+                                // styles are not rendered
+                                'c' => {}
+                                _ => out.push_str(&format_arg(arg)),
+                            }
+                        }
+                        None => out.push('%'),
+                    },
+                    _ => out.push('%'),
+                }
+            }
+            parts.push(out);
+        }
+    }
+
+    parts.extend(rest.map(format_arg));
+    parts.join(" ")
+}
+
+fn format_number(arg: &RemoteObject, integer: bool) -> String {
+    match arg.value.as_ref().and_then(|v| v.as_f64()) {
+        Some(n) if integer => format!("{}", n.trunc() as i64),
+        Some(n) => n.to_string(),
+        None => "NaN".to_string(),
+    }
+}
+
+/// Renders a single remote object as string
+fn format_arg(arg: &RemoteObject) -> String {
+    if let Some(value) = arg.value.as_ref() {
+        return match value {
+            serde_json::Value::String(s) => s.clone(),
+            value => value.to_string(),
+        };
+    }
+    if let Some(value) = arg.unserializable_value.as_ref() {
+        return value.inner().clone();
+    }
+    if arg.r#type == RemoteObjectType::Undefined {
+        return "undefined".to_string();
+    }
+    let is_plain = matches!(arg.subtype, None | Some(RemoteObjectSubtype::Array));
+    match arg.preview.as_ref() {
+        Some(preview) if arg.r#type == RemoteObjectType::Object && is_plain => {
+            format_preview(preview)
+        }
+        _ => arg.description.clone().unwrap_or_default(),
+    }
+}
+
+fn format_preview(preview: &ObjectPreview) -> String {
+    let overflow = if preview.overflow { ", …" } else { "" };
+    if preview.subtype == Some(ObjectPreviewSubtype::Array) {
+        let items = preview
+            .properties
+            .iter()
+            .map(format_property)
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("[{items}{overflow}]")
+    } else {
+        let items = preview
+            .properties
+            .iter()
+            .map(|prop| format!("{}: {}", prop.name, format_property(prop)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{{{items}{overflow}}}")
+    }
+}
+
+fn format_property(prop: &PropertyPreview) -> String {
+    if let Some(preview) = prop.value_preview.as_ref() {
+        return format_preview(preview);
+    }
+    let value = prop.value.clone().unwrap_or_default();
+    match prop.r#type {
+        PropertyPreviewType::String => format!("'{value}'"),
+        PropertyPreviewType::Undefined => "undefined".to_string(),
+        _ => value,
+    }
+}
+
+/// Converts a remote object to JSON, resolving objects from their previews
+fn remote_object_to_json(arg: &RemoteObject) -> serde_json::Value {
+    if let Some(value) = arg.value.as_ref() {
+        return value.clone();
+    }
+    if let Some(value) = arg.unserializable_value.as_ref() {
+        return value.inner().clone().into();
+    }
+    if arg.r#type == RemoteObjectType::Undefined {
+        return serde_json::Value::Null;
+    }
+    match arg.preview.as_ref() {
+        Some(preview) if arg.r#type == RemoteObjectType::Object => preview_to_json(preview),
+        _ => arg.description.clone().map(Into::into).unwrap_or_default(),
+    }
+}
+
+fn preview_to_json(preview: &ObjectPreview) -> serde_json::Value {
+    match preview.subtype {
+        Some(ObjectPreviewSubtype::Array) => preview
+            .properties
+            .iter()
+            .map(property_to_json)
+            .collect::<Vec<_>>()
+            .into(),
+        Some(ObjectPreviewSubtype::Null) => serde_json::Value::Null,
+        None => preview
+            .properties
+            .iter()
+            .map(|prop| (prop.name.clone(), property_to_json(prop)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        _ => preview
+            .description
+            .clone()
+            .map(Into::into)
+            .unwrap_or_default(),
+    }
+}
+
+fn property_to_json(prop: &PropertyPreview) -> serde_json::Value {
+    if let Some(preview) = prop.value_preview.as_ref() {
+        return preview_to_json(preview);
+    }
+    let value = prop.value.clone().unwrap_or_default();
+    match prop.r#type {
+        PropertyPreviewType::String => value.into(),
+        PropertyPreviewType::Undefined => serde_json::Value::Null,
+        PropertyPreviewType::Number | PropertyPreviewType::Boolean => {
+            serde_json::from_str(&value).unwrap_or(value.into())
+        }
+        PropertyPreviewType::Object if prop.subtype == Some(PropertyPreviewSubtype::Null) => {
+            serde_json::Value::Null
+        }
+        _ => value.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote(value: serde_json::Value) -> RemoteObject {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn formats_specifiers() {
+        let args = vec![
+            remote(serde_json::json!({"type": "string", "value": "%s has %d items (%c%%)"})),
+            remote(serde_json::json!({"type": "string", "value": "cart"})),
+            remote(serde_json::json!({"type": "number", "value": 3.7})),
+            remote(serde_json::json!({"type": "string", "value": "color: red"})),
+            remote(serde_json::json!({"type": "boolean", "value": true})),
+        ];
+        assert_eq!(format_console_args(&args), "cart has 3 items (%) true");
+    }
+
+    #[test]
+    fn formats_missing_specifier_args() {
+        let args = vec![remote(
+            serde_json::json!({"type": "string", "value": "%s and %o"}),
+        )];
+        assert_eq!(format_console_args(&args), "%s and %o");
+    }
+
+    #[test]
+    fn resolves_object_previews() {
+        let obj = remote(serde_json::json!({
+            "type": "object",
+            "className": "Object",
+            "description": "Object",
+            "objectId": "1",
+            "preview": {
+                "type": "object",
+                "description": "Object",
+                "overflow": false,
+                "properties": [
+                    {"name": "a", "type": "number", "value": "1"},
+                    {"name": "b", "type": "string", "value": "x"},
+                    {"name": "c", "type": "object", "value": "Array(2)", "subtype": "array",
+                     "valuePreview": {
+                        "type": "object", "subtype": "array", "description": "Array(2)",
+                        "overflow": false,
+                        "properties": [
+                            {"name": "0", "type": "boolean", "value": "true"},
+                            {"name": "1", "type": "object", "value": "null", "subtype": "null"}
+                        ]
+                     }}
+                ]
+            }
+        }));
+        assert_eq!(format_arg(&obj), "{a: 1, b: 'x', c: [true, null]}");
+        assert_eq!(
+            remote_object_to_json(&obj),
+            serde_json::json!({"a": 1, "b": "x", "c": [true, null]})
+        );
+    }
+
+    #[test]
+    fn page_error_from_exception() {
+        let event: EventExceptionThrown = serde_json::from_value(serde_json::json!({
+            "timestamp": 0.0,
+            "exceptionDetails": {
+                "exceptionId": 1,
+                "text": "Uncaught",
+                "lineNumber": 4,
+                "columnNumber": 10,
+                "url": "https://example.com/app.js",
+                "exception": {
+                    "type": "object",
+                    "subtype": "error",
+                    "className": "TypeError",
+                    "description": "TypeError: x is not a function\n    at https://example.com/app.js:5:11"
+                }
+            }
+        }))
+        .unwrap();
+        let error = PageError::from(&event);
+        assert_eq!(error.name.as_deref(), Some("TypeError"));
+        assert_eq!(error.message, "x is not a function");
+        assert_eq!(error.to_string(), "TypeError: x is not a function");
+        assert_eq!(
+            error.location.unwrap().to_string(),
+            "https://example.com/app.js:5:11"
+        );
+    }
+}
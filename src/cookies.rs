@@ -0,0 +1,331 @@
+//! Conversions between protocol cookies and the Netscape `cookies.txt` format
+//! used by curl and wget, as well as a stable JSON form.
+//!
+//! Both formats distinguish host-only cookies, which are only sent to the
+//! host that set them, from domain cookies, which are also sent to its
+//! subdomains. In the protocol, the domain of a domain cookie starts with a
+//! `.`, while host-only cookies are set via their url.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    Cookie, CookieParam, CookieSameSite, TimeSinceEpoch,
+};
+
+use crate::error::{CdpError, Result};
+
+/// The first line of a Netscape cookie file
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File";
+
+/// curl's prefix for the domain of http-only cookies
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// The format of a cookie file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieFormat {
+    /// The tab separated `cookies.txt` format of curl and wget
+    Netscape,
+    /// A JSON array of [`CookieRecord`]s
+    Json,
+}
+
+impl CookieFormat {
+    /// `Json` for files with a `.json` extension, `Netscape` otherwise
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => CookieFormat::Json,
+            _ => CookieFormat::Netscape,
+        }
+    }
+
+    /// Parses the cookies of a file in this format
+    pub fn parse(&self, content: &str) -> Result<Vec<CookieRecord>> {
+        match self {
+            CookieFormat::Netscape => from_netscape(content),
+            CookieFormat::Json => Ok(serde_json::from_str(content)?),
+        }
+    }
+
+    /// Formats the cookies as the content of a file in this format
+    pub fn format(&self, cookies: &[CookieRecord]) -> Result<String> {
+        match self {
+            CookieFormat::Netscape => Ok(to_netscape(cookies)),
+            CookieFormat::Json => Ok(serde_json::to_string_pretty(cookies)?),
+        }
+    }
+}
+
+/// A cookie in a form that is independent of the protocol version, which is
+/// serialized as the JSON form.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CookieRecord {
+    pub name: String,
+    pub value: String,
+    /// The domain without a leading `.`
+    pub domain: String,
+    /// Whether the cookie is only sent to the `domain` itself and not to its
+    /// subdomains
+    #[serde(default)]
+    pub host_only: bool,
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    /// The expiry in seconds since the unix epoch, `None` for session
+    /// cookies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub same_site: Option<SameSite>,
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+/// The `SameSite` attribute of a cookie
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl From<&CookieSameSite> for SameSite {
+    fn from(same_site: &CookieSameSite) -> Self {
+        match same_site {
+            CookieSameSite::Strict => SameSite::Strict,
+            CookieSameSite::Lax => SameSite::Lax,
+            CookieSameSite::None => SameSite::None,
+        }
+    }
+}
+
+impl From<SameSite> for CookieSameSite {
+    fn from(same_site: SameSite) -> Self {
+        match same_site {
+            SameSite::Strict => CookieSameSite::Strict,
+            SameSite::Lax => CookieSameSite::Lax,
+            SameSite::None => CookieSameSite::None,
+        }
+    }
+}
+
+impl From<&Cookie> for CookieRecord {
+    fn from(cookie: &Cookie) -> Self {
+        let (domain, host_only) = match cookie.domain.strip_prefix('.') {
+            Some(domain) => (domain.to_string(), false),
+            None => (cookie.domain.clone(), true),
+        };
+        Self {
+            name: cookie.name.clone(),
+            value: cookie.value.clone(),
+            domain,
+            host_only,
+            path: cookie.path.clone(),
+            secure: cookie.secure,
+            http_only: cookie.http_only,
+            expires: (!cookie.session && cookie.expires > 0.).then_some(cookie.expires as i64),
+            same_site: cookie.same_site.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl From<Cookie> for CookieRecord {
+    fn from(cookie: Cookie) -> Self {
+        Self::from(&cookie)
+    }
+}
+
+impl From<CookieRecord> for CookieParam {
+    fn from(cookie: CookieRecord) -> Self {
+        let mut param = CookieParam::new(cookie.name, cookie.value);
+        if cookie.host_only {
+            // setting the domain would turn it into a domain cookie
+            let scheme = if cookie.secure { "https" } else { "http" };
+            param.url = Some(format!("{scheme}://{}{}", cookie.domain, cookie.path));
+        } else {
+            param.domain = Some(format!(".{}", cookie.domain));
+        }
+        param.path = Some(cookie.path);
+        param.secure = Some(cookie.secure);
+        param.http_only = Some(cookie.http_only);
+        param.expires = cookie
+            .expires
+            .map(|expires| TimeSinceEpoch::new(expires as f64));
+        param.same_site = cookie.same_site.map(Into::into);
+        param
+    }
+}
+
+/// Parses the cookies of a Netscape `cookies.txt` file.
+///
+/// Comments and empty lines are skipped, except for the `#HttpOnly_` prefix
+/// curl uses to mark http-only cookies.
+pub fn from_netscape(content: &str) -> Result<Vec<CookieRecord>> {
+    let mut cookies = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || CdpError::msg(format!("Invalid cookie on line {}", idx + 1));
+        let fields = line.split('\t').collect::<Vec<_>>();
+        if fields.len() < 6 || fields.len() > 7 {
+            return Err(invalid());
+        }
+        let (domain, include_subdomains, path, secure, expires, name) = (
+            fields[0], fields[1], fields[2], fields[3], fields[4], fields[5],
+        );
+        // the value may be missing entirely if it is empty
+        let value = fields.get(6).copied().unwrap_or_default();
+        let include_subdomains = parse_flag(include_subdomains).ok_or_else(invalid)?;
+        let expires = expires.trim().parse::<i64>().map_err(|_| invalid())?;
+        cookies.push(CookieRecord {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain: domain.trim_start_matches('.').to_string(),
+            host_only: !include_subdomains,
+            path: path.to_string(),
+            secure: parse_flag(secure).ok_or_else(invalid)?,
+            http_only,
+            // `0` marks session cookies
+            expires: (expires > 0).then_some(expires),
+            same_site: None,
+        });
+    }
+    Ok(cookies)
+}
+
+/// Formats the cookies as a Netscape `cookies.txt` file.
+///
+/// The format has no field for `SameSite`, which is therefore lost.
+pub fn to_netscape(cookies: &[CookieRecord]) -> String {
+    let mut content = format!("{NETSCAPE_HEADER}\n\n");
+    for cookie in cookies {
+        let domain = if cookie.host_only {
+            cookie.domain.clone()
+        } else {
+            format!(".{}", cookie.domain)
+        };
+        content.push_str(&format!(
+            "{}{domain}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            if cookie.http_only {
+                HTTP_ONLY_PREFIX
+            } else {
+                ""
+            },
+            format_flag(!cookie.host_only),
+            cookie.path,
+            format_flag(cookie.secure),
+            cookie.expires.unwrap_or_default(),
+            cookie.name,
+            cookie.value
+        ));
+    }
+    content
+}
+
+fn parse_flag(flag: &str) -> Option<bool> {
+    match flag.trim() {
+        f if f.eq_ignore_ascii_case("true") => Some(true),
+        f if f.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    }
+}
+
+fn format_flag(flag: bool) -> &'static str {
+    if flag {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOKIES_TXT: &str = "# Netscape HTTP Cookie File
+# https://curl.se/docs/http-cookies.html
+
+.example.com\tTRUE\t/\tFALSE\t1893456000\tsession_id\tabc123
+#HttpOnly_www.example.com\tFALSE\t/account\tTRUE\t0\ttoken\txyz
+shop.example\tFALSE\t/\tFALSE\t0\tempty
+";
+
+    #[test]
+    fn parses_netscape() {
+        let cookies = from_netscape(COOKIES_TXT).unwrap();
+        assert_eq!(cookies.len(), 3);
+
+        assert_eq!(cookies[0].domain, "example.com");
+        assert!(!cookies[0].host_only);
+        assert!(!cookies[0].http_only);
+        assert_eq!(cookies[0].expires, Some(1893456000));
+
+        assert_eq!(cookies[1].domain, "www.example.com");
+        assert!(cookies[1].host_only);
+        assert!(cookies[1].http_only);
+        assert!(cookies[1].secure);
+        assert_eq!(cookies[1].path, "/account");
+        assert_eq!(cookies[1].expires, None);
+
+        assert_eq!(cookies[2].name, "empty");
+        assert_eq!(cookies[2].value, "");
+    }
+
+    #[test]
+    fn rejects_invalid_netscape() {
+        assert!(from_netscape("example.com\tTRUE\t/\n").is_err());
+        assert!(from_netscape("example.com\tYES\t/\tFALSE\t0\tname\tvalue\n").is_err());
+    }
+
+    #[test]
+    fn netscape_round_trip() {
+        let cookies = from_netscape(COOKIES_TXT).unwrap();
+        let content = to_netscape(&cookies);
+        assert!(content.starts_with(NETSCAPE_HEADER));
+        assert!(
+            content.contains("#HttpOnly_www.example.com\tFALSE\t/account\tTRUE\t0\ttoken\txyz\n")
+        );
+        assert_eq!(from_netscape(&content).unwrap(), cookies);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let json =
+            r#"[{"name":"a","value":"1","domain":"example.com","hostOnly":true,"sameSite":"Lax"}]"#;
+        let cookies = CookieFormat::Json.parse(json).unwrap();
+        assert_eq!(cookies[0].path, "/");
+        assert_eq!(cookies[0].same_site, Some(SameSite::Lax));
+        let json = CookieFormat::Json.format(&cookies).unwrap();
+        assert_eq!(CookieFormat::Json.parse(&json).unwrap(), cookies);
+    }
+
+    #[test]
+    fn converts_to_params() {
+        let cookies = from_netscape(COOKIES_TXT).unwrap();
+        let domain = CookieParam::from(cookies[0].clone());
+        assert_eq!(domain.domain.as_deref(), Some(".example.com"));
+        assert_eq!(domain.url, None);
+        assert_eq!(domain.expires, Some(TimeSinceEpoch::new(1893456000.)));
+
+        let host_only = CookieParam::from(cookies[1].clone());
+        assert_eq!(host_only.domain, None);
+        assert_eq!(
+            host_only.url.as_deref(),
+            Some("https://www.example.com/account")
+        );
+        assert_eq!(host_only.expires, None);
+    }
+}
@@ -20,6 +20,7 @@ use chromiumoxide_cdp::cdp::js_protocol::runtime::{
 
 use crate::error::{CdpError, Result};
 use crate::handler::PageInner;
+use crate::js::{JsArg, JsHandle};
 use crate::layout::{BoundingBox, BoxModel, ElementQuad, Point};
 use crate::utils;
 
@@ -32,6 +33,8 @@ pub struct Element {
     pub backend_node_id: BackendNodeId,
     /// The identifier of the node this element represents.
     pub node_id: NodeId,
+    /// Releases the remote object once the element is dropped
+    handle: JsHandle,
     tab: Arc<PageInner>,
 }
 
@@ -60,15 +63,23 @@ impl Element {
             .result
             .object
             .object_id
+            .clone()
             .ok_or_else(|| CdpError::msg(format!("No object Id found for {node_id:?}")))?;
+        let handle = JsHandle::new(Arc::clone(&tab), resp.result.object, None);
         Ok(Self {
             remote_object_id,
             backend_node_id,
             node_id,
+            handle,
             tab,
         })
     }
 
+    /// The handle to the JavaScript object of this element
+    pub fn as_js_handle(&self) -> &JsHandle {
+        &self.handle
+    }
+
     /// Convert a slice of `NodeId`s into a `Vec` of `Element`s
     pub(crate) async fn from_nodes(tab: &Arc<PageInner>, node_ids: &[NodeId]) -> Result<Vec<Self>> {
         future::join_all(
@@ -449,6 +460,18 @@ impl Element {
     }
 }
 
+impl From<Element> for JsHandle {
+    fn from(element: Element) -> Self {
+        element.handle
+    }
+}
+
+impl From<&Element> for JsArg {
+    fn from(element: &Element) -> Self {
+        JsArg::from(&element.handle)
+    }
+}
+
 pub type AttributeValueFuture<'a> = Option<(
     String,
     Pin<Box<dyn Future<Output = Result<Option<String>>> + 'a>>,
@@ -1,5 +1,6 @@
 use std::fmt;
 use std::io;
+use std::path::PathBuf;
 use std::process::ExitStatus;
 use std::time::Instant;
 
@@ -43,6 +44,10 @@ pub enum CdpError {
     LaunchIo(#[source] io::Error, BrowserStderr),
     #[error("Request timed out.")]
     Timeout,
+    #[error("Target crashed.")]
+    TargetCrashed,
+    #[error("Target closed.")]
+    TargetClosed,
     #[error("FrameId {0:?} not found.")]
     FrameNotFound(FrameId),
     /// Error message related to a cdp response that is not a
@@ -51,6 +56,9 @@ pub enum CdpError {
     ChromeMessage(String),
     #[error("{0}")]
     DecodeError(#[from] DecodeError),
+    /// An image, like a screenshot, could not be decoded or encoded
+    #[error("{0}")]
+    Image(#[source] Box<dyn std::error::Error + Send + Sync>),
     #[error("{0}")]
     ScrollingFailed(String),
     #[error("Requested value not found.")]
@@ -63,11 +71,20 @@ pub enum CdpError {
     Url(#[from] url::ParseError),
     #[error("{1}")]
     InvalidMessage(String, serde_json::Error),
+    #[error("Screenshot differs from the baseline in {diff_pixels} pixels, see {}", diff_path.display())]
+    ScreenshotMismatch {
+        diff_pixels: usize,
+        diff_path: PathBuf,
+    },
 }
 impl CdpError {
     pub fn msg(msg: impl Into<String>) -> Self {
         CdpError::ChromeMessage(msg.into())
     }
+
+    pub(crate) fn image(err: impl std::error::Error + Send + Sync + 'static) -> Self {
+        CdpError::Image(Box::new(err))
+    }
 }
 
 #[derive(Debug, Error)]
@@ -1,4 +1,7 @@
-use chromiumoxide_cdp::cdp::browser_protocol::browser::BrowserContextId;
+use chromiumoxide_cdp::cdp::browser_protocol::browser::{BrowserContextId, PermissionType};
+
+use crate::browser::Browser;
+use crate::error::Result;
 
 /// BrowserContexts provide a way to operate multiple independent browser
 /// sessions. When a browser is launched, it has a single BrowserContext used by
@@ -22,6 +25,58 @@ impl BrowserContext {
         self.id.as_ref()
     }
 
+    /// Grants the permissions to the `origin`, e.g. `https://example.com`, in
+    /// this context, so that prompts for them resolve without user
+    /// interaction. Permissions that are not listed are denied.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::browser::Browser;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide_cdp::cdp::browser_protocol::browser::PermissionType;
+    /// # async fn demo(browser: Browser) -> Result<()> {
+    ///     browser
+    ///         .browser_context()
+    ///         .grant_permissions(
+    ///             &browser,
+    ///             "https://example.com",
+    ///             [PermissionType::Geolocation, PermissionType::Notifications],
+    ///         )
+    ///         .await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn grant_permissions(
+        &self,
+        browser: &Browser,
+        origin: impl Into<String>,
+        permissions: impl IntoIterator<Item = PermissionType>,
+    ) -> Result<&Self> {
+        browser
+            .grant_permissions_to(self.id.clone(), Some(origin.into()), permissions)
+            .await?;
+        Ok(self)
+    }
+
+    /// Grants the permissions to all origins in this context
+    pub async fn grant_permissions_for_all_origins(
+        &self,
+        browser: &Browser,
+        permissions: impl IntoIterator<Item = PermissionType>,
+    ) -> Result<&Self> {
+        browser
+            .grant_permissions_to(self.id.clone(), None, permissions)
+            .await?;
+        Ok(self)
+    }
+
+    /// Resets all permissions that were granted or set in this context
+    pub async fn clear_permissions(&self, browser: &Browser) -> Result<&Self> {
+        browser.clear_permissions_of(self.id.clone()).await?;
+        Ok(self)
+    }
+
     pub(crate) fn take(&mut self) -> Option<BrowserContextId> {
         self.id.take()
     }
@@ -1,3 +1,4 @@
+use std::collections::hash_map::Entry as HashMapEntry;
 use std::collections::VecDeque;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -9,9 +10,9 @@ use chromiumoxide_cdp::cdp::browser_protocol::network::LoaderId;
 use chromiumoxide_cdp::cdp::browser_protocol::page::{
     AddScriptToEvaluateOnNewDocumentParams, CreateIsolatedWorldParams, EventFrameDetached,
     EventFrameStartedLoading, EventFrameStoppedLoading, EventLifecycleEvent,
-    EventNavigatedWithinDocument, Frame as CdpFrame, FrameTree,
+    EventNavigatedWithinDocument, Frame as CdpFrame, FrameDetachedReason, FrameTree,
 };
-use chromiumoxide_cdp::cdp::browser_protocol::target::EventAttachedToTarget;
+use chromiumoxide_cdp::cdp::browser_protocol::target::{EventAttachedToTarget, SessionId};
 use chromiumoxide_cdp::cdp::js_protocol::runtime::*;
 use chromiumoxide_cdp::cdp::{
     browser_protocol::page::{self, FrameId},
@@ -27,6 +28,9 @@ use crate::{cmd::CommandChain, ArcHttpRequest};
 
 pub const UTILITY_WORLD_NAME: &str = "__chromiumoxide_utility_world__";
 const EVALUATION_SCRIPT_URL: &str = "____chromiumoxide_utility_world___evaluation_script__";
+/// For how long the number of in-flight requests must stay below the limit of
+/// `WaitUntil::NetworkIdle0` or `WaitUntil::NetworkIdle2`
+pub const NETWORK_IDLE_DURATION: Duration = Duration::from_millis(500);
 
 /// Represents a frame on the page
 #[derive(Debug)]
@@ -46,6 +50,9 @@ pub struct Frame {
     name: Option<String>,
     /// The received lifecycle events
     lifecycle_events: HashSet<MethodId>,
+    /// The session of the out-of-process iframe this frame lives in, `None` if
+    /// the frame belongs to the session of the page target itself.
+    session_id: Option<SessionId>,
 }
 
 impl Frame {
@@ -61,6 +68,7 @@ impl Frame {
             child_frames: Default::default(),
             name: None,
             lifecycle_events: Default::default(),
+            session_id: None,
         }
     }
 
@@ -77,6 +85,7 @@ impl Frame {
             child_frames: Default::default(),
             name: None,
             lifecycle_events: Default::default(),
+            session_id: parent.session_id.clone(),
         }
     }
 
@@ -112,6 +121,13 @@ impl Frame {
         self.http_request.as_ref()
     }
 
+    /// The session of the out-of-process iframe this frame is rendered in.
+    ///
+    /// Returns `None` if the frame lives in the same process as its page.
+    pub fn session_id(&self) -> Option<&SessionId> {
+        self.session_id.as_ref()
+    }
+
     fn navigated(&mut self, frame: &CdpFrame) {
         self.name.clone_from(&frame.name);
         let url = if let Some(ref fragment) = frame.url_fragment {
@@ -136,6 +152,15 @@ impl Frame {
         self.http_request.take();
     }
 
+    /// A document restored from the back/forward cache was fully loaded
+    /// before and is not fetched again, so no lifecycle events are emitted for
+    /// it.
+    fn restored_from_cache(&mut self, frame: &CdpFrame) {
+        self.loader_id = Some(frame.loader_id.clone());
+        self.http_request.take();
+        self.on_loading_stopped();
+    }
+
     pub fn is_loaded(&self) -> bool {
         self.lifecycle_events.contains("load")
     }
@@ -175,6 +200,7 @@ impl From<CdpFrame> for Frame {
             child_frames: Default::default(),
             name: frame.name,
             lifecycle_events: Default::default(),
+            session_id: None,
         }
     }
 }
@@ -196,6 +222,15 @@ pub struct FrameManager {
     pending_navigations: VecDeque<(FrameNavigationRequest, NavigationWatcher)>,
     /// The currently ongoing navigation
     navigation: Option<(NavigationWatcher, Instant)>,
+    /// The root frames of out-of-process iframes mapped to the session that
+    /// was attached to their target
+    oopif_sessions: HashMap<FrameId, SessionId>,
+    /// The sessions that returned remote objects and nodes while out-of-process
+    /// iframes are attached, `None` if they belong to the page's session or
+    /// were returned by more than one session
+    remote_sessions: HashMap<RemoteId, Option<SessionId>>,
+    /// Tracks since when the page's network is idle
+    network_idle: NetworkIdle,
 }
 
 impl FrameManager {
@@ -208,6 +243,9 @@ impl FrameManager {
             request_timeout,
             pending_navigations: Default::default(),
             navigation: None,
+            oopif_sessions: Default::default(),
+            remote_sessions: Default::default(),
+            network_idle: NetworkIdle::new(Instant::now()),
         }
     }
 
@@ -237,6 +275,52 @@ impl FrameManager {
         )
     }
 
+    /// The commands to execute in order to initialize the session of an
+    /// out-of-process iframe that renders the frame `frame_id`
+    pub fn oopif_init_commands(&self, frame_id: FrameId) -> Vec<(MethodId, serde_json::Value)> {
+        let enable = page::EnableParams::default();
+        let get_tree = page::GetFrameTreeParams::default();
+        let set_lifecycle = page::SetLifecycleEventsEnabledParams::new(true);
+        let enable_runtime = runtime::EnableParams::default();
+        let mut cmds = vec![
+            (enable.identifier(), serde_json::to_value(enable).unwrap()),
+            (
+                get_tree.identifier(),
+                serde_json::to_value(get_tree).unwrap(),
+            ),
+            (
+                set_lifecycle.identifier(),
+                serde_json::to_value(set_lifecycle).unwrap(),
+            ),
+            (
+                enable_runtime.identifier(),
+                serde_json::to_value(enable_runtime).unwrap(),
+            ),
+        ];
+        if self.isolated_worlds.contains(UTILITY_WORLD_NAME) {
+            let add_script = AddScriptToEvaluateOnNewDocumentParams::builder()
+                .source(format!("//# sourceURL={EVALUATION_SCRIPT_URL}"))
+                .world_name(UTILITY_WORLD_NAME)
+                .build()
+                .unwrap();
+            cmds.push((
+                add_script.identifier(),
+                serde_json::to_value(add_script).unwrap(),
+            ));
+            let create_world = CreateIsolatedWorldParams::builder()
+                .frame_id(frame_id)
+                .grant_univeral_access(true)
+                .world_name(UTILITY_WORLD_NAME)
+                .build()
+                .unwrap();
+            cmds.push((
+                create_world.identifier(),
+                serde_json::to_value(create_world).unwrap(),
+            ));
+        }
+        cmds
+    }
+
     pub fn main_frame(&self) -> Option<&Frame> {
         self.main_frame.as_ref().and_then(|id| self.frames.get(id))
     }
@@ -257,6 +341,92 @@ impl FrameManager {
         self.frames.get(id)
     }
 
+    /// The session that commands targeting the frame need to be sent to, if
+    /// the frame is part of an out-of-process iframe.
+    pub fn session_for_frame(&self, id: &FrameId) -> Option<&SessionId> {
+        self.frames.get(id).and_then(|f| f.session_id.as_ref())
+    }
+
+    /// The session of the out-of-process iframe that owns the execution
+    /// context.
+    ///
+    /// Execution context ids are only unique within a single renderer, so this
+    /// only resolves if every frame that uses this id belongs to the same
+    /// out-of-process iframe.
+    pub fn session_for_context(&self, id: ExecutionContextId) -> Option<&SessionId> {
+        let mut sessions = self
+            .frames
+            .values()
+            .filter(|f| {
+                f.main_world.execution_context() == Some(id)
+                    || f.secondary_world.execution_context() == Some(id)
+            })
+            .map(|f| f.session_id.as_ref());
+        let first = sessions.next()??;
+        if sessions.all(|s| s == Some(first)) {
+            Some(first)
+        } else {
+            None
+        }
+    }
+
+    /// The session of an out-of-process iframe that should receive the command
+    /// with these `params`, if any.
+    ///
+    /// Commands are routed by their `frameId`, the execution context they
+    /// target or the session that returned the remote object or node they
+    /// target.
+    pub fn session_for_params(&self, params: &serde_json::Value) -> Option<&SessionId> {
+        if let Some(frame_id) = params.get("frameId").and_then(|v| v.as_str()) {
+            return self
+                .frames
+                .get(frame_id)
+                .and_then(|f| f.session_id.as_ref());
+        }
+        if let Some(id) = ["executionContextId", "contextId"]
+            .iter()
+            .find_map(|key| params.get(*key).and_then(|v| v.as_i64()))
+        {
+            return self.session_for_context(ExecutionContextId::new(id));
+        }
+        if self.remote_sessions.is_empty() {
+            return None;
+        }
+        let mut ids = Vec::new();
+        RemoteId::collect(params, &mut ids);
+        ids.iter()
+            .find_map(|id| self.remote_sessions.get(id))
+            .and_then(Option::as_ref)
+    }
+
+    /// Records the session that returned the remote objects and nodes in the
+    /// `result` of a command, `None` for the page's own session
+    pub fn on_remote_ids(&mut self, session: Option<&SessionId>, result: &serde_json::Value) {
+        if self.oopif_sessions.is_empty() {
+            return;
+        }
+        let mut ids = Vec::new();
+        RemoteId::collect(result, &mut ids);
+        for id in ids {
+            match self.remote_sessions.entry(id) {
+                HashMapEntry::Occupied(mut entry) => {
+                    // node ids are only unique within a session
+                    if entry.get().as_ref() != session {
+                        entry.insert(None);
+                    }
+                }
+                HashMapEntry::Vacant(entry) => {
+                    entry.insert(session.cloned());
+                }
+            }
+        }
+    }
+
+    /// All sessions of out-of-process iframes that are currently attached
+    pub fn oopif_sessions(&self) -> impl Iterator<Item = &SessionId> + '_ {
+        self.oopif_sessions.values()
+    }
+
     fn check_lifecycle(&self, watcher: &NavigationWatcher, frame: &Frame) -> bool {
         watcher.expected_lifecycle.iter().all(|ev| {
             frame.lifecycle_events.contains(ev)
@@ -272,10 +442,17 @@ impl FrameManager {
         &self,
         watcher: &NavigationWatcher,
         frame: &Frame,
+        now: Instant,
     ) -> Option<NavigationOk> {
-        if !self.check_lifecycle(watcher, frame) {
+        if !watcher.restored_from_cache && !self.check_lifecycle(watcher, frame) {
             return None;
         }
+        if !self.is_network_idle(watcher.wait_until, now) {
+            return None;
+        }
+        if watcher.restored_from_cache {
+            return Some(NavigationOk::NewDocumentNavigation(watcher.id));
+        }
         if frame.loader_id == watcher.loader_id && !watcher.same_document_navigation {
             return None;
         }
@@ -288,8 +465,63 @@ impl FrameManager {
         None
     }
 
+    /// Whether the network is idle as required by `wait_until`
+    fn is_network_idle(&self, wait_until: WaitUntil, now: Instant) -> bool {
+        wait_until
+            .max_inflight_requests()
+            .map(|max| self.network_idle.is_idle(max, now))
+            .unwrap_or(true)
+    }
+
+    /// Whether the main frame reached the state described by `wait_until`
+    pub fn is_main_frame_ready(&self, wait_until: WaitUntil, now: Instant) -> bool {
+        let ready = match (self.main_frame(), wait_until) {
+            (None, _) => false,
+            (Some(_), WaitUntil::Commit) => true,
+            (Some(frame), WaitUntil::DomContentLoaded) => {
+                frame.lifecycle_events.contains("DOMContentLoaded") || frame.is_loaded()
+            }
+            (Some(frame), _) => frame.is_loaded(),
+        };
+        ready && self.is_network_idle(wait_until, now)
+    }
+
+    /// Updates the number of requests of the page that are currently in
+    /// flight
+    pub fn on_inflight_requests(&mut self, inflight: usize, now: Instant) {
+        self.network_idle.update(inflight, now)
+    }
+
+    /// The next point in time at which the state of the ongoing navigation can
+    /// change without receiving any event, either because it times out or
+    /// because the network became idle for long enough.
+    ///
+    /// `waiting` are the states other parties wait for the main frame to reach.
+    pub fn next_deadline(
+        &self,
+        waiting: impl IntoIterator<Item = WaitUntil>,
+        now: Instant,
+    ) -> Option<Instant> {
+        let navigation = self.navigation.as_ref();
+        let idle = navigation
+            .map(|(watcher, _)| watcher.wait_until)
+            .into_iter()
+            .chain(waiting)
+            .filter_map(|wait_until| wait_until.max_inflight_requests())
+            .filter_map(|max| self.network_idle.idle_deadline(max));
+        navigation
+            .map(|(_, deadline)| *deadline)
+            .into_iter()
+            .chain(idle)
+            .filter(|deadline| *deadline > now)
+            .min()
+    }
+
     /// Track the request in the frame
     pub fn on_http_request_finished(&mut self, request: HttpRequest) {
+        if !request.is_navigation_request {
+            return;
+        }
         if let Some(id) = request.frame.as_ref() {
             if let Some(frame) = self.frames.get_mut(id) {
                 frame.set_request(request);
@@ -310,7 +542,7 @@ impl FrameManager {
                 )));
             }
             if let Some(frame) = self.frames.get(&watcher.frame_id) {
-                if let Some(nav) = self.check_lifecycle_complete(&watcher, frame) {
+                if let Some(nav) = self.check_lifecycle_complete(&watcher, frame, now) {
                     // request is complete if the frame's lifecycle is complete = frame received all
                     // required events
                     return Some(FrameEvent::NavigationResult(Ok(nav)));
@@ -335,6 +567,16 @@ impl FrameManager {
         None
     }
 
+    /// Removes the ongoing and all queued navigations and returns their ids
+    pub fn drain_navigations(&mut self) -> Vec<NavigationId> {
+        self.navigation
+            .take()
+            .map(|(watcher, _)| watcher.id)
+            .into_iter()
+            .chain(self.pending_navigations.drain(..).map(|(req, _)| req.id))
+            .collect()
+    }
+
     /// Entrypoint for page navigation
     pub fn goto(&mut self, req: FrameNavigationRequest) {
         if let Some(frame_id) = self.main_frame.clone() {
@@ -345,15 +587,54 @@ impl FrameManager {
     /// Navigate a specific frame
     pub fn navigate_frame(&mut self, frame_id: FrameId, mut req: FrameNavigationRequest) {
         let loader_id = self.frames.get(&frame_id).and_then(|f| f.loader_id.clone());
-        let watcher = NavigationWatcher::until_page_load(req.id, frame_id.clone(), loader_id);
+        let watcher = NavigationWatcher::new(req.id, frame_id.clone(), loader_id, req.wait_until);
         // insert the frame_id in the request if not present
-        req.set_frame_id(frame_id);
+        if req.req.method.as_ref() == page::NavigateParams::IDENTIFIER {
+            req.set_frame_id(frame_id);
+        }
         self.pending_navigations.push_back((req, watcher))
     }
 
     /// Fired when a frame moved to another session
-    pub fn on_attached_to_target(&mut self, _event: &EventAttachedToTarget) {
-        // _onFrameMoved
+    ///
+    /// The target of an out-of-process iframe shares its id with the frame it
+    /// renders, so the frame and all of its future children are bound to the
+    /// new session.
+    pub fn on_attached_to_target(&mut self, event: &EventAttachedToTarget) {
+        if event.target_info.r#type != "iframe" {
+            return;
+        }
+        let frame_id = FrameId::new(event.target_info.target_id.inner().clone());
+        if let Some(frame) = self.frames.get_mut(&frame_id) {
+            frame.session_id = Some(event.session_id.clone());
+        }
+        self.oopif_sessions
+            .insert(frame_id, event.session_id.clone());
+    }
+
+    /// Fired when the session of an out-of-process iframe was detached
+    pub fn on_detached_from_target(&mut self, session_id: &SessionId) {
+        let frames = self
+            .oopif_sessions
+            .iter()
+            .filter(|(_, session)| *session == session_id)
+            .map(|(frame, _)| frame.clone())
+            .collect::<Vec<_>>();
+        for frame in frames {
+            self.oopif_sessions.remove(&frame);
+            self.remove_frames_recursively(&frame);
+        }
+        self.forget_session(session_id);
+    }
+
+    /// Drops the remote objects and nodes of a session that no longer
+    /// renders any frame
+    fn forget_session(&mut self, session_id: &SessionId) {
+        self.remote_sessions
+            .retain(|_, session| session.as_ref() != Some(session_id));
+        if self.oopif_sessions.is_empty() {
+            self.remote_sessions.clear();
+        }
     }
 
     pub fn on_frame_tree(&mut self, frame_tree: FrameTree) {
@@ -375,14 +656,40 @@ impl FrameManager {
         }
         if let Some(parent_frame_id) = parent_frame_id {
             if let Some(parent_frame) = self.frames.get_mut(&parent_frame_id) {
-                let frame = Frame::with_parent(frame_id.clone(), parent_frame);
+                let mut frame = Frame::with_parent(frame_id.clone(), parent_frame);
+                if let Some(session) = self.oopif_sessions.get(&frame_id) {
+                    frame.session_id = Some(session.clone());
+                }
                 self.frames.insert(frame_id, frame);
             }
         }
     }
 
     pub fn on_frame_detached(&mut self, event: &EventFrameDetached) {
+        // a swapped frame continues to live in the session of its own target
+        if event.reason == FrameDetachedReason::Swap {
+            return;
+        }
+        let mut removed = vec![event.frame_id.clone()];
+        self.collect_child_frames(&event.frame_id, &mut removed);
         self.remove_frames_recursively(&event.frame_id);
+        for frame_id in removed {
+            if let Some(session) = self.oopif_sessions.remove(&frame_id) {
+                if !self.oopif_sessions.values().any(|s| *s == session) {
+                    self.forget_session(&session);
+                }
+            }
+        }
+    }
+
+    /// Adds the ids of all descendants of the frame to `ids`
+    fn collect_child_frames(&self, id: &FrameId, ids: &mut Vec<FrameId>) {
+        if let Some(frame) = self.frames.get(id) {
+            for child in &frame.child_frames {
+                ids.push(child.clone());
+                self.collect_child_frames(child, ids);
+            }
+        }
     }
 
     pub fn on_frame_navigated(&mut self, frame: &CdpFrame) {
@@ -417,6 +724,19 @@ impl FrameManager {
         }
     }
 
+    /// Fired when a frame was restored from the back/forward cache instead of
+    /// being navigated, e.g. after `Page.navigateToHistoryEntry`
+    pub fn on_frame_restored_from_cache(&mut self, frame: &CdpFrame) {
+        if let Some(f) = self.frames.get_mut(&frame.id) {
+            f.restored_from_cache(frame);
+        }
+        if let Some((watcher, _)) = self.navigation.as_mut() {
+            if watcher.frame_id == frame.id {
+                watcher.restored_from_cache = true;
+            }
+        }
+    }
+
     pub fn on_frame_navigated_within_document(&mut self, event: &EventNavigatedWithinDocument) {
         if let Some(frame) = self.frames.get_mut(&event.frame_id) {
             frame.navigated_within_url(event.url.clone());
@@ -502,6 +822,21 @@ impl FrameManager {
         self.context_ids.clear()
     }
 
+    /// Issued when all executionContexts of an out-of-process iframe session
+    /// were cleared
+    pub fn on_session_execution_contexts_cleared(&mut self, session_id: &SessionId) {
+        let frames = &mut self.frames;
+        self.context_ids.retain(|_, id| {
+            if let Some(frame) = frames.get_mut(id) {
+                if frame.session_id.as_ref() == Some(session_id) {
+                    frame.clear_contexts();
+                    return false;
+                }
+            }
+            true
+        });
+    }
+
     /// Fired for top level page lifecycle events (nav, load, paint, etc.)
     pub fn on_page_lifecycle_event(&mut self, event: &EventLifecycleEvent) {
         if let Some(frame) = self.frames.get_mut(&event.frame_id) {
@@ -609,6 +944,8 @@ impl NavigationOk {
 pub struct NavigationWatcher {
     id: NavigationId,
     expected_lifecycle: HashSet<MethodId>,
+    /// When the navigation is considered complete
+    wait_until: WaitUntil,
     frame_id: FrameId,
     loader_id: Option<LoaderId>,
     /// Once we receive the response to the issued `Page.navigate` request we
@@ -616,16 +953,34 @@ pub struct NavigationWatcher {
     /// navigating to a new document by checking if a loader was included in the
     /// response.
     same_document_navigation: bool,
+    /// Whether the frame was restored from the back/forward cache, in which
+    /// case the document is already loaded and no lifecycle events follow.
+    restored_from_cache: bool,
 }
 
 impl NavigationWatcher {
     pub fn until_page_load(id: NavigationId, frame: FrameId, loader_id: Option<LoaderId>) -> Self {
+        Self::new(id, frame, loader_id, WaitUntil::Load)
+    }
+
+    pub fn new(
+        id: NavigationId,
+        frame: FrameId,
+        loader_id: Option<LoaderId>,
+        wait_until: WaitUntil,
+    ) -> Self {
         Self {
             id,
-            expected_lifecycle: std::iter::once("load".into()).collect(),
+            expected_lifecycle: wait_until
+                .lifecycle_event()
+                .map(|ev| MethodId::from(ev.as_ref().to_string()))
+                .into_iter()
+                .collect(),
+            wait_until,
             loader_id,
             frame_id: frame,
             same_document_navigation: false,
+            restored_from_cache: false,
         }
     }
 
@@ -654,6 +1009,8 @@ pub struct FrameNavigationRequest {
     pub req: Request,
     /// The timeout after which the request will be considered timed out
     pub timeout: Duration,
+    /// When the navigation is considered complete
+    pub wait_until: WaitUntil,
 }
 
 impl FrameNavigationRequest {
@@ -662,6 +1019,7 @@ impl FrameNavigationRequest {
             id,
             req,
             timeout: Duration::from_millis(REQUEST_TIMEOUT),
+            wait_until: Default::default(),
         }
     }
 
@@ -694,3 +1052,410 @@ impl AsRef<str> for LifecycleEvent {
         }
     }
 }
+
+/// When a navigation is considered to be finished
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WaitUntil {
+    /// Once the `load` event was fired
+    #[default]
+    Load,
+    /// Once the `DOMContentLoaded` event was fired
+    DomContentLoaded,
+    /// Once the page loaded and there were no requests in flight for at least
+    /// 500ms
+    NetworkIdle0,
+    /// Once the page loaded and there were no more than 2 requests in flight
+    /// for at least 500ms
+    NetworkIdle2,
+    /// Once the new document was committed, before any of its content loaded
+    Commit,
+}
+
+impl WaitUntil {
+    /// The lifecycle event the frames need to receive
+    pub fn lifecycle_event(&self) -> Option<LifecycleEvent> {
+        match self {
+            WaitUntil::DomContentLoaded => Some(LifecycleEvent::DomcontentLoaded),
+            WaitUntil::Commit => None,
+            _ => Some(LifecycleEvent::Load),
+        }
+    }
+
+    /// The maximum number of requests in flight for the network to be
+    /// considered idle, `None` if the network state doesn't matter
+    pub fn max_inflight_requests(&self) -> Option<usize> {
+        match self {
+            WaitUntil::NetworkIdle0 => Some(0),
+            WaitUntil::NetworkIdle2 => Some(2),
+            _ => None,
+        }
+    }
+}
+
+/// Tracks since when the number of in-flight requests is at most 0 and at most
+/// 2
+#[derive(Debug)]
+struct NetworkIdle {
+    idle0_since: Option<Instant>,
+    idle2_since: Option<Instant>,
+}
+
+impl NetworkIdle {
+    fn new(now: Instant) -> Self {
+        Self {
+            idle0_since: Some(now),
+            idle2_since: Some(now),
+        }
+    }
+
+    fn update(&mut self, inflight: usize, now: Instant) {
+        if inflight == 0 {
+            self.idle0_since.get_or_insert(now);
+        } else {
+            self.idle0_since = None;
+        }
+        if inflight <= 2 {
+            self.idle2_since.get_or_insert(now);
+        } else {
+            self.idle2_since = None;
+        }
+    }
+
+    /// When the network will have been idle for long enough, if it stays idle
+    fn idle_deadline(&self, max_inflight: usize) -> Option<Instant> {
+        let since = if max_inflight == 0 {
+            self.idle0_since
+        } else {
+            self.idle2_since
+        };
+        since.map(|since| since + NETWORK_IDLE_DURATION)
+    }
+
+    fn is_idle(&self, max_inflight: usize, now: Instant) -> bool {
+        self.idle_deadline(max_inflight)
+            .map(|deadline| now >= deadline)
+            .unwrap_or_default()
+    }
+}
+
+/// A remote object or DOM node that a command can target
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum RemoteId {
+    Object(String),
+    Node(i64),
+    BackendNode(i64),
+}
+
+impl RemoteId {
+    /// Collects the ids of all remote objects and nodes in the params or the
+    /// result of a command
+    fn collect(value: &serde_json::Value, ids: &mut Vec<RemoteId>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value) {
+                        ("objectId", serde_json::Value::String(id)) => {
+                            ids.push(RemoteId::Object(id.clone()))
+                        }
+                        ("nodeId", value) => ids.extend(value.as_i64().map(RemoteId::Node)),
+                        ("nodeIds", serde_json::Value::Array(values)) => {
+                            ids.extend(values.iter().filter_map(|v| v.as_i64()).map(RemoteId::Node))
+                        }
+                        ("backendNodeId", value) => {
+                            ids.extend(value.as_i64().map(RemoteId::BackendNode))
+                        }
+                        ("backendNodeIds", serde_json::Value::Array(values)) => ids.extend(
+                            values
+                                .iter()
+                                .filter_map(|v| v.as_i64())
+                                .map(RemoteId::BackendNode),
+                        ),
+                        (_, value) => Self::collect(value, ids),
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => {
+                for value in values {
+                    Self::collect(value, ids);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attach_oopif(manager: &mut FrameManager, session: &str) -> SessionId {
+        let event: EventAttachedToTarget = serde_json::from_value(serde_json::json!({
+            "sessionId": session,
+            "targetInfo": {
+                "targetId": "OOPIF",
+                "type": "iframe",
+                "title": "",
+                "url": "https://other.example/",
+                "attached": true,
+                "canAccessOpener": false
+            },
+            "waitingForDebugger": false
+        }))
+        .unwrap();
+        manager.on_attached_to_target(&event);
+        event.session_id
+    }
+
+    fn route(manager: &FrameManager, params: serde_json::Value) -> Option<SessionId> {
+        manager.session_for_params(&params).cloned()
+    }
+
+    fn cdp_frame(loader: &str, url: &str) -> CdpFrame {
+        serde_json::from_value(serde_json::json!({
+            "id": "MAIN",
+            "loaderId": loader,
+            "url": url,
+            "domainAndRegistry": "example.com",
+            "securityOrigin": "https://example.com",
+            "mimeType": "text/html",
+            "secureContextType": "Secure",
+            "crossOriginIsolatedContextType": "NotIsolated",
+            "gatedAPIFeatures": []
+        }))
+        .unwrap()
+    }
+
+    fn lifecycle(manager: &mut FrameManager, loader: &str, name: &str) {
+        let event: EventLifecycleEvent = serde_json::from_value(serde_json::json!({
+            "frameId": "MAIN",
+            "loaderId": loader,
+            "name": name,
+            "timestamp": 0.0
+        }))
+        .unwrap();
+        manager.on_page_lifecycle_event(&event);
+    }
+
+    /// A manager whose main frame finished loading the document of `LOADER`
+    fn loaded_manager() -> FrameManager {
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        manager.on_frame_navigated(&cdp_frame("LOADER", "https://example.com/"));
+        lifecycle(&mut manager, "LOADER", "init");
+        lifecycle(&mut manager, "LOADER", "load");
+        manager.on_inflight_requests(0, Instant::now());
+        manager
+    }
+
+    fn navigate_history(manager: &mut FrameManager) -> NavigationId {
+        let id = NavigationId(1);
+        let req = Request::new(
+            page::NavigateToHistoryEntryParams::IDENTIFIER.into(),
+            serde_json::json!({"entryId": 1}),
+        );
+        manager.goto(FrameNavigationRequest::new(id, req));
+        match manager.poll(Instant::now()) {
+            Some(FrameEvent::NavigationRequest(req_id, _)) => assert_eq!(req_id, id),
+            event => panic!("expected a navigation request, got {event:?}"),
+        }
+        id
+    }
+
+    #[test]
+    fn completes_navigation_within_document() {
+        let mut manager = loaded_manager();
+        let id = navigate_history(&mut manager);
+        // the loader didn't change, so the navigation isn't done yet
+        assert!(manager.poll(Instant::now()).is_none());
+
+        let event: EventNavigatedWithinDocument = serde_json::from_value(serde_json::json!({
+            "frameId": "MAIN",
+            "url": "https://example.com/#section",
+            "navigationType": "historyApi"
+        }))
+        .unwrap();
+        manager.on_frame_navigated_within_document(&event);
+
+        match manager.poll(Instant::now()) {
+            Some(FrameEvent::NavigationResult(Ok(nav))) => {
+                assert_eq!(nav, NavigationOk::SameDocumentNavigation(id))
+            }
+            event => panic!("expected a navigation result, got {event:?}"),
+        }
+        assert_eq!(
+            manager.main_frame().and_then(|f| f.url()),
+            Some("https://example.com/#section")
+        );
+    }
+
+    #[test]
+    fn completes_navigation_restored_from_bfcache() {
+        let mut manager = loaded_manager();
+        let id = navigate_history(&mut manager);
+
+        // a restored document emits neither lifecycle nor loading events
+        let frame = cdp_frame("CACHED", "https://example.com/previous");
+        manager.on_frame_navigated(&frame);
+        manager.on_frame_restored_from_cache(&frame);
+
+        match manager.poll(Instant::now()) {
+            Some(FrameEvent::NavigationResult(Ok(nav))) => {
+                assert_eq!(nav, NavigationOk::NewDocumentNavigation(id))
+            }
+            event => panic!("expected a navigation result, got {event:?}"),
+        }
+        let main = manager.main_frame().unwrap();
+        assert!(main.is_loaded());
+        assert_eq!(main.url(), Some("https://example.com/previous"));
+        assert!(manager.is_main_frame_ready(WaitUntil::Load, Instant::now()));
+    }
+
+    #[test]
+    fn network_idle0_requires_no_inflight_requests() {
+        let start = Instant::now();
+        let mut idle = NetworkIdle::new(start);
+        assert_eq!(idle.idle_deadline(0), Some(start + NETWORK_IDLE_DURATION));
+        assert!(!idle.is_idle(0, start));
+        assert!(idle.is_idle(0, start + NETWORK_IDLE_DURATION));
+
+        let busy = start + Duration::from_millis(100);
+        idle.update(1, busy);
+        assert_eq!(idle.idle_deadline(0), None);
+        assert!(!idle.is_idle(0, busy + NETWORK_IDLE_DURATION));
+
+        let quiet = start + Duration::from_millis(200);
+        idle.update(0, quiet);
+        // staying idle doesn't restart the timer
+        idle.update(0, quiet + Duration::from_millis(300));
+        assert_eq!(idle.idle_deadline(0), Some(quiet + NETWORK_IDLE_DURATION));
+        assert!(!idle.is_idle(0, quiet + Duration::from_millis(499)));
+        assert!(idle.is_idle(0, quiet + NETWORK_IDLE_DURATION));
+    }
+
+    #[test]
+    fn network_idle2_tolerates_two_inflight_requests() {
+        let start = Instant::now();
+        let mut idle = NetworkIdle::new(start);
+
+        idle.update(2, start + Duration::from_millis(100));
+        assert_eq!(idle.idle_deadline(0), None);
+        assert_eq!(idle.idle_deadline(2), Some(start + NETWORK_IDLE_DURATION));
+        assert!(idle.is_idle(2, start + NETWORK_IDLE_DURATION));
+
+        let busy = start + Duration::from_millis(200);
+        idle.update(3, busy);
+        assert_eq!(idle.idle_deadline(2), None);
+        assert!(!idle.is_idle(2, busy + NETWORK_IDLE_DURATION));
+
+        let quiet = start + Duration::from_millis(300);
+        idle.update(2, quiet);
+        assert_eq!(idle.idle_deadline(2), Some(quiet + NETWORK_IDLE_DURATION));
+        assert_eq!(idle.idle_deadline(0), None);
+        assert!(idle.is_idle(2, quiet + NETWORK_IDLE_DURATION));
+        assert!(!idle.is_idle(0, quiet + NETWORK_IDLE_DURATION));
+    }
+
+    #[test]
+    fn main_frame_ready_requires_lifecycle_and_network_idle() {
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        let now = Instant::now();
+        assert!(!manager.is_main_frame_ready(WaitUntil::Commit, now));
+
+        manager.on_frame_navigated(&cdp_frame("LOADER", "https://example.com/"));
+        manager.on_inflight_requests(1, now);
+        let later = now + NETWORK_IDLE_DURATION;
+        assert!(manager.is_main_frame_ready(WaitUntil::Commit, now));
+        assert!(!manager.is_main_frame_ready(WaitUntil::DomContentLoaded, later));
+
+        lifecycle(&mut manager, "LOADER", "DOMContentLoaded");
+        assert!(manager.is_main_frame_ready(WaitUntil::DomContentLoaded, later));
+        assert!(!manager.is_main_frame_ready(WaitUntil::Load, later));
+
+        lifecycle(&mut manager, "LOADER", "load");
+        assert!(manager.is_main_frame_ready(WaitUntil::Load, later));
+        // loaded, but a request is still in flight
+        assert!(manager.is_main_frame_ready(WaitUntil::NetworkIdle2, later));
+        assert!(!manager.is_main_frame_ready(WaitUntil::NetworkIdle0, later));
+
+        let quiet = now + Duration::from_millis(100);
+        manager.on_inflight_requests(0, quiet);
+        assert!(!manager.is_main_frame_ready(WaitUntil::NetworkIdle0, later));
+        assert!(manager.is_main_frame_ready(WaitUntil::NetworkIdle0, quiet + NETWORK_IDLE_DURATION));
+    }
+
+    #[test]
+    fn routes_remote_objects_and_nodes_to_their_session() {
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        let session = attach_oopif(&mut manager, "OOPIF_SESSION");
+
+        manager.on_remote_ids(
+            Some(&session),
+            &serde_json::json!({"result": {"type": "object", "objectId": "7.1.2"}}),
+        );
+        manager.on_remote_ids(
+            Some(&session),
+            &serde_json::json!({"root": {"nodeId": 3, "backendNodeId": 40, "children": [{"nodeId": 4}]}}),
+        );
+        manager.on_remote_ids(None, &serde_json::json!({"nodeIds": [4, 5]}));
+
+        assert_eq!(
+            route(
+                &manager,
+                serde_json::json!({"objectId": "7.1.2", "functionDeclaration": "() => 1"})
+            ),
+            Some(session.clone())
+        );
+        assert_eq!(
+            route(&manager, serde_json::json!({"nodeId": 3})),
+            Some(session.clone())
+        );
+        assert_eq!(
+            route(&manager, serde_json::json!({"backendNodeId": 40})),
+            Some(session.clone())
+        );
+        // returned by both sessions
+        assert_eq!(route(&manager, serde_json::json!({"nodeId": 4})), None);
+        // returned by the page's session
+        assert_eq!(route(&manager, serde_json::json!({"nodeId": 5})), None);
+        assert_eq!(
+            route(&manager, serde_json::json!({"objectId": "1.1.1"})),
+            None
+        );
+
+        manager.on_detached_from_target(&session);
+        assert_eq!(
+            route(&manager, serde_json::json!({"objectId": "7.1.2"})),
+            None
+        );
+    }
+
+    #[test]
+    fn forgets_remote_ids_of_removed_oopifs() {
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        let session = attach_oopif(&mut manager, "OOPIF_SESSION");
+        manager.on_remote_ids(Some(&session), &serde_json::json!({"objectId": "7.1.2"}));
+
+        // the frame of a swapped out-of-process iframe lives on
+        let mut detached: EventFrameDetached = serde_json::from_value(serde_json::json!({
+            "frameId": "OOPIF",
+            "reason": "swap"
+        }))
+        .unwrap();
+        manager.on_frame_detached(&detached);
+        assert_eq!(
+            route(&manager, serde_json::json!({"objectId": "7.1.2"})),
+            Some(session.clone())
+        );
+
+        detached.reason = FrameDetachedReason::Remove;
+        manager.on_frame_detached(&detached);
+        assert_eq!(manager.oopif_sessions().count(), 0);
+        assert!(manager.remote_sessions.is_empty());
+    }
+
+    #[test]
+    fn ignores_remote_ids_without_oopifs() {
+        let mut manager = FrameManager::new(Duration::from_secs(30));
+        manager.on_remote_ids(None, &serde_json::json!({"nodeId": 3}));
+        assert!(manager.remote_sessions.is_empty());
+    }
+}
@@ -2,6 +2,9 @@ use chromiumoxide_cdp::cdp::browser_protocol::network::{InterceptionId, RequestI
 use chromiumoxide_cdp::cdp::browser_protocol::page::FrameId;
 use std::collections::HashMap;
 
+use crate::error::Result;
+use crate::page::Page;
+
 #[derive(Debug, Clone)]
 pub struct HttpRequest {
     request_id: RequestId,
@@ -52,6 +55,14 @@ impl HttpRequest {
         &self.request_id
     }
 
+    /// Returns the body of the response to this request, which must have
+    /// been sent by the `page`.
+    ///
+    /// See [`Page::response_body`]
+    pub async fn body(&self, page: &Page) -> Result<Vec<u8>> {
+        page.response_body(self.request_id.clone()).await
+    }
+
     pub(crate) fn set_response(&mut self, response: Response) {
         self.response = Some(response)
     }
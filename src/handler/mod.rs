@@ -15,8 +15,11 @@ use chromiumoxide_cdp::cdp::events::CdpEvent;
 use chromiumoxide_cdp::cdp::events::CdpEventMessage;
 use chromiumoxide_types::{CallId, Message, Method, Response};
 use chromiumoxide_types::{MethodId, Request as CdpRequest};
+#[cfg(test)]
+pub(crate) use page::PageHandle;
 pub(crate) use page::PageInner;
 
+use crate::adblock::AdblockOptions;
 use crate::cmd::{to_command_response, CommandMessage};
 use crate::conn::Connection;
 use crate::error::{CdpError, Result};
@@ -24,9 +27,10 @@ use crate::handler::browser::BrowserContext;
 use crate::handler::frame::FrameNavigationRequest;
 use crate::handler::frame::{NavigationError, NavigationId, NavigationOk};
 use crate::handler::job::PeriodicJob;
+use crate::handler::network::ResourceFilter;
 use crate::handler::session::Session;
 use crate::handler::target::TargetEvent;
-use crate::handler::target::{Target, TargetConfig};
+use crate::handler::target::{NavigateRequest, Target, TargetConfig};
 use crate::handler::viewport::Viewport;
 use crate::page::Page;
 
@@ -47,6 +51,7 @@ mod session;
 pub mod target;
 pub mod target_message_future;
 pub mod viewport;
+pub mod websocket;
 
 /// The handler that monitors the state of the chromium browser and drives all
 /// the requests and events.
@@ -56,7 +61,7 @@ pub struct Handler {
     /// Commands that are being processed and awaiting a response from the
     /// chromium instance together with the timestamp when the request
     /// started.
-    pending_commands: FnvHashMap<CallId, (PendingRequest, MethodId, Instant)>,
+    pending_commands: FnvHashMap<CallId, (PendingRequest, MethodId, Option<Instant>)>,
     /// Connection to the browser instance
     from_browser: Fuse<Receiver<HandlerMessage>>,
     default_browser_context: BrowserContext,
@@ -100,6 +105,22 @@ impl Handler {
             serde_json::to_value(discover).unwrap(),
         );
 
+        let mut pending_commands = FnvHashMap::default();
+        if !config.permissions.is_empty() {
+            let grant = GrantPermissionsParams::new(config.permissions.clone());
+            let method = grant.identifier();
+            match conn.submit_command(method.clone(), None, serde_json::to_value(grant).unwrap()) {
+                Ok(call_id) => {
+                    let deadline = Instant::now().checked_add(config.request_timeout);
+                    pending_commands
+                        .insert(call_id, (PendingRequest::LoggedCommand, method, deadline));
+                }
+                Err(err) => {
+                    tracing::warn!("Failed to grant the configured permissions: {}", err);
+                }
+            }
+        }
+
         let browser_contexts = config
             .context_ids
             .iter()
@@ -107,7 +128,7 @@ impl Handler {
             .collect();
 
         Self {
-            pending_commands: Default::default(),
+            pending_commands,
             from_browser: rx.fuse(),
             default_browser_context: Default::default(),
             browser_contexts,
@@ -241,6 +262,17 @@ impl Handler {
                     self.on_navigation_response(id, resp);
                 }
                 PendingRequest::ExternalCommand(tx) => {
+                    // chromium sends the events caused by a command before its
+                    // response, so these must be received first as well
+                    self.event_listeners.flush();
+                    let _ = tx.send(Ok(resp)).ok();
+                }
+                PendingRequest::TargetCommand(target_id, session_id, tx) => {
+                    if let Some(target) = self.targets.get_mut(&target_id) {
+                        target.on_command_response(session_id.as_ref(), &resp);
+                        target.event_listeners_mut().flush();
+                    }
+                    self.event_listeners.flush();
                     let _ = tx.send(Ok(resp)).ok();
                 }
                 PendingRequest::InternalCommand(target_id) => {
@@ -248,6 +280,11 @@ impl Handler {
                         target.on_response(resp, method.as_ref());
                     }
                 }
+                PendingRequest::LoggedCommand => {
+                    if let Some(err) = resp.error {
+                        tracing::warn!("{} failed: {}", method, err);
+                    }
+                }
                 PendingRequest::CloseBrowser(tx) => {
                     self.closing = true;
                     let _ = tx.send(Ok(CloseReturns {})).ok();
@@ -267,11 +304,38 @@ impl Handler {
             .submit_command(msg.method.clone(), msg.session_id, msg.params)?;
         self.pending_commands.insert(
             call_id,
-            (PendingRequest::ExternalCommand(msg.sender), msg.method, now),
+            (
+                PendingRequest::ExternalCommand(msg.sender),
+                msg.method,
+                self.deadline(now, msg.timeout),
+            ),
         );
         Ok(())
     }
 
+    /// Submit a command initiated via channel by the `Page` of a target
+    fn submit_target_command(&mut self, target_id: TargetId, msg: CommandMessage, now: Instant) {
+        let deadline = self.deadline(now, msg.timeout);
+        match self
+            .conn
+            .submit_command(msg.method.clone(), msg.session_id.clone(), msg.params)
+        {
+            Ok(call_id) => {
+                self.pending_commands.insert(
+                    call_id,
+                    (
+                        PendingRequest::TargetCommand(target_id, msg.session_id, msg.sender),
+                        msg.method,
+                        deadline,
+                    ),
+                );
+            }
+            Err(err) => {
+                let _ = msg.sender.send(Err(err.into()));
+            }
+        }
+    }
+
     pub(crate) fn submit_internal_command(
         &mut self,
         target_id: TargetId,
@@ -285,7 +349,11 @@ impl Handler {
         )?;
         self.pending_commands.insert(
             call_id,
-            (PendingRequest::InternalCommand(target_id), req.method, now),
+            (
+                PendingRequest::InternalCommand(target_id),
+                req.method,
+                self.deadline(now, None),
+            ),
         );
         Ok(())
     }
@@ -298,8 +366,9 @@ impl Handler {
             .submit_command(method.clone(), None, serde_json::to_value(msg).unwrap())
             .unwrap();
 
+        let deadline = self.deadline(now, None);
         self.pending_commands
-            .insert(call_id, (PendingRequest::GetTargets(tx), method, now));
+            .insert(call_id, (PendingRequest::GetTargets(tx), method, deadline));
     }
 
     /// Send the Request over to the server and store its identifier to handle
@@ -314,8 +383,15 @@ impl Handler {
             )
             .unwrap();
 
-        self.pending_commands
-            .insert(call_id, (PendingRequest::Navigate(id), req.method, now));
+        let timeout = match self.navigations.get(&id) {
+            Some(NavigationRequest::Navigate(nav)) => nav.timeout,
+            None => None,
+        };
+        let deadline = self.deadline(now, timeout);
+        self.pending_commands.insert(
+            call_id,
+            (PendingRequest::Navigate(id), req.method, deadline),
+        );
     }
 
     fn submit_close(&mut self, tx: OneshotSender<Result<CloseReturns>>, now: Instant) {
@@ -331,24 +407,59 @@ impl Handler {
             )
             .unwrap();
 
-        self.pending_commands
-            .insert(call_id, (PendingRequest::CloseBrowser(tx), method, now));
+        let deadline = self.deadline(now, None);
+        self.pending_commands.insert(
+            call_id,
+            (PendingRequest::CloseBrowser(tx), method, deadline),
+        );
     }
 
     /// Process a message received by the target's page via channel
     fn on_target_message(&mut self, target: &mut Target, msg: CommandMessage, now: Instant) {
-        // if let some
-        if msg.is_navigation() {
-            let (req, tx) = msg.split();
-            let id = self.next_navigation_id();
-            target.goto(FrameNavigationRequest::new(id, req));
-            self.navigations.insert(
-                id,
-                NavigationRequest::Navigate(NavigationInProgress::new(tx)),
+        if target.is_crashed() {
+            match close_crashed_target(msg, target.target_id()) {
+                Ok(close) => self.submit_target_command(target.target_id().clone(), close, now),
+                Err(msg) => {
+                    let _ = msg.sender.send(Err(CdpError::TargetCrashed));
+                }
+            }
+        } else if msg.is_navigation() {
+            self.on_target_navigate(
+                target,
+                NavigateRequest {
+                    cmd: msg,
+                    wait_until: Default::default(),
+                    timeout: None,
+                },
             );
         } else {
-            let _ = self.submit_external_command(msg, now);
+            self.submit_target_command(target.target_id().clone(), msg, now);
+        }
+    }
+
+    /// Process a navigation requested by the target's page via channel
+    fn on_target_navigate(&mut self, target: &mut Target, req: NavigateRequest) {
+        let NavigateRequest {
+            cmd,
+            wait_until,
+            timeout,
+        } = req;
+        if target.is_crashed() {
+            let _ = cmd.sender.send(Err(CdpError::TargetCrashed));
+            return;
         }
+        let (req, tx) = cmd.split();
+        let id = self.next_navigation_id();
+        let mut req = FrameNavigationRequest::new(id, req);
+        req.wait_until = wait_until;
+        if let Some(timeout) = timeout {
+            req.timeout = timeout;
+        }
+        target.goto(req);
+        self.navigations.insert(
+            id,
+            NavigationRequest::Navigate(NavigationInProgress::new(tx, timeout)),
+        );
     }
 
     /// An identifier for queued `NavigationRequest`s.
@@ -377,7 +488,11 @@ impl Handler {
                         Ok(call_id) => {
                             self.pending_commands.insert(
                                 call_id,
-                                (PendingRequest::CreateTarget(tx), method, Instant::now()),
+                                (
+                                    PendingRequest::CreateTarget(tx),
+                                    method,
+                                    self.deadline(Instant::now(), None),
+                                ),
                             );
                         }
                         Err(err) => {
@@ -399,8 +514,36 @@ impl Handler {
     fn on_event(&mut self, event: CdpEventMessage) {
         if let Some(ref session_id) = event.session_id {
             if let Some(session) = self.sessions.get(session_id.as_str()) {
-                if let Some(target) = self.targets.get_mut(session.target_id()) {
-                    return target.on_event(event);
+                let target_id = session.target_id().clone();
+                match &event.params {
+                    CdpEvent::TargetAttachedToTarget(ev) if ev.target_info.r#type == "iframe" => {
+                        // out-of-process iframes are driven by the target of their page
+                        self.sessions.insert(
+                            ev.session_id.clone(),
+                            Session::new(ev.session_id.clone(), target_id.clone()),
+                        );
+                    }
+                    CdpEvent::TargetDetachedFromTarget(ev)
+                        if self
+                            .sessions
+                            .get(&ev.session_id)
+                            .is_some_and(|s| s.target_id() == &target_id) =>
+                    {
+                        self.sessions.remove(&ev.session_id);
+                    }
+                    _ => {}
+                }
+                if let Some(target) = self.targets.get_mut(&target_id) {
+                    let crashed = matches!(event.params, CdpEvent::InspectorTargetCrashed(_))
+                        && target.session_id().map(AsRef::as_ref) == Some(session_id.as_str());
+                    target.on_event(event);
+                    if crashed {
+                        let navigations = target.frame_manager_mut().drain_navigations();
+                        self.fail_target_requests(&target_id, navigations, || {
+                            CdpError::TargetCrashed
+                        });
+                    }
+                    return;
                 }
             }
         }
@@ -437,6 +580,12 @@ impl Handler {
                 viewport: self.config.viewport.clone(),
                 request_intercept: self.config.request_intercept,
                 cache_enabled: self.config.cache_enabled,
+                trace_console: self.config.trace_console,
+                extra_headers: self.config.extra_headers.clone(),
+                blocked_urls: self.config.blocked_urls.clone(),
+                resource_filters: self.config.resource_filters.clone(),
+                adblock: self.config.adblock.clone(),
+                response_buffer_size: self.config.response_buffer_size,
             },
             browser_ctx,
         );
@@ -467,27 +616,72 @@ impl Handler {
 
     /// Fired when the target was destroyed in the browser
     fn on_target_destroyed(&mut self, event: EventTargetDestroyed) {
-        if let Some(target) = self.targets.remove(&event.target_id) {
-            // TODO shutdown?
+        if let Some(mut target) = self.targets.remove(&event.target_id) {
+            target.on_closed();
+            let navigations = target.frame_manager_mut().drain_navigations();
+            self.fail_target_requests(&event.target_id, navigations, || CdpError::TargetClosed);
             if let Some(session) = target.session_id() {
                 self.sessions.remove(session);
             }
+            // also drop the sessions of the target's out-of-process iframes
+            self.sessions
+                .retain(|_, session| session.target_id() != &event.target_id);
+        }
+    }
+
+    /// Fails all pending commands of the target and its `navigations` with
+    /// the error returned by `err`.
+    fn fail_target_requests(
+        &mut self,
+        target_id: &TargetId,
+        navigations: Vec<NavigationId>,
+        err: impl Fn() -> CdpError,
+    ) {
+        for id in &navigations {
+            if let Some(NavigationRequest::Navigate(nav)) = self.navigations.remove(id) {
+                let _ = nav.tx.send(Err(err()));
+            }
+        }
+        let failed =
+            self.pending_commands
+                .iter()
+                .filter(|(_, (req, _, _))| match req {
+                    PendingRequest::TargetCommand(id, _, _)
+                    | PendingRequest::InternalCommand(id) => id == target_id,
+                    PendingRequest::Navigate(id) => navigations.contains(id),
+                    _ => false,
+                })
+                .map(|(k, _)| *k)
+                .collect::<Vec<_>>();
+        for call in failed {
+            if let Some((PendingRequest::TargetCommand(_, _, tx), _, _)) =
+                self.pending_commands.remove(&call)
+            {
+                let _ = tx.send(Err(err()));
+            }
         }
     }
 
+    /// The instant after which a command submitted at `now` times out, `None`
+    /// if it never does. Commands time out after the `request_timeout` unless
+    /// they set their own `timeout`.
+    fn deadline(&self, now: Instant, timeout: Option<Duration>) -> Option<Instant> {
+        now.checked_add(timeout.unwrap_or(self.config.request_timeout))
+    }
+
     /// House keeping of commands
     ///
-    /// Remove all commands where `now` > `timestamp of command starting point +
-    /// request timeout` and notify the senders that their request timed out.
+    /// Remove all commands where `now` > `deadline of the command` and notify
+    /// the senders that their request timed out.
     fn evict_timed_out_commands(&mut self, now: Instant) {
         let timed_out = self
             .pending_commands
             .iter()
-            .filter(|(_, (_, _, timestamp))| now > (*timestamp + self.config.request_timeout))
+            .filter(|(_, (_, _, deadline))| deadline.is_some_and(|deadline| now > deadline))
             .map(|(k, _)| *k)
             .collect::<Vec<_>>();
         for call in timed_out {
-            if let Some((req, _, _)) = self.pending_commands.remove(&call) {
+            if let Some((req, method, _)) = self.pending_commands.remove(&call) {
                 match req {
                     PendingRequest::CreateTarget(tx) => {
                         let _ = tx.send(Err(CdpError::Timeout));
@@ -504,10 +698,14 @@ impl Handler {
                             }
                         }
                     }
-                    PendingRequest::ExternalCommand(tx) => {
+                    PendingRequest::ExternalCommand(tx)
+                    | PendingRequest::TargetCommand(_, _, tx) => {
                         let _ = tx.send(Err(CdpError::Timeout));
                     }
                     PendingRequest::InternalCommand(_) => {}
+                    PendingRequest::LoggedCommand => {
+                        tracing::warn!("{} timed out", method);
+                    }
                     PendingRequest::CloseBrowser(tx) => {
                         let _ = tx.send(Err(CdpError::Timeout));
                     }
@@ -591,6 +789,9 @@ impl Stream for Handler {
                             TargetEvent::Command(msg) => {
                                 pin.on_target_message(&mut target, msg, now);
                             }
+                            TargetEvent::Navigate(req) => {
+                                pin.on_target_navigate(&mut target, req);
+                            }
                             TargetEvent::NavigationRequest(id, req) => {
                                 pin.submit_navigation(id, req, now);
                             }
@@ -669,6 +870,21 @@ pub struct HandlerConfig {
     pub request_intercept: bool,
     /// Whether to enable cache
     pub cache_enabled: bool,
+    /// Whether to emit console messages and page errors as `tracing` events
+    pub trace_console: bool,
+    /// Additional HTTP headers sent with every request of every page
+    pub extra_headers: HashMap<String, String>,
+    /// Url patterns that are blocked for every page
+    pub blocked_urls: Vec<String>,
+    /// Requests of every page matching any of these filters are blocked
+    pub resource_filters: Vec<ResourceFilter>,
+    /// Requests of every page blocked by the adblock engine are aborted
+    pub adblock: Option<AdblockOptions>,
+    /// Permissions granted to all origins of the default browser context
+    pub permissions: Vec<PermissionType>,
+    /// The size of the buffer in which the response bodies of every page are
+    /// kept
+    pub response_buffer_size: Option<usize>,
 }
 
 impl Default for HandlerConfig {
@@ -681,10 +897,37 @@ impl Default for HandlerConfig {
             request_timeout: Duration::from_millis(REQUEST_TIMEOUT),
             request_intercept: false,
             cache_enabled: true,
+            trace_console: false,
+            extra_headers: Default::default(),
+            blocked_urls: Vec::new(),
+            resource_filters: Vec::new(),
+            adblock: None,
+            permissions: Vec::new(),
+            response_buffer_size: None,
         }
     }
 }
 
+/// Turns a command that closes a crashed target into `Target.closeTarget` on
+/// the browser's session, since the renderer that would handle `Page.close`
+/// is gone. All other commands are returned as error.
+fn close_crashed_target(
+    mut msg: CommandMessage,
+    target_id: &TargetId,
+) -> std::result::Result<CommandMessage, CommandMessage> {
+    let method = msg.method.as_ref();
+    if method != chromiumoxide_cdp::cdp::browser_protocol::page::CloseParams::IDENTIFIER
+        && method != CloseTargetParams::IDENTIFIER
+    {
+        return Err(msg);
+    }
+    let close = CloseTargetParams::new(target_id.clone());
+    msg.method = close.identifier();
+    msg.session_id = None;
+    msg.params = serde_json::to_value(close).unwrap();
+    Ok(msg)
+}
+
 /// Wraps the sender half of the channel who requested a navigation
 #[derive(Debug)]
 pub struct NavigationInProgress<T> {
@@ -694,14 +937,17 @@ pub struct NavigationInProgress<T> {
     response: Option<Response>,
     /// Sender who initiated the navigation request
     tx: OneshotSender<T>,
+    /// Overrides the `request_timeout` of the navigation command
+    timeout: Option<Duration>,
 }
 
 impl<T> NavigationInProgress<T> {
-    fn new(tx: OneshotSender<T>) -> Self {
+    fn new(tx: OneshotSender<T>, timeout: Option<Duration>) -> Self {
         Self {
             navigated: false,
             response: None,
             tx,
+            timeout,
         }
     }
 
@@ -740,11 +986,17 @@ enum PendingRequest {
     /// after the `Target` notifies the `Handler` that the `Page` has finished
     /// loading, which comes after the response.
     Navigate(NavigationId),
-    /// A common request received via a channel (`Page`).
+    /// A common request received via a channel (`Browser`).
     ExternalCommand(OneshotSender<Result<Response>>),
+    /// A request received via a channel from the `Page` of a `Target`, with
+    /// the session it was sent to.
+    TargetCommand(TargetId, Option<SessionId>, OneshotSender<Result<Response>>),
     /// Requests that are initiated directly from a `Target` (all the
     /// initialization commands).
     InternalCommand(TargetId),
+    /// A request of the handler itself whose failure is only logged, like
+    /// granting the configured permissions
+    LoggedCommand,
     // A Request to close the browser.
     CloseBrowser(OneshotSender<Result<CloseReturns>>),
 }
@@ -764,3 +1016,38 @@ pub(crate) enum HandlerMessage {
     AddEventListener(EventListenerRequest),
     CloseBrowser(OneshotSender<Result<CloseReturns>>),
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::oneshot::channel as oneshot_channel;
+
+    use chromiumoxide_cdp::cdp::browser_protocol::page;
+
+    use super::*;
+
+    fn command<C: chromiumoxide_types::Command>(cmd: C) -> CommandMessage {
+        let (tx, _rx) = oneshot_channel();
+        CommandMessage::with_session(cmd, tx, Some(SessionId::new("PAGE_SESSION"))).unwrap()
+    }
+
+    #[test]
+    fn closes_crashed_target_via_browser() {
+        let target_id = TargetId::new("CRASHED");
+        for msg in [
+            command(page::CloseParams::default()),
+            command(CloseTargetParams::new(target_id.clone())),
+        ] {
+            let close = close_crashed_target(msg, &target_id).unwrap();
+            assert_eq!(close.method.as_ref(), CloseTargetParams::IDENTIFIER);
+            assert_eq!(close.session_id, None);
+            assert_eq!(close.params["targetId"], "CRASHED");
+        }
+    }
+
+    #[test]
+    fn rejects_other_commands_of_crashed_target() {
+        let msg = command(page::ReloadParams::default());
+        let msg = close_crashed_target(msg, &TargetId::new("CRASHED")).unwrap_err();
+        assert_eq!(msg.method.as_ref(), page::ReloadParams::IDENTIFIER);
+    }
+}
@@ -1,22 +1,34 @@
 use chromiumoxide_cdp::cdp::browser_protocol::fetch::{
     self, AuthChallengeResponse, AuthChallengeResponseResponse, ContinueRequestParams,
-    ContinueWithAuthParams, DisableParams, EventAuthRequired, EventRequestPaused, RequestPattern,
+    ContinueWithAuthParams, DisableParams, EventAuthRequired, EventRequestPaused,
+    FailRequestParams, HeaderEntry, RequestPattern, RequestStage,
 };
 #[allow(deprecated)]
 use chromiumoxide_cdp::cdp::browser_protocol::network::{
-    EmulateNetworkConditionsParams, EventLoadingFailed, EventLoadingFinished,
-    EventRequestServedFromCache, EventRequestWillBeSent, EventResponseReceived, Headers,
-    InterceptionId, RequestId, Response, SetCacheDisabledParams, SetExtraHttpHeadersParams,
+    BlockedReason, ConnectionType, EmulateNetworkConditionsParams, ErrorReason, EventLoadingFailed,
+    EventLoadingFinished, EventRequestServedFromCache, EventRequestWillBeSent,
+    EventResponseReceived, EventWebSocketClosed, EventWebSocketCreated, EventWebSocketFrameError,
+    EventWebSocketFrameReceived, EventWebSocketFrameSent, Headers, InterceptionId, Request,
+    RequestId, ResourceType, Response, SetBlockedUrLsParams, SetCacheDisabledParams,
+    SetExtraHttpHeadersParams,
 };
 use chromiumoxide_cdp::cdp::browser_protocol::{
     network::EnableParams, security::SetIgnoreCertificateErrorsParams,
 };
 use chromiumoxide_types::{Command, Method, MethodId};
 
+use crate::adblock::{AdblockOptions, AdblockRequest, RequestType};
 use crate::auth::Credentials;
 use crate::cmd::CommandChain;
 use crate::handler::http::HttpRequest;
+use crate::handler::websocket::{FrameDirection, WebSocketConnection, WebSocketTracker};
+use crate::har::{self, HarAction, HarRoute};
+use crate::pattern::{glob_match, UrlPattern};
+use futures::channel::mpsc::UnboundedSender;
+use regex::Regex;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Debug)]
@@ -26,6 +38,8 @@ pub struct NetworkManager {
     requests: HashMap<RequestId, HttpRequest>,
     // TODO put event in an Arc?
     requests_will_be_sent: HashMap<RequestId, EventRequestWillBeSent>,
+    /// Requests that were sent but did neither finish nor fail loading yet
+    inflight_requests: HashSet<RequestId>,
     extra_headers: HashMap<String, String>,
     request_id_to_interception_id: HashMap<RequestId, InterceptionId>,
     user_cache_disabled: bool,
@@ -33,7 +47,32 @@ pub struct NetworkManager {
     credentials: Option<Credentials>,
     user_request_interception_enabled: bool,
     protocol_request_interception_enabled: bool,
-    offline: bool,
+    network_conditions: NetworkConditions,
+    /// Rewrites applied to the headers of matching requests
+    header_rewrites: Vec<HeaderRewrite>,
+    /// Url patterns blocked via `Network.setBlockedURLs`
+    blocked_urls: Vec<String>,
+    /// Requests matching any of these filters are aborted
+    resource_filters: Vec<ResourceFilter>,
+    /// Url patterns of requests that are paused in the response stage, to be
+    /// continued by the user
+    response_patterns: Vec<String>,
+    /// The size of the buffer in which chromium keeps response bodies
+    response_buffer_size: Option<usize>,
+    /// Requests that were aborted by a resource filter, by their network id
+    filtered_requests: HashSet<RequestId>,
+    /// Counters of the requests that were blocked so far
+    blocked_requests: BlockedRequests,
+    /// Requests matching the filter lists of the engine are aborted
+    adblock: Option<AdblockOptions>,
+    /// The open websocket connections
+    websockets: WebSocketTracker,
+    /// Archives requests are served from
+    har_routes: Vec<HarRoute>,
+    /// Requests that are recorded into an archive once their body loaded, by
+    /// their network id, mapped to the index of the route and the request
+    /// once its response was received
+    har_recordings: HashMap<RequestId, (usize, Option<HttpRequest>)>,
     request_timeout: Duration,
 }
 
@@ -44,6 +83,7 @@ impl NetworkManager {
             ignore_httpserrors,
             requests: Default::default(),
             requests_will_be_sent: Default::default(),
+            inflight_requests: Default::default(),
             extra_headers: Default::default(),
             request_id_to_interception_id: Default::default(),
             user_cache_disabled: false,
@@ -51,13 +91,24 @@ impl NetworkManager {
             credentials: None,
             user_request_interception_enabled: false,
             protocol_request_interception_enabled: false,
-            offline: false,
+            network_conditions: Default::default(),
+            header_rewrites: Vec::new(),
+            blocked_urls: Vec::new(),
+            resource_filters: Vec::new(),
+            response_patterns: Vec::new(),
+            response_buffer_size: None,
+            filtered_requests: Default::default(),
+            blocked_requests: Default::default(),
+            adblock: None,
+            websockets: Default::default(),
+            har_routes: Vec::new(),
+            har_recordings: Default::default(),
             request_timeout,
         }
     }
 
     pub fn init_commands(&self) -> CommandChain {
-        let enable = EnableParams::default();
+        let enable = self.enable_params();
         let cmds = if self.ignore_httpserrors {
             let ignore = SetIgnoreCertificateErrorsParams::new(true);
             vec![
@@ -70,6 +121,68 @@ impl NetworkManager {
         CommandChain::new(cmds, self.request_timeout)
     }
 
+    /// The commands to execute in order to bring a newly attached session, like
+    /// the one of an out-of-process iframe, in line with the current state of
+    /// this network manager.
+    pub fn session_init_commands(&self) -> Vec<(MethodId, serde_json::Value)> {
+        let enable = self.enable_params();
+        let mut cmds = vec![(enable.identifier(), serde_json::to_value(enable).unwrap())];
+        if self.ignore_httpserrors {
+            let ignore = SetIgnoreCertificateErrorsParams::new(true);
+            cmds.push((ignore.identifier(), serde_json::to_value(ignore).unwrap()));
+        }
+        let cache = SetCacheDisabledParams::new(
+            self.user_cache_disabled || self.protocol_request_interception_enabled,
+        );
+        cmds.push((cache.identifier(), serde_json::to_value(cache).unwrap()));
+        if !self.extra_headers.is_empty() {
+            let headers = SetExtraHttpHeadersParams::new(Headers::new(
+                serde_json::to_value(self.extra_headers.clone()).unwrap(),
+            ));
+            cmds.push((headers.identifier(), serde_json::to_value(headers).unwrap()));
+        }
+        if !self.blocked_urls.is_empty() {
+            let blocked = SetBlockedUrLsParams::new(self.blocked_urls.clone());
+            cmds.push((blocked.identifier(), serde_json::to_value(blocked).unwrap()));
+        }
+        if self.network_conditions != NetworkConditions::default() {
+            let conditions = self.network_conditions.params();
+            cmds.push((
+                conditions.identifier(),
+                serde_json::to_value(conditions).unwrap(),
+            ));
+        }
+        if self.protocol_request_interception_enabled {
+            let fetch = self.fetch_enable_params();
+            cmds.push((fetch.identifier(), serde_json::to_value(fetch).unwrap()));
+        }
+        cmds
+    }
+
+    fn enable_params(&self) -> EnableParams {
+        match self.response_buffer_size {
+            Some(size) => EnableParams::builder()
+                .max_total_buffer_size(size as i64)
+                .max_resource_buffer_size(size as i64)
+                .enable_durable_messages(true)
+                .build(),
+            None => EnableParams::default(),
+        }
+    }
+
+    fn fetch_enable_params(&self) -> fetch::EnableParams {
+        fetch::EnableParams::builder()
+            .handle_auth_requests(true)
+            .pattern(RequestPattern::builder().url_pattern("*").build())
+            .patterns(self.response_patterns.iter().map(|pattern| {
+                RequestPattern::builder()
+                    .url_pattern(pattern.clone())
+                    .request_stage(RequestStage::Response)
+                    .build()
+            }))
+            .build()
+    }
+
     fn push_cdp_request<T: Command>(&mut self, cmd: T) {
         let method = cmd.identifier();
         let params = serde_json::to_value(cmd).expect("Command should not panic");
@@ -82,6 +195,16 @@ impl NetworkManager {
         self.queued_events.pop_front()
     }
 
+    /// The request with the given id that has not finished yet
+    pub fn request(&self, request_id: &RequestId) -> Option<&HttpRequest> {
+        self.requests.get(request_id)
+    }
+
+    /// The number of requests that are currently in flight
+    pub fn inflight_requests(&self) -> usize {
+        self.inflight_requests.len()
+    }
+
     pub fn extra_headers(&self) -> &HashMap<String, String> {
         &self.extra_headers
     }
@@ -113,28 +236,114 @@ impl NetworkManager {
         self.update_protocol_request_interception()
     }
 
+    /// Rewrites the headers of all requests that match the rewrite's pattern
+    pub fn add_header_rewrite(&mut self, rewrite: HeaderRewrite) {
+        self.header_rewrites.push(rewrite);
+        self.update_protocol_request_interception()
+    }
+
+    /// Blocks all requests whose url matches any of the patterns, in which
+    /// `*` is a wildcard
+    pub fn set_blocked_urls(&mut self, urls: Vec<String>) {
+        self.blocked_urls = urls;
+        self.push_cdp_request(SetBlockedUrLsParams::new(self.blocked_urls.clone()));
+    }
+
+    /// Aborts all requests that match any of the filters
+    pub fn set_resource_filters(&mut self, filters: Vec<ResourceFilter>) {
+        self.resource_filters = filters;
+        self.update_protocol_request_interception()
+    }
+
+    /// Pauses the responses of all requests whose url matches any of the
+    /// patterns, in which `*` is a wildcard, until the user continues them
+    pub fn set_response_interception(&mut self, patterns: Vec<String>) {
+        let was_enabled = self.protocol_request_interception_enabled;
+        self.response_patterns = patterns;
+        self.update_protocol_request_interception();
+        if was_enabled && self.protocol_request_interception_enabled {
+            self.push_cdp_request(self.fetch_enable_params())
+        }
+    }
+
+    /// Keeps up to `size` bytes of response bodies in chromium's buffers,
+    /// also across cross-process navigations, so that they are not evicted
+    /// before they are read. Only affects sessions attached afterwards.
+    pub fn set_response_buffer_size(&mut self, size: Option<usize>) {
+        self.response_buffer_size = size;
+    }
+
+    /// Aborts all requests that the adblock engine blocks, `None` disables
+    /// adblocking
+    pub fn set_adblock(&mut self, adblock: Option<AdblockOptions>) {
+        self.adblock = adblock;
+        self.update_protocol_request_interception()
+    }
+
+    pub fn adblock(&self) -> Option<&AdblockOptions> {
+        self.adblock.as_ref()
+    }
+
+    /// Serves requests from the archive of the route, see
+    /// `Page::route_from_har`
+    pub fn add_har_route(&mut self, route: HarRoute) {
+        self.har_routes.push(route);
+        self.update_protocol_request_interception()
+    }
+
+    /// The requests that were blocked so far
+    pub fn blocked_requests(&self) -> &BlockedRequests {
+        &self.blocked_requests
+    }
+
     fn update_protocol_request_interception(&mut self) {
-        let enabled = self.user_request_interception_enabled || self.credentials.is_some();
+        let enabled = self.user_request_interception_enabled
+            || self.credentials.is_some()
+            || !self.header_rewrites.is_empty()
+            || !self.resource_filters.is_empty()
+            || !self.response_patterns.is_empty()
+            || self.adblock.is_some()
+            || !self.har_routes.is_empty();
         if enabled == self.protocol_request_interception_enabled {
             return;
         }
         self.update_protocol_cache_disabled();
         self.protocol_request_interception_enabled = enabled;
         if enabled {
-            self.push_cdp_request(
-                fetch::EnableParams::builder()
-                    .handle_auth_requests(true)
-                    .pattern(RequestPattern::builder().url_pattern("*").build())
-                    .build(),
-            )
+            self.push_cdp_request(self.fetch_enable_params())
         } else {
             self.push_cdp_request(DisableParams::default())
         }
     }
 
-    pub fn on_fetch_request_paused(&mut self, event: &EventRequestPaused) {
+    pub fn on_fetch_request_paused(
+        &mut self,
+        event: &EventRequestPaused,
+        source: RequestSource<'_>,
+    ) {
+        // paused responses are continued by the user, see
+        // `set_response_interception`
+        if event.response_status_code.is_some() || event.response_error_reason.is_some() {
+            return;
+        }
         if !self.user_request_interception_enabled && self.protocol_request_interception_enabled {
-            self.push_cdp_request(ContinueRequestParams::new(event.request_id.clone()))
+            if self
+                .resource_filters
+                .iter()
+                .any(|filter| filter.matches(&event.request.url, &event.resource_type))
+                || self.is_adblocked(event, source)
+            {
+                self.blocked_requests.add(event.resource_type.clone());
+                if let Some(network_id) = event.network_id.as_ref() {
+                    self.filtered_requests.insert(network_id.clone());
+                }
+                self.push_cdp_request(FailRequestParams::new(
+                    event.request_id.clone(),
+                    ErrorReason::BlockedByClient,
+                ))
+            } else {
+                self.route_paused_request(event)
+            }
         }
         if let Some(network_id) = event.network_id.as_ref() {
             if let Some(request_will_be_sent) =
@@ -148,6 +357,86 @@ impl NetworkManager {
         }
     }
 
+    /// Serves the paused request from the first archive that handles its url
+    /// or continues it
+    fn route_paused_request(&mut self, event: &EventRequestPaused) {
+        let route = self
+            .har_routes
+            .iter()
+            .position(|route| route.matches(&event.request.url));
+        let action = match route {
+            Some(idx) => self.har_routes[idx].route(event.request_id.inner(), &event.request),
+            None => HarAction::Fallback,
+        };
+        match action {
+            HarAction::Fulfill(params) => self.push_cdp_request(*params),
+            HarAction::Abort => self.push_cdp_request(FailRequestParams::new(
+                event.request_id.clone(),
+                ErrorReason::Failed,
+            )),
+            HarAction::Record => {
+                if let (Some(idx), Some(network_id)) = (route, event.network_id.as_ref()) {
+                    self.har_recordings.insert(network_id.clone(), (idx, None));
+                }
+                self.continue_paused_request(event)
+            }
+            HarAction::Fallback => self.continue_paused_request(event),
+        }
+    }
+
+    fn continue_paused_request(&mut self, event: &EventRequestPaused) {
+        let mut params = ContinueRequestParams::new(event.request_id.clone());
+        params.headers = self.rewrite_headers(&event.request);
+        self.push_cdp_request(params)
+    }
+
+    /// Whether the adblock engine blocks the paused request
+    fn is_adblocked(&self, event: &EventRequestPaused, source: RequestSource<'_>) -> bool {
+        match self.adblock.as_ref() {
+            Some(adblock) => {
+                let request_type = RequestType::new(&event.resource_type, source.subdocument);
+                let source_url = source.url.unwrap_or(&event.request.url);
+                adblock.engine.should_block(&AdblockRequest::new(
+                    &event.request.url,
+                    source_url,
+                    request_type,
+                ))
+            }
+            None => false,
+        }
+    }
+
+    /// The headers of the request after applying all matching rewrites, or
+    /// `None` if no rewrite matches
+    fn rewrite_headers(&self, request: &Request) -> Option<Vec<HeaderEntry>> {
+        let mut rewrites = self
+            .header_rewrites
+            .iter()
+            .filter(|rewrite| rewrite.pattern.matches(&request.url))
+            .peekable();
+        rewrites.peek()?;
+        let mut headers = request
+            .headers
+            .inner()
+            .as_object()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default();
+        for rewrite in rewrites {
+            (rewrite.rewrite)(&mut headers);
+        }
+        Some(
+            headers
+                .into_iter()
+                .map(|(name, value)| HeaderEntry::new(name, value))
+                .collect(),
+        )
+    }
+
     pub fn on_fetch_auth_required(&mut self, event: &EventAuthRequired) {
         let response = if self
             .attempted_authentications
@@ -171,26 +460,25 @@ impl NetworkManager {
     }
 
     pub fn set_offline_mode(&mut self, value: bool) {
-        if self.offline == value {
+        if self.network_conditions.offline == value {
             return;
         }
-        self.offline = value;
-        self.push_cdp_request(
-            // This event was recently deprecated, so we continue to use it for now
-            // if some users are on older versions of chromium.
-            #[allow(deprecated)]
-            EmulateNetworkConditionsParams::builder()
-                .offline(self.offline)
-                .latency(0)
-                .download_throughput(-1.)
-                .upload_throughput(-1.)
-                .build()
-                .unwrap(),
-        );
+        self.network_conditions.offline = value;
+        let params = self.network_conditions.params();
+        self.push_cdp_request(params);
+    }
+
+    /// Remembers the emulated conditions of the network, so they're applied
+    /// to every session that is set up from now on
+    pub fn set_network_conditions(&mut self, conditions: NetworkConditions) {
+        self.network_conditions = conditions;
     }
 
     /// Request interception doesn't happen for data URLs with Network Service.
     pub fn on_request_will_be_sent(&mut self, event: &EventRequestWillBeSent) {
+        if !event.request.url.starts_with("data:") {
+            self.inflight_requests.insert(event.request_id.clone());
+        }
         if self.protocol_request_interception_enabled && !event.request.url.starts_with("data:") {
             if let Some(interception_id) = self
                 .request_id_to_interception_id
@@ -216,12 +504,20 @@ impl NetworkManager {
     pub fn on_response_received(&mut self, event: &EventResponseReceived) {
         if let Some(mut request) = self.requests.remove(event.request_id.as_ref()) {
             request.set_response(event.response.clone());
+            if let Some((_, recording)) = self.har_recordings.get_mut(event.request_id.as_ref()) {
+                *recording = Some(request.clone());
+            }
             self.queued_events
                 .push_back(NetworkEvent::RequestFinished(request))
         }
     }
 
     pub fn on_network_loading_finished(&mut self, event: &EventLoadingFinished) {
+        self.inflight_requests.remove(event.request_id.as_ref());
+        self.filtered_requests.remove(event.request_id.as_ref());
+        if let Some((idx, Some(request))) = self.har_recordings.remove(event.request_id.as_ref()) {
+            self.har_routes[idx].record(request);
+        }
         if let Some(request) = self.requests.remove(event.request_id.as_ref()) {
             if let Some(interception_id) = request.interception_id.as_ref() {
                 self.attempted_authentications
@@ -233,6 +529,14 @@ impl NetworkManager {
     }
 
     pub fn on_network_loading_failed(&mut self, event: &EventLoadingFailed) {
+        self.inflight_requests.remove(event.request_id.as_ref());
+        self.har_recordings.remove(event.request_id.as_ref());
+        // requests aborted by a resource filter are already counted
+        if !self.filtered_requests.remove(event.request_id.as_ref())
+            && event.blocked_reason == Some(BlockedReason::Inspector)
+        {
+            self.blocked_requests.add(event.r#type.clone());
+        }
         if let Some(mut request) = self.requests.remove(event.request_id.as_ref()) {
             request.failure_text = Some(event.error_text.clone());
             if let Some(interception_id) = request.interception_id.as_ref() {
@@ -244,6 +548,48 @@ impl NetworkManager {
         }
     }
 
+    /// The main frame navigated to a new document, whose requests no longer
+    /// report the failures of the aborted ones
+    pub fn on_main_frame_navigated(&mut self) {
+        self.filtered_requests.clear();
+    }
+
+    /// Reports all websocket connections opened from now on to the
+    /// `listener`
+    pub fn add_websocket_listener(&mut self, listener: UnboundedSender<WebSocketConnection>) {
+        self.websockets.add_listener(listener)
+    }
+
+    pub fn on_websocket_created(&mut self, event: &EventWebSocketCreated) {
+        self.websockets.on_created(event)
+    }
+
+    pub fn on_websocket_frame_sent(&mut self, event: &EventWebSocketFrameSent) {
+        self.websockets.on_frame(
+            &event.request_id,
+            FrameDirection::Sent,
+            &event.timestamp,
+            &event.response,
+        )
+    }
+
+    pub fn on_websocket_frame_received(&mut self, event: &EventWebSocketFrameReceived) {
+        self.websockets.on_frame(
+            &event.request_id,
+            FrameDirection::Received,
+            &event.timestamp,
+            &event.response,
+        )
+    }
+
+    pub fn on_websocket_frame_error(&mut self, event: &EventWebSocketFrameError) {
+        self.websockets.on_frame_error(event)
+    }
+
+    pub fn on_websocket_closed(&mut self, event: &EventWebSocketClosed) {
+        self.websockets.on_closed(event)
+    }
+
     fn on_request(
         &mut self,
         event: &EventRequestWillBeSent,
@@ -257,13 +603,25 @@ impl NetworkManager {
                 redirect_chain.push(request);
             }
         }
-        let request = HttpRequest::new(
+        let mut request = HttpRequest::new(
             event.request_id.clone(),
             event.frame_id.clone(),
             interception_id,
             self.user_request_interception_enabled,
             redirect_chain,
         );
+        request.url = Some(event.request.url.clone());
+        request.method = Some(event.request.method.clone());
+        request.post_data = har::post_data(&event.request);
+        request.resource_type = event.r#type.as_ref().map(|ty| ty.as_ref().to_string());
+        request.is_navigation_request = event.request_id.inner() == event.loader_id.inner()
+            && event.r#type == Some(ResourceType::Document);
+        if let Some(headers) = event.request.headers.inner().as_object() {
+            request.headers = headers
+                .iter()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                .collect();
+        }
 
         self.requests.insert(event.request_id.clone(), request);
         self.queued_events
@@ -279,6 +637,220 @@ impl NetworkManager {
     }
 }
 
+/// Decides which requests of a page are blocked.
+///
+/// Requests are matched when they are intercepted, so that a blocked request
+/// never reaches the network.
+#[derive(Debug, Clone)]
+pub enum ResourceFilter {
+    /// Blocks all requests of the resource type
+    ResourceType(ResourceType),
+    /// Blocks all requests whose url matches the glob pattern, see
+    /// [`UrlPattern`]
+    Glob(String),
+    /// Blocks all requests whose url matches the regular expression
+    Regex(Regex),
+}
+
+impl ResourceFilter {
+    /// Creates a filter that blocks all requests whose url matches the
+    /// regular expression
+    pub fn regex(re: &str) -> Result<Self, regex::Error> {
+        Ok(ResourceFilter::Regex(Regex::new(re)?))
+    }
+
+    /// Whether a request for the `url` of the `resource_type` is blocked by
+    /// this filter
+    pub fn matches(&self, url: &str, resource_type: &ResourceType) -> bool {
+        match self {
+            ResourceFilter::ResourceType(ty) => ty == resource_type,
+            ResourceFilter::Glob(glob) => glob_match(glob, url),
+            ResourceFilter::Regex(re) => re.is_match(url),
+        }
+    }
+}
+
+impl From<ResourceType> for ResourceFilter {
+    fn from(ty: ResourceType) -> Self {
+        ResourceFilter::ResourceType(ty)
+    }
+}
+
+impl From<Regex> for ResourceFilter {
+    fn from(re: Regex) -> Self {
+        ResourceFilter::Regex(re)
+    }
+}
+
+impl From<&str> for ResourceFilter {
+    fn from(glob: &str) -> Self {
+        ResourceFilter::Glob(glob.to_string())
+    }
+}
+
+impl From<String> for ResourceFilter {
+    fn from(glob: String) -> Self {
+        ResourceFilter::Glob(glob)
+    }
+}
+
+/// Where a paused request originates from, which the filters of an
+/// `AdblockEngine` are matched against
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestSource<'a> {
+    /// The url of the document that issued the request, `None` for requests
+    /// of the main frame's document
+    pub url: Option<&'a str>,
+    /// Whether the request is for the document of a child frame
+    pub subdocument: bool,
+}
+
+/// Counters of the requests of a page that were blocked, either by a
+/// [`ResourceFilter`], an `AdblockEngine` or by a blocked url pattern
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockedRequests {
+    /// The number of all blocked requests
+    pub total: usize,
+    /// The number of blocked requests by their resource type
+    pub by_resource_type: HashMap<ResourceType, usize>,
+}
+
+impl BlockedRequests {
+    fn add(&mut self, resource_type: ResourceType) {
+        self.total += 1;
+        *self.by_resource_type.entry(resource_type).or_default() += 1;
+    }
+}
+
+/// Rewrites the headers of a request, by name
+pub type RewriteFn = Arc<dyn Fn(&mut HashMap<String, String>) + Send + Sync>;
+
+/// Adds, removes or modifies the headers of all requests whose url matches a
+/// pattern.
+#[derive(Clone)]
+pub struct HeaderRewrite {
+    /// The requests whose headers are rewritten
+    pub pattern: UrlPattern,
+    /// Rewrites the headers of a matching request
+    pub rewrite: RewriteFn,
+}
+
+impl HeaderRewrite {
+    pub fn new(
+        pattern: impl Into<UrlPattern>,
+        rewrite: impl Fn(&mut HashMap<String, String>) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            pattern: pattern.into(),
+            rewrite: Arc::new(rewrite),
+        }
+    }
+}
+
+impl fmt::Debug for HeaderRewrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeaderRewrite")
+            .field("pattern", &self.pattern)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The conditions of the network a page is connected to.
+///
+/// The default does not throttle the network at all.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NetworkConditions {
+    /// Whether the page is disconnected from the network
+    pub offline: bool,
+    /// Minimum latency from a request being sent to its response headers
+    /// being received
+    pub latency: Duration,
+    /// Maximal download throughput in bytes per second, `None` disables
+    /// download throttling
+    pub download_throughput: Option<f64>,
+    /// Maximal upload throughput in bytes per second, `None` disables upload
+    /// throttling
+    pub upload_throughput: Option<f64>,
+    /// Percentage of packets that are dropped, between 0 and 100
+    pub packet_loss: Option<f64>,
+    /// The connection type reported by `navigator.connection`
+    pub connection_type: Option<ConnectionType>,
+}
+
+impl NetworkConditions {
+    /// Creates custom conditions with the given latency and throughputs in
+    /// bytes per second
+    pub fn new(latency: Duration, download_throughput: f64, upload_throughput: f64) -> Self {
+        Self {
+            latency,
+            download_throughput: Some(download_throughput),
+            upload_throughput: Some(upload_throughput),
+            ..Default::default()
+        }
+    }
+
+    /// No network connection at all
+    pub fn offline() -> Self {
+        Self {
+            offline: true,
+            connection_type: Some(ConnectionType::None),
+            ..Default::default()
+        }
+    }
+
+    /// A slow 3G connection, like the "Slow 3G" preset of the DevTools
+    pub fn slow_3g() -> Self {
+        Self {
+            connection_type: Some(ConnectionType::Cellular3g),
+            ..Self::new(Duration::from_millis(2000), 50_000., 50_000.)
+        }
+    }
+
+    /// A fast 3G connection, like the "Fast 3G" preset of the DevTools
+    pub fn fast_3g() -> Self {
+        Self {
+            connection_type: Some(ConnectionType::Cellular3g),
+            ..Self::new(Duration::from_micros(562_500), 180_000., 84_375.)
+        }
+    }
+
+    /// A 4G connection, like the "Fast 4G" preset of the DevTools
+    pub fn four_g() -> Self {
+        Self {
+            connection_type: Some(ConnectionType::Cellular4g),
+            ..Self::new(Duration::from_millis(165), 1_012_500., 168_750.)
+        }
+    }
+
+    /// Drops the given percentage of packets
+    pub fn packet_loss(mut self, percent: f64) -> Self {
+        self.packet_loss = Some(percent);
+        self
+    }
+
+    /// Sets the connection type reported by `navigator.connection`
+    pub fn connection_type(mut self, connection_type: ConnectionType) -> Self {
+        self.connection_type = Some(connection_type);
+        self
+    }
+
+    #[allow(deprecated)]
+    pub(crate) fn params(&self) -> EmulateNetworkConditionsParams {
+        // This event was recently deprecated, so we continue to use it for now
+        // if some users are on older versions of chromium.
+        let mut params = EmulateNetworkConditionsParams::builder()
+            .offline(self.offline)
+            .latency(self.latency.as_secs_f64() * 1000.)
+            .download_throughput(self.download_throughput.unwrap_or(-1.))
+            .upload_throughput(self.upload_throughput.unwrap_or(-1.))
+            .build()
+            .unwrap();
+        params.packet_loss = self.packet_loss;
+        params.connection_type = self.connection_type.clone();
+        params
+    }
+}
+
 #[derive(Debug)]
 pub enum NetworkEvent {
     SendCdpRequest((MethodId, serde_json::Value)),
@@ -287,3 +859,415 @@ pub enum NetworkEvent {
     RequestFailed(HttpRequest),
     RequestFinished(HttpRequest),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::har::{Har, HarNotFound, HarReplayOptions};
+
+    fn request(url: &str, headers: serde_json::Value) -> Request {
+        serde_json::from_value(serde_json::json!({
+            "url": url,
+            "method": "GET",
+            "headers": headers,
+            "initialPriority": "High",
+            "referrerPolicy": "no-referrer",
+        }))
+        .unwrap()
+    }
+
+    fn sorted(headers: Vec<HeaderEntry>) -> Vec<(String, String)> {
+        let mut headers: Vec<_> = headers.into_iter().map(|h| (h.name, h.value)).collect();
+        headers.sort();
+        headers
+    }
+
+    fn paused(id: &str, url: &str, resource_type: &str) -> EventRequestPaused {
+        serde_json::from_value(serde_json::json!({
+            "requestId": format!("interception-{id}"),
+            "request": request(url, serde_json::json!({})),
+            "frameId": "frame",
+            "resourceType": resource_type,
+            "networkId": id,
+        }))
+        .unwrap()
+    }
+
+    fn failed(id: &str, resource_type: &str, blocked_reason: Option<&str>) -> EventLoadingFailed {
+        let mut event = serde_json::json!({
+            "requestId": id,
+            "timestamp": 0.,
+            "type": resource_type,
+            "errorText": "net::ERR_BLOCKED_BY_CLIENT",
+        });
+        if let Some(reason) = blocked_reason {
+            event["blockedReason"] = reason.into();
+        }
+        serde_json::from_value(event).unwrap()
+    }
+
+    fn will_be_sent(id: &str, url: &str) -> EventRequestWillBeSent {
+        serde_json::from_value(serde_json::json!({
+            "requestId": id,
+            "loaderId": "loader",
+            "documentURL": "https://example.com/",
+            "request": request(url, serde_json::json!({})),
+            "timestamp": 0.,
+            "wallTime": 0.,
+            "initiator": {"type": "other"},
+            "redirectHasExtraInfo": false,
+            "type": "Document",
+            "frameId": "frame",
+        }))
+        .unwrap()
+    }
+
+    fn response_received(id: &str, url: &str) -> EventResponseReceived {
+        serde_json::from_value(serde_json::json!({
+            "requestId": id,
+            "loaderId": "loader",
+            "timestamp": 0.,
+            "type": "Document",
+            "response": {
+                "url": url,
+                "status": 200,
+                "statusText": "OK",
+                "headers": {},
+                "mimeType": "text/html",
+                "charset": "utf-8",
+                "connectionReused": false,
+                "connectionId": 0.,
+                "encodedDataLength": 0.,
+                "securityState": "secure",
+            },
+            "hasExtraInfo": false,
+            "frameId": "frame",
+        }))
+        .unwrap()
+    }
+
+    fn loading_finished(id: &str) -> EventLoadingFinished {
+        serde_json::from_value(serde_json::json!({
+            "requestId": id,
+            "timestamp": 0.,
+            "encodedDataLength": 0.,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn finishes_requests_once_their_response_arrived() {
+        let mut manager = NetworkManager::new(false, Duration::from_secs(30));
+        manager.on_request_will_be_sent(&will_be_sent("1", "https://example.com/"));
+        assert!(matches!(manager.poll(), Some(NetworkEvent::Request(_))));
+
+        manager.on_response_received(&response_received("1", "https://example.com/"));
+        match manager.poll() {
+            Some(NetworkEvent::RequestFinished(request)) => {
+                assert_eq!(request.response.map(|r| r.status), Some(200))
+            }
+            event => panic!("expected a finished request, got {event:?}"),
+        }
+        assert!(manager.request(&RequestId::new("1")).is_none());
+        // the body is still loading
+        assert_eq!(manager.inflight_requests(), 1);
+
+        manager.on_network_loading_finished(&loading_finished("1"));
+        assert!(manager.poll().is_none());
+        assert_eq!(manager.inflight_requests(), 0);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn maps_network_conditions_to_params() {
+        let params = NetworkConditions::default().params();
+        assert!(!params.offline);
+        assert_eq!(params.latency, 0.);
+        assert_eq!(params.download_throughput, -1.);
+        assert_eq!(params.upload_throughput, -1.);
+        assert_eq!(params.packet_loss, None);
+        assert_eq!(params.connection_type, None);
+
+        let params = NetworkConditions::offline().params();
+        assert!(params.offline);
+        assert_eq!(params.connection_type, Some(ConnectionType::None));
+
+        let params = NetworkConditions::slow_3g().params();
+        assert_eq!(params.latency, 2000.);
+        assert_eq!(params.download_throughput, 50_000.);
+        assert_eq!(params.upload_throughput, 50_000.);
+        assert_eq!(params.connection_type, Some(ConnectionType::Cellular3g));
+
+        let params = NetworkConditions::fast_3g().params();
+        assert_eq!(params.latency, 562.5);
+        assert_eq!(params.download_throughput, 180_000.);
+        assert_eq!(params.upload_throughput, 84_375.);
+        assert_eq!(params.connection_type, Some(ConnectionType::Cellular3g));
+
+        let params = NetworkConditions::four_g().packet_loss(5.).params();
+        assert_eq!(params.latency, 165.);
+        assert_eq!(params.download_throughput, 1_012_500.);
+        assert_eq!(params.upload_throughput, 168_750.);
+        assert_eq!(params.packet_loss, Some(5.));
+        assert_eq!(params.connection_type, Some(ConnectionType::Cellular4g));
+    }
+
+    #[test]
+    fn resource_filters_match_globs() {
+        let filter = ResourceFilter::from("https://*.example.com/**/*.png");
+        assert!(filter.matches("https://cdn.example.com/img/logo.png", &ResourceType::Image));
+        assert!(filter.matches("https://cdn.example.com/a/b/logo.png", &ResourceType::Other));
+        assert!(!filter.matches("https://example.com/img/logo.png", &ResourceType::Image));
+        assert!(!filter.matches("https://cdn.example.com/img/logo.jpg", &ResourceType::Image));
+    }
+
+    #[test]
+    fn resource_filters_match_regexes() {
+        let filter = ResourceFilter::regex(r"^https://[^/]+/ads?/").unwrap();
+        assert!(filter.matches("https://example.com/ad/banner.js", &ResourceType::Script));
+        assert!(filter.matches("https://example.com/ads/banner.js", &ResourceType::Script));
+        assert!(!filter.matches(
+            "https://example.com/static/ads/banner.js",
+            &ResourceType::Script
+        ));
+        assert!(ResourceFilter::regex("(").is_err());
+    }
+
+    #[test]
+    fn resource_filters_match_resource_types() {
+        let filter = ResourceFilter::from(ResourceType::Font);
+        assert!(filter.matches("https://example.com/font.woff2", &ResourceType::Font));
+        assert!(!filter.matches("https://example.com/font.woff2", &ResourceType::Stylesheet));
+    }
+
+    #[test]
+    fn counts_blocked_requests() {
+        let mut manager = NetworkManager::new(false, Duration::from_secs(30));
+        manager.set_resource_filters(vec![ResourceType::Image.into(), "**/tracker.js".into()]);
+
+        manager.on_fetch_request_paused(
+            &paused("1", "https://example.com/a.png", "Image"),
+            RequestSource::default(),
+        );
+        manager.on_fetch_request_paused(
+            &paused("2", "https://example.com/b.png", "Image"),
+            RequestSource::default(),
+        );
+        manager.on_fetch_request_paused(
+            &paused("3", "https://example.com/tracker.js", "Script"),
+            RequestSource::default(),
+        );
+        manager.on_fetch_request_paused(
+            &paused("4", "https://example.com/app.js", "Script"),
+            RequestSource::default(),
+        );
+        // the aborted requests fail afterwards, which must not count them twice
+        manager.on_network_loading_failed(&failed("1", "Image", Some("inspector")));
+        manager.on_network_loading_failed(&failed("3", "Script", Some("inspector")));
+        // blocked by a url pattern of `Network.setBlockedURLs`
+        manager.on_network_loading_failed(&failed("5", "Script", Some("inspector")));
+        // blocked by the browser for other reasons
+        manager.on_network_loading_failed(&failed("6", "Script", Some("mixed-content")));
+        manager.on_network_loading_failed(&failed("7", "Script", None));
+
+        let blocked = manager.blocked_requests();
+        assert_eq!(blocked.total, 4);
+        assert_eq!(blocked.by_resource_type[&ResourceType::Image], 2);
+        assert_eq!(blocked.by_resource_type[&ResourceType::Script], 2);
+        assert_eq!(blocked.by_resource_type.len(), 2);
+    }
+
+    #[test]
+    fn forgets_filtered_requests_once_they_ended() {
+        let mut manager = NetworkManager::new(false, Duration::from_secs(30));
+        manager.set_resource_filters(vec![ResourceType::Image.into()]);
+        for id in ["1", "2", "3"] {
+            manager.on_fetch_request_paused(
+                &paused(id, &format!("https://example.com/{id}.png"), "Image"),
+                RequestSource::default(),
+            );
+        }
+        assert_eq!(manager.filtered_requests.len(), 3);
+
+        manager.on_network_loading_failed(&failed("1", "Image", Some("inspector")));
+        manager.on_network_loading_finished(&loading_finished("2"));
+        assert_eq!(
+            manager.filtered_requests,
+            HashSet::from([RequestId::new("3")])
+        );
+
+        manager.on_main_frame_navigated();
+        assert!(manager.filtered_requests.is_empty());
+        assert_eq!(manager.blocked_requests().total, 3);
+    }
+
+    fn sent_cdp_requests(manager: &mut NetworkManager) -> Vec<(MethodId, serde_json::Value)> {
+        std::iter::from_fn(|| manager.poll())
+            .filter_map(|event| match event {
+                NetworkEvent::SendCdpRequest(req) => Some(req),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn routes_paused_requests_through_archives() {
+        let har: Har = serde_json::from_value(serde_json::json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "test", "version": "1" },
+                "entries": [{
+                    "startedDateTime": "2024-01-31T12:00:00.000Z",
+                    "request": { "method": "GET", "url": "https://example.com/cached" },
+                    "response": { "status": 200, "content": { "text": "hi" } },
+                }],
+            }
+        }))
+        .unwrap();
+        let (tx, mut recorded) = futures::channel::mpsc::unbounded();
+        let mut manager = NetworkManager::new(false, Duration::from_secs(30));
+        manager.add_har_route(HarRoute::new(
+            Arc::new(Mutex::new(har)),
+            HarReplayOptions {
+                url_filter: Some("https://example.com/**".into()),
+                update: true,
+                ..Default::default()
+            },
+            Some(tx),
+        ));
+        manager.add_har_route(HarRoute::new(
+            Default::default(),
+            HarReplayOptions {
+                url_filter: Some("https://other.example/**".into()),
+                not_found: HarNotFound::Abort,
+                ..Default::default()
+            },
+            None,
+        ));
+        sent_cdp_requests(&mut manager);
+
+        let mut route = |id: &str, url: &str| {
+            manager.on_request_will_be_sent(&will_be_sent(id, url));
+            manager.on_fetch_request_paused(&paused(id, url, "Document"), RequestSource::default());
+            let sent = sent_cdp_requests(&mut manager);
+            let (method, params) = sent.last().unwrap().clone();
+            assert_eq!(params["requestId"], format!("interception-{id}"));
+            method
+        };
+        assert_eq!(
+            route("1", "https://example.com/cached"),
+            "Fetch.fulfillRequest"
+        );
+        assert_eq!(
+            route("2", "https://example.com/new"),
+            "Fetch.continueRequest"
+        );
+        assert_eq!(route("3", "https://other.example/new"), "Fetch.failRequest");
+        assert_eq!(
+            route("4", "https://unrouted.example/"),
+            "Fetch.continueRequest"
+        );
+        assert_eq!(
+            route("5", "https://example.com/failed"),
+            "Fetch.continueRequest"
+        );
+        assert_eq!(manager.har_recordings.len(), 2);
+
+        manager.on_response_received(&response_received("2", "https://example.com/new"));
+        manager.on_network_loading_finished(&loading_finished("2"));
+        manager.on_network_loading_failed(&failed("5", "Document", None));
+        assert!(manager.har_recordings.is_empty());
+
+        let request = recorded.try_recv().unwrap();
+        assert_eq!(request.url.as_deref(), Some("https://example.com/new"));
+        assert!(request.response.is_some());
+        assert!(recorded.try_recv().is_err());
+    }
+
+    #[test]
+    fn leaves_intercepted_responses_to_the_user() {
+        let mut manager = NetworkManager::new(false, Duration::from_secs(30));
+        manager.set_response_interception(vec!["*.zip".to_string()]);
+        let sent = sent_cdp_requests(&mut manager);
+        let (method, params) = sent.last().unwrap();
+        assert_eq!(method, "Fetch.enable");
+        assert_eq!(
+            params["patterns"],
+            serde_json::json!([
+                { "urlPattern": "*" },
+                { "urlPattern": "*.zip", "requestStage": "Response" },
+            ])
+        );
+
+        let mut event = paused("1", "https://example.com/a.zip", "Other");
+        manager.on_fetch_request_paused(&event, RequestSource::default());
+        assert_eq!(
+            sent_cdp_requests(&mut manager)[0].0,
+            "Fetch.continueRequest"
+        );
+        event.response_status_code = Some(200);
+        manager.on_fetch_request_paused(&event, RequestSource::default());
+        assert!(sent_cdp_requests(&mut manager).is_empty());
+
+        manager.set_response_interception(Vec::new());
+        assert_eq!(
+            sent_cdp_requests(&mut manager).last().unwrap().0,
+            "Fetch.disable"
+        );
+    }
+
+    #[test]
+    fn rewrites_headers_of_matching_requests() {
+        let mut manager = NetworkManager::new(false, Duration::from_secs(30));
+        manager.add_header_rewrite(HeaderRewrite::new(
+            "https://api.example.com/**",
+            |headers| {
+                headers.insert("x-trace-id".to_string(), "1234".to_string());
+                headers.remove("Authorization");
+            },
+        ));
+        let headers = serde_json::json!({ "Authorization": "secret", "Accept": "*/*" });
+
+        let rewritten = manager
+            .rewrite_headers(&request(
+                "https://api.example.com/v1/users",
+                headers.clone(),
+            ))
+            .unwrap();
+        assert_eq!(
+            sorted(rewritten),
+            vec![
+                ("Accept".to_string(), "*/*".to_string()),
+                ("x-trace-id".to_string(), "1234".to_string()),
+            ]
+        );
+        assert!(manager
+            .rewrite_headers(&request("https://example.com/", headers))
+            .is_none());
+    }
+
+    #[test]
+    fn applies_header_rewrites_in_order() {
+        let mut manager = NetworkManager::new(false, Duration::from_secs(30));
+        manager.add_header_rewrite(HeaderRewrite::new("**", |headers| {
+            headers.insert("x-step".to_string(), "first".to_string());
+        }));
+        manager.add_header_rewrite(HeaderRewrite::new("**/*.js", |headers| {
+            let step = headers.get("x-step").cloned().unwrap_or_default();
+            headers.insert("x-step".to_string(), format!("{step},second"));
+        }));
+
+        let rewritten = manager
+            .rewrite_headers(&request(
+                "https://example.com/app.js",
+                serde_json::json!({ "x-invalid": 1 }),
+            ))
+            .unwrap();
+        assert_eq!(
+            sorted(rewritten),
+            vec![("x-step".to_string(), "first,second".to_string())]
+        );
+    }
+}
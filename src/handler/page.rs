@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::channel::mpsc::{channel, Receiver, Sender};
 use futures::channel::oneshot::channel as oneshot_channel;
@@ -19,26 +20,78 @@ use chromiumoxide_cdp::cdp::browser_protocol::input::{
     MouseButton,
 };
 use chromiumoxide_cdp::cdp::browser_protocol::page::{
-    FrameId, GetLayoutMetricsParams, GetLayoutMetricsReturns, Viewport,
+    CaptureScreenshotFormat, CaptureScreenshotParams, FrameId, GetLayoutMetricsParams,
+    GetLayoutMetricsReturns, Viewport,
 };
 use chromiumoxide_cdp::cdp::browser_protocol::target::{ActivateTargetParams, SessionId, TargetId};
 use chromiumoxide_cdp::cdp::js_protocol::runtime::{
-    CallFunctionOnParams, CallFunctionOnReturns, EvaluateParams, ExecutionContextId, RemoteObjectId,
+    CallArgument, CallFunctionOnParams, CallFunctionOnReturns, EvaluateParams, ExecutionContextId,
+    RemoteObjectId,
 };
 use chromiumoxide_types::{Command, CommandResponse};
 
+use crate::bitmap::Bitmap;
 use crate::cmd::{to_command_response, CommandMessage};
 use crate::error::{CdpError, Result};
 use crate::handler::commandfuture::CommandFuture;
 use crate::handler::domworld::DOMWorldKind;
+use crate::handler::frame::WaitUntil;
 use crate::handler::httpfuture::HttpFuture;
-use crate::handler::target::{GetExecutionContext, TargetMessage};
+use crate::handler::target::{GetExecutionContext, NavigateRequest, TargetMessage};
 use crate::handler::target_message_future::TargetMessageFuture;
 use crate::js::EvaluationResult;
 use crate::layout::Point;
 use crate::page::ScreenshotParams;
 use crate::{keys, utils, ArcHttpRequest};
 
+/// The maximum height of a tile of a tiled screenshot in CSS pixels, which
+/// stays below the texture size limit even on high density displays
+const MAX_TILE_HEIGHT: f64 = 4096.;
+
+const DEFAULT_MASK_COLOR: &str = "#FF00FF";
+
+/// Covers the elements matching the mask selectors with boxes and injects the
+/// styles that pause animations and hide the caret
+const DECORATE_SCREENSHOT: &str = r#"function(mask, color, pauseAnimations, hideCaret) {
+    const marker = 'data-chromiumoxide-screenshot';
+    let css = '';
+    if (pauseAnimations) {
+        css += '*, *::before, *::after { animation-play-state: paused !important; transition: none !important; }';
+    }
+    if (hideCaret) {
+        css += '*, *::before, *::after { caret-color: transparent !important; }';
+    }
+    if (css) {
+        const style = document.createElement('style');
+        style.setAttribute(marker, '');
+        style.textContent = css;
+        document.documentElement.appendChild(style);
+    }
+    if (mask.length) {
+        const container = document.createElement('div');
+        container.setAttribute(marker, '');
+        container.style.cssText = 'position: absolute; top: 0; left: 0; width: 0; height: 0; z-index: 2147483647; pointer-events: none;';
+        for (const selector of mask) {
+            for (const element of document.querySelectorAll(selector)) {
+                const rect = element.getBoundingClientRect();
+                const box = document.createElement('div');
+                box.style.position = 'absolute';
+                box.style.left = `${rect.left + window.scrollX}px`;
+                box.style.top = `${rect.top + window.scrollY}px`;
+                box.style.width = `${rect.width}px`;
+                box.style.height = `${rect.height}px`;
+                box.style.background = color;
+                container.appendChild(box);
+            }
+        }
+        document.documentElement.appendChild(container);
+    }
+}"#;
+
+const UNDECORATE_SCREENSHOT: &str = r#"function() {
+    document.querySelectorAll('[data-chromiumoxide-screenshot]').forEach(e => e.remove());
+}"#;
+
 #[derive(Debug)]
 pub struct PageHandle {
     pub(crate) rx: Fuse<Receiver<TargetMessage>>,
@@ -76,7 +129,58 @@ pub(crate) struct PageInner {
 impl PageInner {
     /// Execute a PDL command and return its response
     pub(crate) async fn execute<T: Command>(&self, cmd: T) -> Result<CommandResponse<T::Response>> {
-        execute(cmd, self.sender.clone(), Some(self.session_id.clone())).await
+        execute(
+            cmd,
+            self.sender.clone(),
+            Some(self.session_id.clone()),
+            None,
+        )
+        .await
+    }
+
+    /// Same as `execute`, but waits up to `timeout` instead of the request
+    /// timeout for the response, `Duration::MAX` waits forever
+    pub(crate) async fn execute_with_timeout<T: Command>(
+        &self,
+        cmd: T,
+        timeout: Duration,
+    ) -> Result<CommandResponse<T::Response>> {
+        execute(
+            cmd,
+            self.sender.clone(),
+            Some(self.session_id.clone()),
+            Some(timeout),
+        )
+        .await
+    }
+
+    /// Execute a PDL command within another session of this page's target,
+    /// like the session of an out-of-process iframe
+    pub(crate) async fn execute_in_session<T: Command>(
+        &self,
+        cmd: T,
+        session: SessionId,
+    ) -> Result<CommandResponse<T::Response>> {
+        execute(cmd, self.sender.clone(), Some(session), None).await
+    }
+
+    /// Submit a PDL command without waiting for its response.
+    ///
+    /// Used to release protocol resources where awaiting isn't possible, like
+    /// in `Drop` implementations. Every clone of the sender has a guaranteed
+    /// slot in the channel, so the command is queued even if the channel is
+    /// full and is only lost if the target is gone.
+    pub(crate) fn execute_detached<T: Command>(&self, cmd: T) {
+        let method = cmd.identifier();
+        let (tx, _) = oneshot_channel();
+        match CommandMessage::with_session(cmd, tx, Some(self.session_id.clone())) {
+            Ok(msg) => {
+                if let Err(err) = self.sender.clone().try_send(TargetMessage::Command(msg)) {
+                    tracing::warn!("Failed to submit {}: {}", method, err);
+                }
+            }
+            Err(err) => tracing::warn!("Failed to serialize {}: {}", method, err),
+        }
     }
 
     /// Create a PDL command future
@@ -89,6 +193,41 @@ impl PageInner {
         TargetMessageFuture::<ArcHttpRequest>::wait_for_navigation(self.sender.clone())
     }
 
+    /// This creates navigation future with the final http response once the
+    /// main frame reached `wait_until`
+    pub(crate) fn wait_for_navigation_until(
+        &self,
+        wait_until: WaitUntil,
+    ) -> TargetMessageFuture<ArcHttpRequest> {
+        TargetMessageFuture::<ArcHttpRequest>::wait_for_navigation_until(
+            self.sender.clone(),
+            wait_until,
+        )
+    }
+
+    /// Execute a PDL command that triggers a navigation of the main frame and
+    /// wait until the navigation reached `wait_until`
+    pub(crate) async fn navigate<T: Command>(
+        &self,
+        cmd: T,
+        wait_until: WaitUntil,
+        timeout: Option<Duration>,
+    ) -> Result<CommandResponse<T::Response>> {
+        let (tx, rx) = oneshot_channel();
+        let method = cmd.identifier();
+        let cmd = CommandMessage::with_session(cmd, tx, Some(self.session_id.clone()))?;
+        self.sender
+            .clone()
+            .send(TargetMessage::Navigate(NavigateRequest {
+                cmd,
+                wait_until,
+                timeout,
+            }))
+            .await?;
+        let resp = rx.await??;
+        to_command_response::<T>(resp, method)
+    }
+
     /// This creates HTTP future with navigation and responds with the final
     /// http response when the page is loaded
     pub(crate) fn http_future<T: Command>(&self, cmd: T) -> Result<HttpFuture<T>> {
@@ -290,6 +429,16 @@ impl PageInner {
     pub async fn evaluate_expression(
         &self,
         evaluate: impl Into<EvaluateParams>,
+    ) -> Result<EvaluationResult> {
+        self.evaluate_expression_with_timeout(evaluate, None).await
+    }
+
+    /// Same as `evaluate_expression`, but waits up to `timeout` instead of
+    /// the request timeout for the evaluation to finish
+    pub async fn evaluate_expression_with_timeout(
+        &self,
+        evaluate: impl Into<EvaluateParams>,
+        timeout: Option<Duration>,
     ) -> Result<EvaluationResult> {
         let mut evaluate = evaluate.into();
         if evaluate.context_id.is_none() {
@@ -302,7 +451,14 @@ impl PageInner {
             evaluate.return_by_value = Some(true);
         }
 
-        let resp = self.execute(evaluate).await?.result;
+        let resp = execute(
+            evaluate,
+            self.sender.clone(),
+            Some(self.session_id.clone()),
+            timeout,
+        )
+        .await?
+        .result;
         if let Some(exception) = resp.exception_details {
             return Err(CdpError::JavascriptException(Box::new(exception)));
         }
@@ -388,55 +544,141 @@ impl PageInner {
         let params = params.into();
         let full_page = params.full_page();
         let omit_background = params.omit_background();
+        let tiled = full_page && params.tiled();
+        let pause_animations = params.pause_animations();
+        let hide_caret = params.hide_caret();
 
         let mut cdp_params = params.cdp_params;
+        let resized = full_page && !tiled;
+        let decorated = !params.mask.is_empty() || pause_animations || hide_caret;
 
-        if full_page {
-            let metrics = self.layout_metrics().await?;
-            let width = metrics.css_content_size.width;
-            let height = metrics.css_content_size.height;
-
-            cdp_params.clip = Some(Viewport {
-                x: 0.,
-                y: 0.,
-                width,
-                height,
-                scale: 1.,
-            });
+        let metrics = if full_page {
+            Some(self.layout_metrics().await?)
+        } else {
+            None
+        };
 
-            self.execute(SetDeviceMetricsOverrideParams::new(
-                width as i64,
-                height as i64,
-                1.,
-                false,
-            ))
-            .await?;
+        let res = async {
+            let mut tiled_size = None;
+            if let Some(metrics) = metrics {
+                let width = metrics.css_content_size.width;
+                let height = metrics.css_content_size.height;
+
+                if tiled {
+                    // resizing the viewport to the whole page is what exceeds the
+                    // texture size, so the tiles are captured beyond the viewport
+                    tiled_size = Some((width.ceil(), height.ceil()));
+                } else {
+                    cdp_params.clip = Some(Viewport {
+                        x: 0.,
+                        y: 0.,
+                        width,
+                        height,
+                        scale: 1.,
+                    });
+
+                    self.execute(SetDeviceMetricsOverrideParams::new(
+                        width as i64,
+                        height as i64,
+                        1.,
+                        false,
+                    ))
+                    .await?;
+                }
+            }
+
+            // masks are placed after resizing, since that may change the layout
+            if decorated {
+                let mask_color = params
+                    .mask_color
+                    .unwrap_or_else(|| DEFAULT_MASK_COLOR.to_string());
+                let decorate = CallFunctionOnParams::builder()
+                    .function_declaration(DECORATE_SCREENSHOT)
+                    .argument(CallArgument::builder().value(params.mask).build())
+                    .argument(CallArgument::builder().value(mask_color).build())
+                    .argument(CallArgument::builder().value(pause_animations).build())
+                    .argument(CallArgument::builder().value(hide_caret).build())
+                    .build()
+                    .map_err(CdpError::msg)?;
+                self.evaluate_function(decorate).await?;
+            }
+
+            if omit_background {
+                self.execute(SetDefaultBackgroundColorOverrideParams {
+                    color: Some(Rgba {
+                        r: 0,
+                        g: 0,
+                        b: 0,
+                        a: Some(0.),
+                    }),
+                })
+                .await?;
+            }
+
+            match tiled_size {
+                Some((width, height)) => self.capture_tiles(cdp_params, width, height).await,
+                None => {
+                    let res = self.execute(cdp_params).await?;
+                    Ok(utils::base64::decode(&res.result.data)?)
+                }
+            }
         }
+        .await;
 
+        // the page is restored even if the screenshot failed, the first error
+        // is returned
+        let mut restored = Ok(());
         if omit_background {
-            self.execute(SetDefaultBackgroundColorOverrideParams {
-                color: Some(Rgba {
-                    r: 0,
-                    g: 0,
-                    b: 0,
-                    a: Some(0.),
-                }),
-            })
-            .await?;
+            let reset = SetDefaultBackgroundColorOverrideParams { color: None };
+            restored = restored.and(self.execute(reset).await.map(drop));
         }
-
-        let res = self.execute(cdp_params).await?.result;
-
-        if omit_background {
-            self.execute(SetDefaultBackgroundColorOverrideParams { color: None })
-                .await?;
+        if decorated {
+            restored = restored.and(
+                self.evaluate_function(UNDECORATE_SCREENSHOT)
+                    .await
+                    .map(drop),
+            );
         }
+        if resized {
+            let clear = ClearDeviceMetricsOverrideParams {};
+            restored = restored.and(self.execute(clear).await.map(drop));
+        }
+        res.and_then(|data| restored.map(|_| data))
+    }
 
-        if full_page {
-            self.execute(ClearDeviceMetricsOverrideParams {}).await?;
+    /// Captures the area of the given size from the top left corner of the
+    /// page in tiles of at most `MAX_TILE_HEIGHT` and stitches them together
+    async fn capture_tiles(
+        &self,
+        mut cdp_params: CaptureScreenshotParams,
+        width: f64,
+        height: f64,
+    ) -> Result<Vec<u8>> {
+        if cdp_params
+            .format
+            .as_ref()
+            .is_some_and(|format| format != &CaptureScreenshotFormat::Png)
+        {
+            return Err(CdpError::msg("Tiled screenshots are only supported as png"));
         }
+        cdp_params.capture_beyond_viewport = Some(true);
 
-        Ok(utils::base64::decode(&res.data)?)
+        let mut tiles = Vec::new();
+        let mut y = 0.;
+        while y < height {
+            let tile_height = (height - y).min(MAX_TILE_HEIGHT);
+            cdp_params.clip = Some(Viewport {
+                x: 0.,
+                y,
+                width,
+                height: tile_height,
+                scale: 1.,
+            });
+            let res = self.execute(cdp_params.clone()).await?.result;
+            tiles.push(Bitmap::from_png(&utils::base64::decode(&res.data)?)?);
+            y += tile_height;
+        }
+        Bitmap::stack(tiles)?.to_png()
     }
 }
 
@@ -444,12 +686,92 @@ pub(crate) async fn execute<T: Command>(
     cmd: T,
     mut sender: Sender<TargetMessage>,
     session: Option<SessionId>,
+    timeout: Option<Duration>,
 ) -> Result<CommandResponse<T::Response>> {
     let (tx, rx) = oneshot_channel();
     let method = cmd.identifier();
-    let msg = CommandMessage::with_session(cmd, tx, session)?;
+    let mut msg = CommandMessage::with_session(cmd, tx, session)?;
+    msg.timeout = timeout;
 
     sender.send(TargetMessage::Command(msg)).await?;
     let resp = rx.await??;
     to_command_response::<T>(resp, method)
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::future::{select, Either};
+
+    use chromiumoxide_types::{CallId, Error, Response};
+
+    use super::*;
+
+    /// Answers the messages of the page, failing the command with the
+    /// `failing` method, and records the methods of the commands
+    async fn serve(handle: &mut PageHandle, failing: &str, methods: &mut Vec<String>) {
+        while let Some(msg) = handle.rx.next().await {
+            match msg {
+                TargetMessage::GetExecutionContext(ctx) => {
+                    let _ = ctx.tx.send(None);
+                }
+                TargetMessage::Command(msg) => {
+                    let method = msg.method.to_string();
+                    let (result, error) = if method == failing && !methods.contains(&method) {
+                        let error = Error {
+                            code: -32000,
+                            message: "failed".to_string(),
+                        };
+                        (None, Some(error))
+                    } else if method == "Runtime.callFunctionOn" {
+                        (
+                            Some(serde_json::json!({ "result": { "type": "undefined" } })),
+                            None,
+                        )
+                    } else {
+                        (Some(serde_json::json!({})), None)
+                    };
+                    methods.push(method);
+                    let _ = msg.sender.send(Ok(Response {
+                        id: CallId::new(methods.len()),
+                        result,
+                        error,
+                    }));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn restores_the_page_after_a_failed_screenshot() {
+        let mut handle = PageHandle::new(TargetId::new("target"), SessionId::new("session"), None);
+        let page = Arc::clone(handle.inner());
+        let params = ScreenshotParams::builder()
+            .omit_background(true)
+            .mask("#ad")
+            .build();
+        let mut methods = Vec::new();
+        let screenshot = Box::pin(page.screenshot(params));
+        let server = Box::pin(serve(
+            &mut handle,
+            "Emulation.setDefaultBackgroundColorOverride",
+            &mut methods,
+        ));
+        let res = match select(screenshot, server).await {
+            Either::Left((res, _)) => res,
+            Either::Right(_) => panic!("the page handle was dropped"),
+        };
+        assert!(res.is_err());
+        assert_eq!(
+            methods,
+            [
+                "Target.activateTarget",
+                "Runtime.callFunctionOn",
+                "Emulation.setDefaultBackgroundColorOverride",
+                // the background and the mask are restored regardless
+                "Emulation.setDefaultBackgroundColorOverride",
+                "Runtime.callFunctionOn",
+            ]
+        );
+    }
+}
@@ -1,27 +1,38 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Instant;
 
 use chromiumoxide_cdp::cdp::browser_protocol::target::DetachFromTargetParams;
+use futures::channel::mpsc::UnboundedSender;
 use futures::channel::oneshot::Sender;
 use futures::stream::Stream;
 use futures::task::{Context, Poll};
+use futures_timer::Delay;
+use std::future::Future;
 
-use chromiumoxide_cdp::cdp::browser_protocol::page::{FrameId, GetFrameTreeParams};
+use chromiumoxide_cdp::cdp::browser_protocol::page::{
+    AddScriptToEvaluateOnNewDocumentParams, FrameId, GetFrameTreeParams, NavigationType,
+};
 use chromiumoxide_cdp::cdp::browser_protocol::{
     browser::BrowserContextId,
-    log as cdplog, performance,
-    target::{AttachToTargetParams, SessionId, SetAutoAttachParams, TargetId, TargetInfo},
+    inspector, log as cdplog, performance,
+    target::{
+        AttachToTargetParams, EventAttachedToTarget, SessionId, SetAutoAttachParams, TargetId,
+        TargetInfo,
+    },
 };
 use chromiumoxide_cdp::cdp::events::CdpEvent;
 use chromiumoxide_cdp::cdp::CdpEventMessage;
-use chromiumoxide_types::{Command, Method, Request, Response};
+use chromiumoxide_types::{Command, Method, MethodId, Request, Response};
 
+use crate::adblock::AdblockOptions;
 use crate::auth::Credentials;
 use crate::cdp::browser_protocol::target::CloseTargetParams;
 use crate::cmd::CommandChain;
 use crate::cmd::CommandMessage;
+use crate::console::{ConsoleMessage, PageError};
 use crate::error::{CdpError, Result};
 use crate::handler::browser::BrowserContext;
 use crate::handler::domworld::DOMWorldKind;
@@ -29,18 +40,42 @@ use crate::handler::emulation::EmulationManager;
 use crate::handler::frame::{
     FrameEvent, FrameManager, NavigationError, NavigationId, NavigationOk,
 };
-use crate::handler::frame::{FrameNavigationRequest, UTILITY_WORLD_NAME};
-use crate::handler::network::{NetworkEvent, NetworkManager};
+use crate::handler::frame::{FrameNavigationRequest, WaitUntil, UTILITY_WORLD_NAME};
+use crate::handler::http::HttpRequest;
+use crate::handler::network::{
+    BlockedRequests, HeaderRewrite, NetworkConditions, NetworkEvent, NetworkManager, RequestSource,
+    ResourceFilter,
+};
 use crate::handler::page::PageHandle;
 use crate::handler::viewport::Viewport;
+use crate::handler::websocket::WebSocketConnection;
 use crate::handler::{PageInner, REQUEST_TIMEOUT};
+use crate::har::HarRoute;
 use crate::listeners::{EventListenerRequest, EventListeners};
+use crate::pattern::UrlPattern;
 use crate::{page::Page, ArcHttpRequest};
+use chromiumoxide_cdp::cdp::browser_protocol::fetch::EventRequestPaused;
+use chromiumoxide_cdp::cdp::browser_protocol::network::ResourceType;
 use chromiumoxide_cdp::cdp::js_protocol::runtime::{
-    ExecutionContextId, RunIfWaitingForDebuggerParams,
+    AddBindingParams, EvaluateParams, EventBindingCalled, EventExecutionContextCreated,
+    ExecutionContextId, RemoveBindingParams, RunIfWaitingForDebuggerParams,
 };
 use std::time::Duration;
 
+/// Appends a stylesheet with the css passed as argument to the document
+const INJECT_STYLE: &str = r#"css => {
+    const inject = () => {
+        const style = document.createElement('style');
+        style.textContent = css;
+        (document.head || document.documentElement).appendChild(style);
+    };
+    if (document.documentElement) {
+        inject();
+    } else {
+        document.addEventListener('DOMContentLoaded', inject, { once: true });
+    }
+}"#;
+
 macro_rules! advance_state {
     ($s:ident, $cx:ident, $now:ident, $cmds: ident, $next_state:expr ) => {{
         if let Poll::Ready(poll) = $cmds.poll($now) {
@@ -89,9 +124,30 @@ pub struct Target {
     /// All registered event subscriptions
     event_listeners: EventListeners,
     /// Senders that need to be notified once the main frame has loaded
-    wait_for_frame_navigation: Vec<Sender<ArcHttpRequest>>,
+    wait_for_frame_navigation: Vec<WaitForNavigation>,
+    /// Senders that need to be notified once a matching request is sent
+    wait_for_request: Vec<WaitForRequest>,
+    /// Senders that need to be notified once a matching response is received
+    wait_for_response: Vec<WaitForRequest>,
+    /// Senders that need to be notified once the url of the main frame
+    /// matches
+    wait_for_url: Vec<WaitForUrl>,
+    /// Wakes up the target once the deadline of a navigation or network idle
+    /// condition is reached
+    deadline_timer: Option<Delay>,
     /// The sender who requested the page.
     initiator: Option<Sender<Result<Page>>>,
+    /// Intercepted requests of out-of-process iframes mapped to the session
+    /// they were paused in
+    oopif_interceptions: HashMap<String, SessionId>,
+    /// Bindings exposed via `Page::expose_rust_fn`, by name
+    bindings: HashMap<String, ExposeBinding>,
+    /// Whether the renderer of this target crashed
+    crashed: bool,
+    /// Senders that need to be notified once the target crashed
+    wait_for_crash: Vec<Sender<()>>,
+    /// Senders that need to be notified once the target is closed
+    wait_for_close: Vec<Sender<()>>,
 }
 
 impl Target {
@@ -103,7 +159,20 @@ impl Target {
         let mut network_manager = NetworkManager::new(config.ignore_https_errors, request_timeout);
 
         network_manager.set_cache_enabled(config.cache_enabled);
+        network_manager.set_response_buffer_size(config.response_buffer_size);
         network_manager.set_request_interception(config.request_intercept);
+        if !config.extra_headers.is_empty() {
+            network_manager.set_extra_headers(config.extra_headers.clone());
+        }
+        if !config.blocked_urls.is_empty() {
+            network_manager.set_blocked_urls(config.blocked_urls.clone());
+        }
+        if !config.resource_filters.is_empty() {
+            network_manager.set_resource_filters(config.resource_filters.clone());
+        }
+        if config.adblock.is_some() {
+            network_manager.set_adblock(config.adblock.clone());
+        }
 
         Self {
             info,
@@ -116,10 +185,19 @@ impl Target {
             page: None,
             init_state: TargetInit::AttachToTarget,
             wait_for_frame_navigation: Default::default(),
+            wait_for_request: Default::default(),
+            wait_for_response: Default::default(),
+            wait_for_url: Default::default(),
+            deadline_timer: None,
             queued_events: Default::default(),
             event_listeners: Default::default(),
             initiator: None,
             browser_context,
+            oopif_interceptions: Default::default(),
+            bindings: Default::default(),
+            crashed: false,
+            wait_for_crash: Default::default(),
+            wait_for_close: Default::default(),
         }
     }
 
@@ -154,6 +232,55 @@ impl Target {
         matches!(self.init_state, TargetInit::Initialized)
     }
 
+    /// Whether the renderer of this target crashed
+    pub fn is_crashed(&self) -> bool {
+        self.crashed
+    }
+
+    /// Called when the renderer of this target crashed
+    fn on_crashed(&mut self) {
+        self.crashed = true;
+        if let Some(initiator) = self.initiator.take() {
+            let _ = initiator.send(Err(CdpError::TargetCrashed));
+        }
+        // navigations can't complete anymore
+        self.clear_waiters();
+        for tx in self.wait_for_crash.drain(..) {
+            let _ = tx.send(());
+        }
+    }
+
+    /// Drops all senders waiting for the page to reach a certain state
+    fn clear_waiters(&mut self) {
+        self.wait_for_frame_navigation.clear();
+        self.wait_for_request.clear();
+        self.wait_for_response.clear();
+        self.wait_for_url.clear();
+    }
+
+    /// Notifies the senders whose predicate matches the `request`
+    fn notify_request_waiters(waiters: &mut Vec<WaitForRequest>, request: &HttpRequest) {
+        waiters.retain(|wait| !wait.tx.is_canceled());
+        let (ready, waiting): (Vec<_>, Vec<_>) = std::mem::take(waiters)
+            .into_iter()
+            .partition(|wait| (wait.predicate)(request));
+        *waiters = waiting;
+        for wait in ready {
+            let _ = wait.tx.send(request.clone());
+        }
+    }
+
+    /// Called when this target was closed
+    pub(crate) fn on_closed(&mut self) {
+        if let Some(initiator) = self.initiator.take() {
+            let _ = initiator.send(Err(CdpError::TargetClosed));
+        }
+        self.clear_waiters();
+        for tx in self.wait_for_close.drain(..) {
+            let _ = tx.send(());
+        }
+    }
+
     /// Navigate a frame
     pub fn goto(&mut self, req: FrameNavigationRequest) {
         self.frame_manager.goto(req)
@@ -225,31 +352,78 @@ impl Target {
         }
     }
 
+    /// Called with the response to a command of the page that was sent to
+    /// the `session`, so that the remote objects and nodes it returned can be
+    /// routed back to that session.
+    pub fn on_command_response(&mut self, session: Option<&SessionId>, resp: &Response) {
+        if let Some(result) = resp.result.as_ref() {
+            let oopif_session =
+                session.filter(|session| Some(*session) != self.session_id.as_ref());
+            self.frame_manager.on_remote_ids(oopif_session, result);
+        }
+    }
+
     pub fn on_event(&mut self, event: CdpEventMessage) {
-        let CdpEventMessage { params, method, .. } = event;
+        let CdpEventMessage {
+            params,
+            method,
+            session_id,
+        } = event;
+        // events of out-of-process iframes are delivered on their own session
+        let oopif_session = session_id
+            .map(SessionId::from)
+            .filter(|session| Some(session) != self.session_id.as_ref());
         match &params {
             // `FrameManager` events
             CdpEvent::PageFrameAttached(ev) => self
                 .frame_manager
                 .on_frame_attached(ev.frame_id.clone(), Some(ev.parent_frame_id.clone())),
-            CdpEvent::PageFrameDetached(ev) => self.frame_manager.on_frame_detached(ev),
-            CdpEvent::PageFrameNavigated(ev) => self.frame_manager.on_frame_navigated(&ev.frame),
+            CdpEvent::PageFrameDetached(ev) => {
+                self.frame_manager.on_frame_detached(ev);
+                let sessions = self.frame_manager.oopif_sessions().collect::<HashSet<_>>();
+                self.oopif_interceptions
+                    .retain(|_, session| sessions.contains(session));
+            }
+            CdpEvent::PageFrameNavigated(ev) => {
+                if ev.frame.parent_id.is_none() {
+                    self.network_manager.on_main_frame_navigated();
+                }
+                self.frame_manager.on_frame_navigated(&ev.frame);
+                if ev.r#type == NavigationType::BackForwardCacheRestore {
+                    self.frame_manager.on_frame_restored_from_cache(&ev.frame);
+                }
+            }
             CdpEvent::PageNavigatedWithinDocument(ev) => {
                 self.frame_manager.on_frame_navigated_within_document(ev)
             }
             CdpEvent::RuntimeExecutionContextCreated(ev) => {
-                self.frame_manager.on_frame_execution_context_created(ev)
+                self.frame_manager.on_frame_execution_context_created(ev);
+                let session = oopif_session.clone().or_else(|| self.session_id.clone());
+                self.inject_cosmetic_css(ev, session);
             }
             CdpEvent::RuntimeExecutionContextDestroyed(ev) => {
                 self.frame_manager.on_frame_execution_context_destroyed(ev)
             }
             CdpEvent::RuntimeExecutionContextsCleared(_) => {
-                self.frame_manager.on_execution_contexts_cleared()
+                if let Some(session) = oopif_session.as_ref() {
+                    self.frame_manager
+                        .on_session_execution_contexts_cleared(session)
+                } else {
+                    self.frame_manager.on_execution_contexts_cleared()
+                }
             }
             CdpEvent::RuntimeBindingCalled(ev) => {
-                // TODO check if binding registered and payload is json
-                self.frame_manager.on_runtime_binding_called(ev)
+                self.frame_manager.on_runtime_binding_called(ev);
+                let session = oopif_session.clone().or_else(|| self.session_id.clone());
+                self.on_binding_called(ev, session);
+            }
+            CdpEvent::RuntimeConsoleApiCalled(ev) if self.config.trace_console => {
+                ConsoleMessage::from(ev).trace()
+            }
+            CdpEvent::RuntimeExceptionThrown(ev) if self.config.trace_console => {
+                PageError::from(&**ev).trace()
             }
+            CdpEvent::InspectorTargetCrashed(_) if oopif_session.is_none() => self.on_crashed(),
             CdpEvent::PageLifecycleEvent(ev) => self.frame_manager.on_page_lifecycle_event(ev),
             CdpEvent::PageFrameStartedLoading(ev) => {
                 self.frame_manager.on_frame_started_loading(ev);
@@ -257,6 +431,10 @@ impl Target {
 
             // `Target` events
             CdpEvent::TargetAttachedToTarget(ev) => {
+                if "iframe" == &ev.target_info.r#type {
+                    self.on_oopif_attached(ev);
+                }
+
                 if ev.waiting_for_debugger {
                     let runtime_cmd = RunIfWaitingForDebuggerParams::default();
 
@@ -279,10 +457,28 @@ impl Target {
                     }));
                 }
             }
+            CdpEvent::TargetDetachedFromTarget(ev) => {
+                self.frame_manager.on_detached_from_target(&ev.session_id);
+                self.oopif_interceptions
+                    .retain(|_, session| session != &ev.session_id);
+            }
 
             // `NetworkManager` events
-            CdpEvent::FetchRequestPaused(ev) => self.network_manager.on_fetch_request_paused(ev),
-            CdpEvent::FetchAuthRequired(ev) => self.network_manager.on_fetch_auth_required(ev),
+            CdpEvent::FetchRequestPaused(ev) => {
+                if let Some(session) = oopif_session {
+                    self.oopif_interceptions
+                        .insert(ev.request_id.inner().clone(), session);
+                }
+                let source = Self::request_source(&self.frame_manager, ev);
+                self.network_manager.on_fetch_request_paused(ev, source)
+            }
+            CdpEvent::FetchAuthRequired(ev) => {
+                if let Some(session) = oopif_session {
+                    self.oopif_interceptions
+                        .insert(ev.request_id.inner().clone(), session);
+                }
+                self.network_manager.on_fetch_auth_required(ev)
+            }
             CdpEvent::NetworkRequestWillBeSent(ev) => {
                 self.network_manager.on_request_will_be_sent(ev)
             }
@@ -296,14 +492,175 @@ impl Target {
             CdpEvent::NetworkLoadingFailed(ev) => {
                 self.network_manager.on_network_loading_failed(ev)
             }
+            CdpEvent::NetworkWebSocketCreated(ev) => self.network_manager.on_websocket_created(ev),
+            CdpEvent::NetworkWebSocketFrameSent(ev) => {
+                self.network_manager.on_websocket_frame_sent(ev)
+            }
+            CdpEvent::NetworkWebSocketFrameReceived(ev) => {
+                self.network_manager.on_websocket_frame_received(ev)
+            }
+            CdpEvent::NetworkWebSocketFrameError(ev) => {
+                self.network_manager.on_websocket_frame_error(ev)
+            }
+            CdpEvent::NetworkWebSocketClosed(ev) => self.network_manager.on_websocket_closed(ev),
             _ => {}
         }
+        self.frame_manager
+            .on_inflight_requests(self.network_manager.inflight_requests(), Instant::now());
         chromiumoxide_cdp::consume_event!(match params {
            |ev| self.event_listeners.start_send(ev),
            |json| { let _ = self.event_listeners.try_send_custom(&method, json);}
         });
     }
 
+    /// Called when the session of an out-of-process iframe was attached to this
+    /// target's page.
+    ///
+    /// The frames of the iframe are merged into this target's `FrameManager` and
+    /// the new session is initialized like the page's own session, before the
+    /// iframe is allowed to run.
+    fn on_oopif_attached(&mut self, ev: &EventAttachedToTarget) {
+        self.frame_manager.on_attached_to_target(ev);
+        let frame_id = FrameId::new(ev.target_info.target_id.inner().clone());
+        let auto_attach = SetAutoAttachParams::builder()
+            .flatten(true)
+            .auto_attach(true)
+            .wait_for_debugger_on_start(true)
+            .build()
+            .unwrap();
+        let cmds = self
+            .frame_manager
+            .oopif_init_commands(frame_id)
+            .into_iter()
+            .chain(self.network_manager.session_init_commands())
+            .chain(self.bindings.values().flat_map(ExposeBinding::commands))
+            .chain(std::iter::once((
+                auto_attach.identifier(),
+                serde_json::to_value(auto_attach).unwrap(),
+            )));
+        for (method, params) in cmds {
+            self.queued_events.push_back(TargetEvent::Request(Request {
+                method,
+                session_id: Some(ev.session_id.clone().into()),
+                params,
+            }));
+        }
+    }
+
+    /// The document a paused request originates from.
+    ///
+    /// Documents of child frames originate from their parent frame's document.
+    fn request_source<'a>(
+        frame_manager: &'a FrameManager,
+        ev: &EventRequestPaused,
+    ) -> RequestSource<'a> {
+        let frame = match frame_manager.frame(&ev.frame_id) {
+            Some(frame) => frame,
+            None => return RequestSource::default(),
+        };
+        if ev.resource_type != ResourceType::Document {
+            return RequestSource {
+                url: frame.url(),
+                subdocument: false,
+            };
+        }
+        match frame.parent_id().and_then(|id| frame_manager.frame(id)) {
+            Some(parent) => RequestSource {
+                url: parent.url(),
+                subdocument: true,
+            },
+            None => RequestSource::default(),
+        }
+    }
+
+    /// Hides the elements matched by the element hiding filters of the
+    /// adblock engine in the frame of a newly created default execution
+    /// context.
+    fn inject_cosmetic_css(
+        &mut self,
+        ev: &EventExecutionContextCreated,
+        session: Option<SessionId>,
+    ) {
+        let adblock = match self.network_manager.adblock() {
+            Some(adblock) if adblock.cosmetic_filtering => adblock,
+            _ => return,
+        };
+        let aux_data = match ev.context.aux_data.as_ref() {
+            Some(aux_data) if aux_data["isDefault"].as_bool().unwrap_or_default() => aux_data,
+            _ => return,
+        };
+        let url = aux_data["frameId"]
+            .as_str()
+            .and_then(|id| self.frame_manager.frame(&FrameId::new(id)))
+            .and_then(|frame| frame.url());
+        let css = match url.and_then(|url| adblock.engine.cosmetic_css(url)) {
+            Some(css) => css,
+            None => return,
+        };
+        let evaluate = EvaluateParams::builder()
+            .expression(format!(
+                "({})({})",
+                INJECT_STYLE,
+                serde_json::Value::String(css)
+            ))
+            .context_id(ev.context.id)
+            .build()
+            .unwrap();
+        self.queued_events.push_back(TargetEvent::Request(Request {
+            method: evaluate.identifier(),
+            session_id: session.map(Into::into),
+            params: serde_json::to_value(evaluate).unwrap(),
+        }));
+    }
+
+    /// Forwards a call of a binding registered via
+    /// `TargetMessage::ExposeBinding` to its channel.
+    ///
+    /// Calls of bindings that were not registered that way, or whose payload
+    /// was not produced by the binding's wrapper, are ignored.
+    fn on_binding_called(&mut self, ev: &EventBindingCalled, session: Option<SessionId>) {
+        #[derive(serde::Deserialize)]
+        struct Payload {
+            seq: u64,
+            args: serde_json::Value,
+        }
+        let (session_id, binding) = match (session, self.bindings.get(&ev.name)) {
+            (Some(session_id), Some(binding)) => (session_id, binding),
+            _ => return,
+        };
+        if let Ok(Payload { seq, args }) = serde_json::from_str(&ev.payload) {
+            let call = BindingCall {
+                name: ev.name.clone(),
+                seq,
+                args,
+                execution_context_id: ev.execution_context_id,
+                session_id,
+            };
+            if binding.tx.unbounded_send(call).is_err() {
+                // the page side of the binding is gone
+                self.bindings.remove(&ev.name);
+            }
+        }
+    }
+
+    /// All the sessions a request issued by the `NetworkManager` must be sent
+    /// to.
+    ///
+    /// Requests that continue an intercepted request go to the session the
+    /// request was paused in, settings are applied to the page and all of its
+    /// out-of-process iframes.
+    fn network_request_sessions(&mut self, params: &serde_json::Value) -> Vec<Option<SessionId>> {
+        if let Some(request_id) = params.get("requestId").and_then(|v| v.as_str()) {
+            return vec![self
+                .oopif_interceptions
+                .remove(request_id)
+                .or_else(|| self.session_id.clone())];
+        }
+        std::iter::once(self.session_id.clone())
+            .chain(self.frame_manager.oopif_sessions().cloned().map(Some))
+            .collect()
+    }
+
     /// Called when a init command timed out
     fn on_initialization_failed(&mut self) -> TargetEvent {
         if let Some(initiator) = self.initiator.take() {
@@ -417,11 +774,33 @@ impl Target {
             TargetInit::Closing => return None,
         };
         loop {
-            if let Some(frame) = self.frame_manager.main_frame() {
-                if frame.is_loaded() {
-                    while let Some(tx) = self.wait_for_frame_navigation.pop() {
-                        let _ = tx.send(frame.http_request().cloned());
-                    }
+            if !self.wait_for_frame_navigation.is_empty() {
+                let frame_manager = &self.frame_manager;
+                let (ready, waiting): (Vec<_>, Vec<_>) =
+                    std::mem::take(&mut self.wait_for_frame_navigation)
+                        .into_iter()
+                        .partition(|wait| frame_manager.is_main_frame_ready(wait.wait_until, now));
+                self.wait_for_frame_navigation = waiting;
+                let request = frame_manager
+                    .main_frame()
+                    .and_then(|frame| frame.http_request().cloned());
+                for wait in ready {
+                    let _ = wait.tx.send(request.clone());
+                }
+            }
+            if !self.wait_for_url.is_empty() {
+                let frame_manager = &self.frame_manager;
+                let url = frame_manager.main_frame().and_then(|f| f.url());
+                let (ready, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.wait_for_url)
+                    .into_iter()
+                    .filter(|wait| !wait.tx.is_canceled())
+                    .partition(|wait| {
+                        url.map(|url| wait.pattern.matches(url)).unwrap_or_default()
+                            && frame_manager.is_main_frame_ready(wait.wait_until, now)
+                    });
+                self.wait_for_url = waiting;
+                for wait in ready {
+                    let _ = wait.tx.send(url.unwrap_or_default().to_string());
                 }
             }
 
@@ -433,7 +812,14 @@ impl Target {
             if let Some(handle) = self.page.as_mut() {
                 while let Poll::Ready(Some(msg)) = Pin::new(&mut handle.rx).poll_next(cx) {
                     match msg {
-                        TargetMessage::Command(cmd) => {
+                        TargetMessage::Command(mut cmd) => {
+                            if cmd.session_id == self.session_id {
+                                if let Some(session) =
+                                    self.frame_manager.session_for_params(&cmd.params)
+                                {
+                                    cmd.session_id = Some(session.clone());
+                                }
+                            }
                             self.queued_events.push_back(TargetEvent::Command(cmd));
                         }
                         TargetMessage::MainFrame(tx) => {
@@ -471,19 +857,22 @@ impl Target {
                             let frame = self.frame_manager.frame(&frame_id);
                             let _ = tx.send(frame.and_then(|f| f.parent_id().cloned()));
                         }
-                        TargetMessage::WaitForNavigation(tx) => {
-                            if let Some(frame) = self.frame_manager.main_frame() {
-                                // TODO submit a navigation watcher: waitForFrameNavigation
-
-                                // TODO return the watchers navigationResponse
-                                if frame.is_loaded() {
-                                    let _ = tx.send(frame.http_request().cloned());
-                                } else {
-                                    self.wait_for_frame_navigation.push(tx);
-                                }
-                            } else {
-                                self.wait_for_frame_navigation.push(tx);
-                            }
+                        TargetMessage::WaitForNavigation(wait) => {
+                            // checked at the top of the next iteration
+                            self.wait_for_frame_navigation.push(wait);
+                        }
+                        TargetMessage::Navigate(req) => {
+                            self.queued_events.push_back(TargetEvent::Navigate(req));
+                        }
+                        TargetMessage::WaitForRequest(wait) => {
+                            self.wait_for_request.push(wait);
+                        }
+                        TargetMessage::WaitForResponse(wait) => {
+                            self.wait_for_response.push(wait);
+                        }
+                        TargetMessage::WaitForUrl(wait) => {
+                            // checked at the top of the next iteration
+                            self.wait_for_url.push(wait);
                         }
                         TargetMessage::AddEventListener(req) => {
                             // register a new listener
@@ -518,6 +907,90 @@ impl Target {
                         TargetMessage::Authenticate(credentials) => {
                             self.network_manager.authenticate(credentials);
                         }
+                        TargetMessage::EmulateNetworkConditions(conditions) => {
+                            // the page's own session already emulates them
+                            let params = conditions.params();
+                            for session in self.frame_manager.oopif_sessions() {
+                                self.queued_events.push_back(TargetEvent::Request(Request {
+                                    method: params.identifier(),
+                                    session_id: Some(session.clone().into()),
+                                    params: serde_json::to_value(&params).unwrap(),
+                                }));
+                            }
+                            self.network_manager.set_network_conditions(conditions);
+                        }
+                        TargetMessage::SetExtraHeaders(headers) => {
+                            self.network_manager.set_extra_headers(headers);
+                        }
+                        TargetMessage::RewriteHeaders(rewrite) => {
+                            self.network_manager.add_header_rewrite(rewrite);
+                        }
+                        TargetMessage::BlockUrls(urls) => {
+                            self.network_manager.set_blocked_urls(urls);
+                        }
+                        TargetMessage::SetResourceFilters(filters) => {
+                            self.network_manager.set_resource_filters(filters);
+                        }
+                        TargetMessage::InterceptResponses(patterns) => {
+                            self.network_manager.set_response_interception(patterns);
+                        }
+                        TargetMessage::SetAdblock(adblock) => {
+                            self.network_manager.set_adblock(adblock);
+                        }
+                        TargetMessage::RouteFromHar(route) => {
+                            self.network_manager.add_har_route(route);
+                        }
+                        TargetMessage::WebSockets(tx) => {
+                            self.network_manager.add_websocket_listener(tx);
+                        }
+                        TargetMessage::BlockedRequests(tx) => {
+                            let _ = tx.send(self.network_manager.blocked_requests().clone());
+                        }
+                        TargetMessage::WaitForCrash(tx) => {
+                            if self.crashed {
+                                let _ = tx.send(());
+                            } else {
+                                self.wait_for_crash.push(tx);
+                            }
+                        }
+                        TargetMessage::WaitForClose(tx) => {
+                            self.wait_for_close.push(tx);
+                        }
+                        TargetMessage::ExposeBinding(binding) => {
+                            // the page's own session is set up by the page itself,
+                            // out-of-process iframes are already attached
+                            for session in self.frame_manager.oopif_sessions() {
+                                for (method, params) in binding.commands() {
+                                    self.queued_events.push_back(TargetEvent::Request(Request {
+                                        method,
+                                        session_id: Some(session.clone().into()),
+                                        params,
+                                    }));
+                                }
+                            }
+                            self.bindings.insert(binding.name.clone(), binding);
+                        }
+                        TargetMessage::RemoveBinding(RemoveBinding { name, tx }) => {
+                            // the binding may have been exposed again with a
+                            // new channel since
+                            let removed = self
+                                .bindings
+                                .get(&name)
+                                .is_some_and(|binding| binding.tx.is_closed());
+                            if removed {
+                                self.bindings.remove(&name);
+                                // the page's own session is cleaned up by the page
+                                let remove = RemoveBindingParams::new(name);
+                                for session in self.frame_manager.oopif_sessions() {
+                                    self.queued_events.push_back(TargetEvent::Request(Request {
+                                        method: remove.identifier(),
+                                        session_id: Some(session.clone().into()),
+                                        params: serde_json::to_value(&remove).unwrap(),
+                                    }));
+                                }
+                            }
+                            let _ = tx.send(removed);
+                        }
                     }
                 }
             }
@@ -526,18 +999,29 @@ impl Target {
                 match event {
                     NetworkEvent::SendCdpRequest((method, params)) => {
                         // send a message to the browser
-                        self.queued_events.push_back(TargetEvent::Request(Request {
-                            method,
-                            session_id: self.session_id.clone().map(Into::into),
-                            params,
-                        }))
+                        for session_id in self.network_request_sessions(&params) {
+                            self.queued_events.push_back(TargetEvent::Request(Request {
+                                method: method.clone(),
+                                session_id: session_id.map(Into::into),
+                                params: params.clone(),
+                            }))
+                        }
+                    }
+                    NetworkEvent::Request(request_id) => {
+                        if !self.wait_for_request.is_empty() {
+                            if let Some(request) = self.network_manager.request(&request_id) {
+                                Self::notify_request_waiters(&mut self.wait_for_request, request);
+                            }
+                        }
                     }
-                    NetworkEvent::Request(_) => {}
                     NetworkEvent::Response(_) => {}
                     NetworkEvent::RequestFailed(request) => {
                         self.frame_manager.on_http_request_finished(request);
                     }
                     NetworkEvent::RequestFinished(request) => {
+                        if request.response.is_some() && !self.wait_for_response.is_empty() {
+                            Self::notify_request_waiters(&mut self.wait_for_response, &request);
+                        }
                         self.frame_manager.on_http_request_finished(request);
                     }
                 }
@@ -549,7 +1033,10 @@ impl Target {
                         self.queued_events
                             .push_back(TargetEvent::NavigationResult(res));
                     }
-                    FrameEvent::NavigationRequest(id, req) => {
+                    FrameEvent::NavigationRequest(id, mut req) => {
+                        if let Some(session) = self.frame_manager.session_for_params(&req.params) {
+                            req.session_id = Some(session.clone().into());
+                        }
                         self.queued_events
                             .push_back(TargetEvent::NavigationRequest(id, req));
                     }
@@ -557,11 +1044,29 @@ impl Target {
             }
 
             if self.queued_events.is_empty() {
+                self.poll_deadline(cx, now);
                 return None;
             }
         }
     }
 
+    /// Makes sure this target is polled again once the next deadline of a
+    /// navigation or network idle condition is reached
+    fn poll_deadline(&mut self, cx: &mut Context<'_>, now: Instant) {
+        let waiting = self
+            .wait_for_frame_navigation
+            .iter()
+            .map(|w| w.wait_until)
+            .chain(self.wait_for_url.iter().map(|w| w.wait_until));
+        self.deadline_timer = self
+            .frame_manager
+            .next_deadline(waiting, now)
+            .and_then(|deadline| {
+                let mut timer = Delay::new(deadline - now);
+                Pin::new(&mut timer).poll(cx).is_pending().then_some(timer)
+            });
+    }
+
     /// Set the sender half of the channel who requested the creation of this
     /// target
     pub fn set_initiator(&mut self, tx: Sender<Result<Page>>) {
@@ -577,6 +1082,7 @@ impl Target {
             .unwrap();
         let enable_performance = performance::EnableParams::default();
         let enable_log = cdplog::EnableParams::default();
+        let enable_inspector = inspector::EnableParams::default();
         CommandChain::new(
             vec![
                 (attach.identifier(), serde_json::to_value(attach).unwrap()),
@@ -588,6 +1094,10 @@ impl Target {
                     enable_log.identifier(),
                     serde_json::to_value(enable_log).unwrap(),
                 ),
+                (
+                    enable_inspector.identifier(),
+                    serde_json::to_value(enable_inspector).unwrap(),
+                ),
             ],
             timeout,
         )
@@ -602,6 +1112,18 @@ pub struct TargetConfig {
     pub viewport: Option<Viewport>,
     pub request_intercept: bool,
     pub cache_enabled: bool,
+    /// Whether to emit console messages and page errors as `tracing` events
+    pub trace_console: bool,
+    /// Additional HTTP headers sent with every request
+    pub extra_headers: HashMap<String, String>,
+    /// Url patterns that are blocked
+    pub blocked_urls: Vec<String>,
+    /// Requests matching any of these filters are blocked
+    pub resource_filters: Vec<ResourceFilter>,
+    /// Requests blocked by the adblock engine are aborted
+    pub adblock: Option<AdblockOptions>,
+    /// The size of the buffer in which response bodies are kept
+    pub response_buffer_size: Option<usize>,
 }
 
 impl Default for TargetConfig {
@@ -612,6 +1134,12 @@ impl Default for TargetConfig {
             viewport: Default::default(),
             request_intercept: false,
             cache_enabled: true,
+            trace_console: false,
+            extra_headers: Default::default(),
+            blocked_urls: Vec::new(),
+            resource_filters: Vec::new(),
+            adblock: None,
+            response_buffer_size: None,
         }
     }
 }
@@ -681,6 +1209,8 @@ pub(crate) enum TargetEvent {
     NavigationResult(Result<NavigationOk, NavigationError>),
     /// A new command arrived via a channel
     Command(CommandMessage),
+    /// A command that triggers a navigation arrived via a channel
+    Navigate(NavigateRequest),
 }
 
 // TODO this can be moved into the classes?
@@ -709,6 +1239,73 @@ impl TargetInit {
     }
 }
 
+#[derive(Debug)]
+pub struct WaitForNavigation {
+    /// The state the main frame needs to reach
+    pub wait_until: WaitUntil,
+    /// Sender half of the channel to send the response back
+    pub tx: Sender<ArcHttpRequest>,
+}
+
+impl WaitForNavigation {
+    pub fn new(tx: Sender<ArcHttpRequest>) -> Self {
+        Self {
+            wait_until: Default::default(),
+            tx,
+        }
+    }
+}
+
+/// A predicate requests are matched against
+pub type RequestPredicate = Box<dyn Fn(&HttpRequest) -> bool + Send>;
+
+pub struct WaitForRequest {
+    /// Whether a request is the one waited for
+    pub predicate: RequestPredicate,
+    /// Sender half of the channel to send the matching request back
+    pub tx: Sender<HttpRequest>,
+}
+
+impl WaitForRequest {
+    pub fn new(
+        predicate: impl Fn(&HttpRequest) -> bool + Send + 'static,
+        tx: Sender<HttpRequest>,
+    ) -> Self {
+        Self {
+            predicate: Box::new(predicate),
+            tx,
+        }
+    }
+}
+
+impl fmt::Debug for WaitForRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitForRequest")
+            .field("tx", &self.tx)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+pub struct WaitForUrl {
+    /// The pattern the url of the main frame needs to match
+    pub pattern: UrlPattern,
+    /// The state the main frame needs to reach
+    pub wait_until: WaitUntil,
+    /// Sender half of the channel to send the matching url back
+    pub tx: Sender<String>,
+}
+
+#[derive(Debug)]
+pub struct NavigateRequest {
+    /// The command that triggers the navigation, like `Page.navigate`
+    pub cmd: CommandMessage,
+    /// When the navigation is considered complete
+    pub wait_until: WaitUntil,
+    /// Overrides the default navigation timeout
+    pub timeout: Option<Duration>,
+}
+
 #[derive(Debug)]
 pub struct GetExecutionContext {
     /// For which world the execution context was requested
@@ -774,11 +1371,104 @@ pub enum TargetMessage {
     /// Return the parent id of a frame
     Parent(GetParent),
     /// A Message that resolves when the frame finished loading a new url
-    WaitForNavigation(Sender<ArcHttpRequest>),
+    WaitForNavigation(WaitForNavigation),
+    /// Execute a command that triggers a navigation and resolve once the
+    /// navigation finished
+    Navigate(NavigateRequest),
+    /// A message that resolves once a matching request is sent
+    WaitForRequest(WaitForRequest),
+    /// A message that resolves once a response for a matching request is
+    /// received
+    WaitForResponse(WaitForRequest),
+    /// A message that resolves once the url of the main frame matches
+    WaitForUrl(WaitForUrl),
     /// A request to submit a new listener that gets notified with every
     /// received event
     AddEventListener(EventListenerRequest),
     /// Get the `ExecutionContext` if available
     GetExecutionContext(GetExecutionContext),
     Authenticate(Credentials),
+    /// Emulate the conditions of the network in the out-of-process iframes of
+    /// the target and in all sessions attached later
+    EmulateNetworkConditions(NetworkConditions),
+    /// Send additional HTTP headers with every request of the target
+    SetExtraHeaders(HashMap<String, String>),
+    /// Rewrite the headers of matching requests of the target
+    RewriteHeaders(HeaderRewrite),
+    /// Block all requests whose url matches any of the patterns
+    BlockUrls(Vec<String>),
+    /// Block all requests that match any of the filters
+    SetResourceFilters(Vec<ResourceFilter>),
+    /// Pause the responses of all requests whose url matches any of the
+    /// patterns
+    InterceptResponses(Vec<String>),
+    /// Block all requests that the adblock engine blocks, `None` disables
+    /// adblocking
+    SetAdblock(Option<AdblockOptions>),
+    /// Return the counters of the blocked requests
+    BlockedRequests(Sender<BlockedRequests>),
+    /// Serve requests from an archive
+    RouteFromHar(HarRoute),
+    /// Report all websocket connections opened from now on
+    WebSockets(UnboundedSender<WebSocketConnection>),
+    /// A message that resolves when the renderer of the target crashed
+    WaitForCrash(Sender<()>),
+    /// A message that resolves when the target was closed
+    WaitForClose(Sender<()>),
+    /// Register a binding whose calls are forwarded to a channel
+    ExposeBinding(ExposeBinding),
+    /// Unregister a binding whose channel was closed
+    RemoveBinding(RemoveBinding),
+}
+
+#[derive(Debug)]
+pub struct ExposeBinding {
+    /// The name of the binding on the global object
+    pub name: String,
+    /// The script that wraps the binding in every new document
+    pub source: String,
+    /// Sender half of the channel the calls of the binding are forwarded to
+    pub tx: UnboundedSender<BindingCall>,
+}
+
+#[derive(Debug)]
+pub struct RemoveBinding {
+    /// The name of the binding on the global object
+    pub name: String,
+    /// Sender half of the channel to report whether the binding was removed
+    pub tx: Sender<bool>,
+}
+
+impl ExposeBinding {
+    /// The commands that install the binding in a session
+    pub fn commands(&self) -> Vec<(MethodId, serde_json::Value)> {
+        let add_binding = AddBindingParams::new(self.name.clone());
+        let add_script = AddScriptToEvaluateOnNewDocumentParams::new(self.source.clone());
+        vec![
+            (
+                add_binding.identifier(),
+                serde_json::to_value(add_binding).unwrap(),
+            ),
+            (
+                add_script.identifier(),
+                serde_json::to_value(add_script).unwrap(),
+            ),
+        ]
+    }
+}
+
+/// A call of a binding exposed via `Page::expose_rust_fn` or of the one
+/// `Page::web_vitals` reports with
+#[derive(Debug, Clone)]
+pub struct BindingCall {
+    /// The name of the binding
+    pub name: String,
+    /// The sequence number of the call, identifying its pending promise
+    pub seq: u64,
+    /// The arguments the function was called with, as array
+    pub args: serde_json::Value,
+    /// The execution context the call was made in
+    pub execution_context_id: ExecutionContextId,
+    /// The session of the execution context
+    pub session_id: SessionId,
 }
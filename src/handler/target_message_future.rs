@@ -7,7 +7,8 @@ use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-use crate::handler::target::TargetMessage;
+use crate::handler::frame::WaitUntil;
+use crate::handler::target::{TargetMessage, WaitForNavigation};
 use crate::{error::Result, ArcHttpRequest};
 
 type TargetSender = mpsc::Sender<TargetMessage>;
@@ -37,9 +38,16 @@ impl<T> TargetMessageFuture<T> {
     }
 
     pub fn wait_for_navigation(target_sender: TargetSender) -> TargetMessageFuture<ArcHttpRequest> {
+        Self::wait_for_navigation_until(target_sender, WaitUntil::Load)
+    }
+
+    pub fn wait_for_navigation_until(
+        target_sender: TargetSender,
+        wait_until: WaitUntil,
+    ) -> TargetMessageFuture<ArcHttpRequest> {
         let (tx, rx_request) = oneshot_channel();
 
-        let message = TargetMessage::WaitForNavigation(tx);
+        let message = TargetMessage::WaitForNavigation(WaitForNavigation { wait_until, tx });
 
         TargetMessageFuture::new(target_sender, message, rx_request)
     }
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::{Stream, StreamExt};
+
+use chromiumoxide_cdp::cdp::browser_protocol::network::{
+    self, EventEventSourceMessageReceived, EventWebSocketClosed, EventWebSocketCreated,
+    EventWebSocketFrameError, MonotonicTime, RequestId,
+};
+
+use crate::utils;
+
+/// The websocket opcodes of the frames reported by chromium
+const OPCODE_TEXT: f64 = 1.;
+const OPCODE_CLOSE: f64 = 8.;
+
+/// A websocket connection opened by a page.
+///
+/// This is a stream of the frames sent and received over the connection that
+/// ends once the connection is closed, after which the reason of the close is
+/// available via [`WebSocketConnection::close_reason`].
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct WebSocketConnection {
+    request_id: RequestId,
+    url: String,
+    events: UnboundedReceiver<WebSocketEvent>,
+    close_reason: Option<WebSocketClose>,
+}
+
+impl WebSocketConnection {
+    /// The identifier of the request that opened the connection
+    pub fn request_id(&self) -> &RequestId {
+        &self.request_id
+    }
+
+    /// The url the connection was opened to
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Why the connection was closed, `None` while it is still open
+    pub fn close_reason(&self) -> Option<&WebSocketClose> {
+        self.close_reason.as_ref()
+    }
+}
+
+impl Stream for WebSocketConnection {
+    type Item = WebSocketFrame;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match futures::ready!(this.events.poll_next_unpin(cx)) {
+                Some(WebSocketEvent::Frame(frame)) => return Poll::Ready(Some(frame)),
+                Some(WebSocketEvent::Closed(close)) => this.close_reason = Some(close),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// A single frame sent or received over a [`WebSocketConnection`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketFrame {
+    pub direction: FrameDirection,
+    pub payload: WebSocketPayload,
+    pub timestamp: MonotonicTime,
+}
+
+/// Whether a frame was sent or received by the page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameDirection {
+    Sent,
+    Received,
+}
+
+/// The data of a websocket frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketPayload {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl WebSocketPayload {
+    /// The data as bytes, regardless of the type of the frame
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            WebSocketPayload::Text(text) => text.as_bytes(),
+            WebSocketPayload::Binary(data) => data,
+        }
+    }
+}
+
+/// Why a [`WebSocketConnection`] was closed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WebSocketClose {
+    /// The status code of the close frame, if one was exchanged
+    pub code: Option<u16>,
+    /// The reason of the close frame, if one was exchanged
+    pub reason: Option<String>,
+    /// The last error that occurred on the connection
+    pub error: Option<String>,
+    pub timestamp: MonotonicTime,
+}
+
+/// A message received by an `EventSource` of a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventSourceMessage {
+    /// The identifier of the request of the event source
+    pub request_id: RequestId,
+    /// The type of the event, `message` by default
+    pub event_name: String,
+    /// The `id` of the event, empty if none was set
+    pub event_id: String,
+    pub data: String,
+    pub timestamp: MonotonicTime,
+}
+
+impl From<&EventEventSourceMessageReceived> for EventSourceMessage {
+    fn from(ev: &EventEventSourceMessageReceived) -> Self {
+        Self {
+            request_id: ev.request_id.clone(),
+            event_name: ev.event_name.clone(),
+            event_id: ev.event_id.clone(),
+            data: ev.data.clone(),
+            timestamp: ev.timestamp.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum WebSocketEvent {
+    Frame(WebSocketFrame),
+    Closed(WebSocketClose),
+}
+
+/// The state of an open connection
+#[derive(Debug, Default)]
+struct OpenWebSocket {
+    /// One sender per [`WebSocketConnection`] handed out for the connection
+    senders: Vec<UnboundedSender<WebSocketEvent>>,
+    close: WebSocketClose,
+}
+
+impl OpenWebSocket {
+    fn send(&mut self, event: impl Fn() -> WebSocketEvent) {
+        self.senders.retain(|tx| tx.unbounded_send(event()).is_ok());
+    }
+}
+
+/// Tracks the websocket connections of a target and hands them out to the
+/// listeners registered via `Page::websockets`.
+#[derive(Debug, Default)]
+pub(crate) struct WebSocketTracker {
+    listeners: Vec<UnboundedSender<WebSocketConnection>>,
+    connections: HashMap<RequestId, OpenWebSocket>,
+}
+
+impl WebSocketTracker {
+    /// Reports all connections opened from now on to the `listener`
+    pub fn add_listener(&mut self, listener: UnboundedSender<WebSocketConnection>) {
+        self.listeners.push(listener);
+    }
+
+    pub fn on_created(&mut self, ev: &EventWebSocketCreated) {
+        self.listeners.retain(|listener| !listener.is_closed());
+        if self.listeners.is_empty() {
+            return;
+        }
+        let mut open = OpenWebSocket::default();
+        for listener in &self.listeners {
+            let (tx, rx) = unbounded();
+            let connection = WebSocketConnection {
+                request_id: ev.request_id.clone(),
+                url: ev.url.clone(),
+                events: rx,
+                close_reason: None,
+            };
+            if listener.unbounded_send(connection).is_ok() {
+                open.senders.push(tx);
+            }
+        }
+        self.connections.insert(ev.request_id.clone(), open);
+    }
+
+    pub fn on_frame(
+        &mut self,
+        request_id: &RequestId,
+        direction: FrameDirection,
+        timestamp: &MonotonicTime,
+        frame: &network::WebSocketFrame,
+    ) {
+        let open = match self.connections.get_mut(request_id) {
+            Some(open) => open,
+            None => return,
+        };
+        // the payload of all but text frames is base64 encoded
+        let payload = if frame.opcode == OPCODE_TEXT {
+            WebSocketPayload::Text(frame.payload_data.clone())
+        } else {
+            match utils::base64::decode(&frame.payload_data) {
+                Ok(data) => WebSocketPayload::Binary(data),
+                Err(_) => return,
+            }
+        };
+        if frame.opcode == OPCODE_CLOSE {
+            let data = payload.as_bytes();
+            if data.len() >= 2 {
+                open.close.code = Some(u16::from_be_bytes([data[0], data[1]]));
+                open.close.reason = Some(String::from_utf8_lossy(&data[2..]).into_owned());
+            }
+            return;
+        }
+        let frame = WebSocketFrame {
+            direction,
+            payload,
+            timestamp: timestamp.clone(),
+        };
+        open.send(|| WebSocketEvent::Frame(frame.clone()));
+    }
+
+    pub fn on_frame_error(&mut self, ev: &EventWebSocketFrameError) {
+        if let Some(open) = self.connections.get_mut(&ev.request_id) {
+            open.close.error = Some(ev.error_message.clone());
+        }
+    }
+
+    pub fn on_closed(&mut self, ev: &EventWebSocketClosed) {
+        if let Some(mut open) = self.connections.remove(&ev.request_id) {
+            open.close.timestamp = ev.timestamp.clone();
+            let close = open.close.clone();
+            // dropping the senders afterwards ends the connection streams
+            open.send(|| WebSocketEvent::Closed(close.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(tracker: &mut WebSocketTracker) -> WebSocketConnection {
+        let (tx, mut rx) = unbounded();
+        tracker.add_listener(tx);
+        tracker.on_created(
+            &serde_json::from_value(serde_json::json!({
+                "requestId": "ws",
+                "url": "wss://example.com/socket",
+            }))
+            .unwrap(),
+        );
+        rx.try_recv().unwrap()
+    }
+
+    fn frame(tracker: &mut WebSocketTracker, direction: FrameDirection, opcode: f64, data: &str) {
+        let frame = serde_json::from_value(serde_json::json!({
+            "opcode": opcode,
+            "mask": false,
+            "payloadData": data,
+        }))
+        .unwrap();
+        tracker.on_frame(
+            &RequestId::new("ws"),
+            direction,
+            &MonotonicTime::new(1.),
+            &frame,
+        );
+    }
+
+    fn close(tracker: &mut WebSocketTracker) {
+        tracker.on_closed(
+            &serde_json::from_value(serde_json::json!({ "requestId": "ws", "timestamp": 2. }))
+                .unwrap(),
+        );
+    }
+
+    #[tokio::test]
+    async fn decodes_text_and_binary_frames() {
+        let mut tracker = WebSocketTracker::default();
+        let connection = connect(&mut tracker);
+        assert_eq!(connection.url(), "wss://example.com/socket");
+
+        frame(&mut tracker, FrameDirection::Sent, 1., "hello");
+        frame(&mut tracker, FrameDirection::Received, 2., "AAEC");
+        // binary frames that are not valid base64 are skipped
+        frame(&mut tracker, FrameDirection::Received, 2., "%%%");
+        close(&mut tracker);
+
+        let frames = connection.collect::<Vec<_>>().await;
+        assert_eq!(
+            frames,
+            vec![
+                WebSocketFrame {
+                    direction: FrameDirection::Sent,
+                    payload: WebSocketPayload::Text("hello".to_string()),
+                    timestamp: MonotonicTime::new(1.),
+                },
+                WebSocketFrame {
+                    direction: FrameDirection::Received,
+                    payload: WebSocketPayload::Binary(vec![0, 1, 2]),
+                    timestamp: MonotonicTime::new(1.),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn parses_close_frames() {
+        let mut tracker = WebSocketTracker::default();
+        let mut connection = connect(&mut tracker);
+
+        // code 1001 followed by the reason "going away"
+        frame(
+            &mut tracker,
+            FrameDirection::Received,
+            8.,
+            "A+lnb2luZyBhd2F5",
+        );
+        close(&mut tracker);
+
+        assert!(connection.next().await.is_none());
+        assert_eq!(
+            connection.close_reason(),
+            Some(&WebSocketClose {
+                code: Some(1001),
+                reason: Some("going away".to_string()),
+                error: None,
+                timestamp: MonotonicTime::new(2.),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_frame_errors_on_close() {
+        let mut tracker = WebSocketTracker::default();
+        let mut connection = connect(&mut tracker);
+
+        // a close frame without a status code
+        frame(&mut tracker, FrameDirection::Received, 8., "");
+        tracker.on_frame_error(
+            &serde_json::from_value(serde_json::json!({
+                "requestId": "ws",
+                "timestamp": 1.5,
+                "errorMessage": "Invalid frame header",
+            }))
+            .unwrap(),
+        );
+        close(&mut tracker);
+
+        assert!(connection.next().await.is_none());
+        let close = connection.close_reason().unwrap();
+        assert_eq!(close.code, None);
+        assert_eq!(close.reason, None);
+        assert_eq!(close.error.as_deref(), Some("Invalid frame header"));
+    }
+
+    #[tokio::test]
+    async fn ends_streams_once_closed() {
+        let mut tracker = WebSocketTracker::default();
+        let mut connection = connect(&mut tracker);
+        let (tx, mut rx) = unbounded();
+        tracker.add_listener(tx);
+
+        frame(&mut tracker, FrameDirection::Received, 1., "before");
+        close(&mut tracker);
+        // frames after the close are not reported
+        frame(&mut tracker, FrameDirection::Received, 1., "after");
+
+        assert!(tracker.connections.is_empty());
+        assert!(rx.try_recv().is_err());
+        let frame = connection.next().await.unwrap();
+        assert_eq!(frame.payload, WebSocketPayload::Text("before".to_string()));
+        assert!(connection.close_reason().is_none());
+        assert!(connection.next().await.is_none());
+        assert!(connection.close_reason().is_some());
+    }
+}
@@ -0,0 +1,646 @@
+//! Replaying requests from HTTP archives (HAR files), see
+//! [`Page::route_from_har`](crate::page::Page::route_from_har).
+
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::channel::mpsc::UnboundedSender;
+use serde::{Deserialize, Serialize};
+
+use chromiumoxide_cdp::cdp::browser_protocol::fetch::{FulfillRequestParams, HeaderEntry};
+use chromiumoxide_cdp::cdp::browser_protocol::network::Request;
+
+use crate::error::{CdpError, Result};
+use crate::handler::http::HttpRequest;
+use crate::pattern::UrlPattern;
+use crate::utils;
+
+/// Response headers that don't apply to the body as it is stored in an archive
+const STRIPPED_HEADERS: [&str; 3] = ["content-encoding", "content-length", "transfer-encoding"];
+
+/// An HTTP archive as specified by the
+/// [HAR 1.2 spec](http://www.softwareishard.com/blog/har-12-spec/)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    #[serde(default)]
+    pub entries: Vec<HarEntry>,
+    /// Fields that are not interpreted, like `pages`
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Default for HarLog {
+    fn default() -> Self {
+        Self {
+            version: "1.2".to_string(),
+            creator: HarCreator {
+                name: env!("CARGO_PKG_NAME").to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            entries: Vec::new(),
+            extra: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+/// A request and its response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: String,
+    #[serde(default)]
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    #[serde(default)]
+    pub cache: serde_json::Value,
+    #[serde(default)]
+    pub timings: serde_json::Value,
+    /// Fields that are not interpreted, like `serverIPAddress`
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub cookies: Vec<serde_json::Value>,
+    pub headers: Vec<HarHeader>,
+    pub query_string: Vec<HarHeader>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HarResponse {
+    pub status: i64,
+    pub status_text: String,
+    pub http_version: String,
+    pub cookies: Vec<serde_json::Value>,
+    pub headers: Vec<HarHeader>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+/// A header or query parameter
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HarHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HarPostData {
+    pub mime_type: String,
+    pub text: String,
+}
+
+/// The body of a response
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HarContent {
+    pub size: i64,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// `base64` if the text is base64 encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+impl Har {
+    /// Reads an archive from a file
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let content = utils::read(path.as_ref()).await?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// The first entry with the request's method, url and post data
+    pub fn find(&self, method: &str, url: &str, post_data: Option<&str>) -> Option<&HarEntry> {
+        self.log.entries.iter().find(|entry| {
+            entry.request.method.eq_ignore_ascii_case(method)
+                && entry.request.url == url
+                && entry
+                    .request
+                    .post_data
+                    .as_ref()
+                    .map(|data| data.text.as_str())
+                    == post_data
+        })
+    }
+}
+
+impl HarEntry {
+    /// Creates an entry for a request that finished loading, with the `body`
+    /// of its response
+    pub fn new(request: &HttpRequest, body: Option<Vec<u8>>) -> Option<Self> {
+        let response = request.response.as_ref()?;
+        let url = request.url.clone()?;
+        let mime_type = response.mime_type.clone();
+        let request_headers = request
+            .headers
+            .iter()
+            .map(|(name, value)| HarHeader {
+                name: name.clone(),
+                value: value.clone(),
+            })
+            .collect();
+        let post_data = request.post_data.clone().map(|text| HarPostData {
+            mime_type: request
+                .headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+                .map(|(_, value)| value.clone())
+                .unwrap_or_default(),
+            text,
+        });
+        let response_headers = response
+            .headers
+            .inner()
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, value)| {
+                value.as_str().map(|value| HarHeader {
+                    name: name.clone(),
+                    value: value.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let redirect_url = response_headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case("location"))
+            .map(|header| header.value.clone())
+            .unwrap_or_default();
+        let http_version = response.protocol.clone().unwrap_or_default();
+        let content = match body {
+            Some(body) => {
+                let size = body.len() as i64;
+                let (text, encoding) = match String::from_utf8(body) {
+                    Ok(text) => (text, None),
+                    Err(err) => (
+                        utils::base64::encode(err.into_bytes()),
+                        Some("base64".to_string()),
+                    ),
+                };
+                HarContent {
+                    size,
+                    mime_type,
+                    text: Some(text),
+                    encoding,
+                }
+            }
+            None => HarContent {
+                mime_type,
+                ..Default::default()
+            },
+        };
+        Some(Self {
+            started_date_time: rfc3339(SystemTime::now()),
+            time: -1.,
+            request: HarRequest {
+                method: request.method.clone().unwrap_or_else(|| "GET".to_string()),
+                query_string: query_string(&url),
+                url,
+                http_version: http_version.clone(),
+                headers: request_headers,
+                post_data,
+                headers_size: -1,
+                body_size: -1,
+                ..Default::default()
+            },
+            response: HarResponse {
+                status: response.status,
+                status_text: response.status_text.clone(),
+                http_version,
+                headers: response_headers,
+                content,
+                redirect_url,
+                headers_size: -1,
+                body_size: -1,
+                ..Default::default()
+            },
+            cache: serde_json::Value::Object(Default::default()),
+            timings: serde_json::json!({ "send": -1, "wait": -1, "receive": -1 }),
+            extra: Default::default(),
+        })
+    }
+
+    /// The params to fulfill the intercepted request with the response of
+    /// this entry
+    fn fulfill_params(&self, request_id: impl Into<String>) -> Result<FulfillRequestParams> {
+        let response = &self.response;
+        let body = match (&response.content.text, &response.content.encoding) {
+            (Some(text), Some(encoding)) if encoding == "base64" => Some(text.clone()),
+            (Some(text), _) => Some(utils::base64::encode(text)),
+            (None, _) => None,
+        };
+        let headers = response
+            .headers
+            .iter()
+            .filter(|header| {
+                !STRIPPED_HEADERS
+                    .iter()
+                    .any(|name| header.name.eq_ignore_ascii_case(name))
+            })
+            .map(|header| HeaderEntry::new(header.name.clone(), header.value.clone()));
+        let mut builder = FulfillRequestParams::builder()
+            .request_id(request_id.into())
+            .response_code(response.status)
+            .response_headers(headers);
+        if !response.status_text.is_empty() {
+            builder = builder.response_phrase(response.status_text.clone());
+        }
+        if let Some(body) = body {
+            builder = builder.body(body);
+        }
+        builder.build().map_err(CdpError::msg)
+    }
+}
+
+/// What to do with requests that have no entry in the archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HarNotFound {
+    /// Fail the request
+    #[default]
+    Abort,
+    /// Send the request to the network
+    Fallback,
+}
+
+/// How requests are served from an archive
+#[derive(Debug, Clone, Default)]
+pub struct HarReplayOptions {
+    /// What to do with requests that have no entry in the archive
+    pub not_found: HarNotFound,
+    /// Only requests with a matching url are served from the archive, all
+    /// others are sent to the network
+    pub url_filter: Option<UrlPattern>,
+    /// Send requests without an entry to the network and add their responses
+    /// to the archive file
+    pub update: bool,
+}
+
+/// An archive that intercepted requests of a page are served from
+#[derive(Debug, Clone)]
+pub struct HarRoute {
+    har: Arc<Mutex<Har>>,
+    options: HarReplayOptions,
+    /// Receives the finished requests that are recorded into the archive
+    recorder: Option<UnboundedSender<HttpRequest>>,
+}
+
+/// What happens to an intercepted request
+pub(crate) enum HarAction {
+    Fulfill(Box<FulfillRequestParams>),
+    Abort,
+    Record,
+    Fallback,
+}
+
+impl HarRoute {
+    pub(crate) fn new(
+        har: Arc<Mutex<Har>>,
+        options: HarReplayOptions,
+        recorder: Option<UnboundedSender<HttpRequest>>,
+    ) -> Self {
+        Self {
+            har,
+            options,
+            recorder,
+        }
+    }
+
+    /// Whether requests to the `url` are handled by this route
+    pub(crate) fn matches(&self, url: &str) -> bool {
+        self.options
+            .url_filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(url))
+    }
+
+    /// Decides what happens to the intercepted request
+    pub(crate) fn route(&self, request_id: &str, request: &Request) -> HarAction {
+        let post_data = post_data(request);
+        let har = lock(&self.har);
+        let entry = har.find(&request.method, &request.url, post_data.as_deref());
+        match entry.map(|entry| entry.fulfill_params(request_id)) {
+            Some(Ok(params)) => HarAction::Fulfill(Box::new(params)),
+            _ if self.recorder.is_some() => HarAction::Record,
+            _ => match self.options.not_found {
+                HarNotFound::Abort => HarAction::Abort,
+                HarNotFound::Fallback => HarAction::Fallback,
+            },
+        }
+    }
+
+    /// Records a request that finished loading
+    pub(crate) fn record(&self, request: HttpRequest) {
+        if let Some(recorder) = self.recorder.as_ref() {
+            let _ = recorder.unbounded_send(request);
+        }
+    }
+}
+
+/// Adds the `request` and the requests it was redirected from to the
+/// archive, if they aren't in it yet, and returns whether any was added
+pub(crate) fn record(har: &Mutex<Har>, request: &HttpRequest, body: Option<Vec<u8>>) -> bool {
+    let mut entries = request
+        .redirect_chain
+        .iter()
+        .filter_map(|redirect| HarEntry::new(redirect, None))
+        .collect::<Vec<_>>();
+    entries.extend(HarEntry::new(request, body));
+    let mut har = lock(har);
+    let mut added = false;
+    for entry in entries {
+        let request = &entry.request;
+        let post_data = request.post_data.as_ref().map(|data| data.text.as_str());
+        if har.find(&request.method, &request.url, post_data).is_none() {
+            har.log.entries.push(entry);
+            added = true;
+        }
+    }
+    added
+}
+
+/// Writes the archive to the `path`
+pub(crate) async fn save(har: &Mutex<Har>, path: &Path) -> Result<()> {
+    let content = serde_json::to_vec_pretty(&*lock(har))?;
+    utils::write(path, content).await?;
+    Ok(())
+}
+
+/// The post data of the request as string
+pub(crate) fn post_data(request: &Request) -> Option<String> {
+    let entries = request.post_data_entries.as_ref()?;
+    let mut data = Vec::new();
+    for entry in entries {
+        if let Some(bytes) = entry.bytes.as_ref() {
+            data.extend(utils::base64::decode(AsRef::<str>::as_ref(bytes)).ok()?);
+        }
+    }
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+fn lock(har: &Mutex<Har>) -> MutexGuard<'_, Har> {
+    // an archive is never left in an inconsistent state
+    har.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn query_string(url: &str) -> Vec<HarHeader> {
+    url::Url::parse(url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| HarHeader {
+                    name: name.into_owned(),
+                    value: value.into_owned(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Formats the time as `2024-01-31T12:00:00.000Z`
+fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs) = ((secs / 86_400) as i64, secs % 86_400);
+    // civil date from days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use chromiumoxide_cdp::cdp::browser_protocol::network::{RequestId, Response};
+
+    const HAR: &str = r#"{
+        "log": {
+            "version": "1.2",
+            "creator": { "name": "test", "version": "1" },
+            "pages": [],
+            "entries": [
+                {
+                    "startedDateTime": "2024-01-31T12:00:00.000Z",
+                    "time": 10,
+                    "request": {
+                        "method": "POST",
+                        "url": "https://example.com/api",
+                        "httpVersion": "HTTP/1.1",
+                        "headers": [],
+                        "postData": { "mimeType": "application/json", "text": "{\"a\":1}" }
+                    },
+                    "response": {
+                        "status": 200,
+                        "statusText": "OK",
+                        "httpVersion": "HTTP/1.1",
+                        "headers": [{ "name": "Content-Encoding", "value": "gzip" }],
+                        "content": { "size": 2, "mimeType": "application/json", "text": "{}" },
+                        "redirectURL": ""
+                    },
+                    "cache": {},
+                    "timings": { "send": 0, "wait": 10, "receive": 0 }
+                }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn finds_entries_by_method_url_and_post_data() {
+        let har: Har = serde_json::from_str(HAR).unwrap();
+        assert!(har
+            .find("post", "https://example.com/api", Some("{\"a\":1}"))
+            .is_some());
+        assert!(har.find("GET", "https://example.com/api", None).is_none());
+        assert!(har
+            .find("POST", "https://example.com/api", Some("{\"a\":2}"))
+            .is_none());
+        assert!(har.log.extra.contains_key("pages"));
+    }
+
+    #[test]
+    fn fulfills_with_decoded_body() {
+        let har: Har = serde_json::from_str(HAR).unwrap();
+        let params = har.log.entries[0].fulfill_params("interception").unwrap();
+        assert_eq!(params.response_code, 200);
+        assert_eq!(params.response_headers, Some(Vec::new()));
+        assert_eq!(params.body.map(String::from), Some("e30=".to_string()));
+    }
+
+    fn response(status: i64, headers: serde_json::Value) -> Response {
+        serde_json::from_value(serde_json::json!({
+            "url": "https://example.com/",
+            "status": status,
+            "statusText": "",
+            "headers": headers,
+            "mimeType": "application/octet-stream",
+            "charset": "",
+            "connectionReused": false,
+            "connectionId": 0.,
+            "encodedDataLength": 0.,
+            "securityState": "secure",
+            "protocol": "h2",
+        }))
+        .unwrap()
+    }
+
+    fn http_request(id: &str, url: &str, response: Option<Response>) -> HttpRequest {
+        let mut request = HttpRequest::new(RequestId::new(id), None, None, false, Vec::new());
+        request.method = Some("POST".to_string());
+        request.url = Some(url.to_string());
+        request.response = response;
+        request
+    }
+
+    #[test]
+    fn creates_entries_from_finished_requests() {
+        let mut request = http_request(
+            "1",
+            "https://example.com/api?q=a%20b&page=2",
+            Some(response(
+                200,
+                serde_json::json!({ "Content-Type": "image/png" }),
+            )),
+        );
+        request
+            .headers
+            .insert("Content-Type".to_string(), "text/plain".to_string());
+        request.post_data = Some("hello".to_string());
+
+        let entry = HarEntry::new(&request, Some(vec![0xff, 0x00])).unwrap();
+        assert_eq!(entry.request.method, "POST");
+        assert_eq!(entry.request.http_version, "h2");
+        assert_eq!(
+            entry.request.query_string,
+            vec![
+                HarHeader {
+                    name: "q".to_string(),
+                    value: "a b".to_string()
+                },
+                HarHeader {
+                    name: "page".to_string(),
+                    value: "2".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            entry.request.post_data,
+            Some(HarPostData {
+                mime_type: "text/plain".to_string(),
+                text: "hello".to_string(),
+            })
+        );
+        assert_eq!(entry.response.status, 200);
+        assert_eq!(
+            entry.response.content,
+            HarContent {
+                size: 2,
+                mime_type: "application/octet-stream".to_string(),
+                text: Some("/wA=".to_string()),
+                encoding: Some("base64".to_string()),
+            }
+        );
+
+        let entry = HarEntry::new(&request, Some(b"{}".to_vec())).unwrap();
+        assert_eq!(entry.response.content.text.as_deref(), Some("{}"));
+        assert_eq!(entry.response.content.encoding, None);
+
+        // requests without a response aren't archived
+        assert!(HarEntry::new(&http_request("2", "https://example.com/", None), None).is_none());
+    }
+
+    #[test]
+    fn records_redirects_once() {
+        let redirect = http_request(
+            "1",
+            "https://example.com/old",
+            Some(response(
+                301,
+                serde_json::json!({ "Location": "https://example.com/new" }),
+            )),
+        );
+        let mut request = http_request(
+            "1",
+            "https://example.com/new",
+            Some(response(200, serde_json::json!({}))),
+        );
+        request.redirect_chain.push(redirect);
+
+        let har = Mutex::new(Har::default());
+        assert!(record(&har, &request, None));
+        assert!(!record(&har, &request, None));
+        let har = lock(&har);
+        assert_eq!(har.log.entries.len(), 2);
+        assert_eq!(
+            har.log.entries[0].response.redirect_url,
+            "https://example.com/new"
+        );
+        assert_eq!(har.log.entries[1].request.url, "https://example.com/new");
+    }
+
+    #[test]
+    fn decodes_post_data_entries() {
+        let mut request: Request = serde_json::from_value(serde_json::json!({
+            "url": "https://example.com/api",
+            "method": "POST",
+            "headers": {},
+            "initialPriority": "High",
+            "referrerPolicy": "no-referrer",
+            "postDataEntries": [{ "bytes": "YT0xJg==" }, {}, { "bytes": "Yj0y" }],
+        }))
+        .unwrap();
+        assert_eq!(post_data(&request).as_deref(), Some("a=1&b=2"));
+        request.post_data_entries = None;
+        assert_eq!(post_data(&request), None);
+    }
+
+    #[test]
+    fn formats_rfc3339() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_706_702_400_123);
+        assert_eq!(rfc3339(time), "2024-01-31T12:00:00.123Z");
+    }
+}
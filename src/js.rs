@@ -1,9 +1,15 @@
+use std::sync::Arc;
+
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 use chromiumoxide_cdp::cdp::js_protocol::runtime::{
-    CallFunctionOnParams, EvaluateParams, RemoteObject,
+    CallArgument, CallFunctionOnParams, EvaluateParams, ReleaseObjectParams, RemoteObject,
+    RemoteObjectId,
 };
 
+use crate::error::{CdpError, Result};
+use crate::handler::PageInner;
 use crate::utils::is_likely_js_function;
 
 #[derive(Debug, Clone)]
@@ -68,3 +74,344 @@ impl From<CallFunctionOnParams> for Evaluation {
         Evaluation::Function(params)
     }
 }
+
+/// A handle to a JavaScript object in the page, like the result of
+/// `Page::evaluate_handle`.
+///
+/// In contrast to an [`EvaluationResult`], the object stays in the page and is
+/// only referenced by its `RemoteObjectId`, so it can be used for objects
+/// that can't be serialized and be passed back to functions via [`JsArg`].
+///
+/// The browser keeps the object alive as long as it is referenced by the
+/// handle. Dropping the handle releases it, unless it belongs to an object
+/// group, which is released all at once with `Page::release_object_group`.
+#[derive(Debug)]
+pub struct JsHandle {
+    inner: Arc<PageInner>,
+    object: RemoteObject,
+    /// The object group the object was created in
+    group: Option<String>,
+}
+
+impl JsHandle {
+    pub(crate) fn new(inner: Arc<PageInner>, object: RemoteObject, group: Option<String>) -> Self {
+        Self {
+            inner,
+            object,
+            group,
+        }
+    }
+
+    /// Mirror object referencing the JavaScript object
+    pub fn object(&self) -> &RemoteObject {
+        &self.object
+    }
+
+    /// The identifier of the object, `None` for primitive values, which are
+    /// not held by the browser
+    pub fn object_id(&self) -> Option<&RemoteObjectId> {
+        self.object.object_id.as_ref()
+    }
+
+    /// The object group the object belongs to
+    pub fn object_group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Returns a handle to the property `name` of the object.
+    ///
+    /// The new handle belongs to the same object group as this handle.
+    pub async fn get_property(&self, name: impl Into<String>) -> Result<JsHandle> {
+        self.evaluate_handle_with_args(
+            "function(name) { return this[name]; }",
+            [JsArg::value(name.into())?],
+        )
+        .await
+    }
+
+    /// Calls the function with `this` bound to the object and returns the
+    /// result by value.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let handle = page.evaluate_handle("document.body").await?;
+    ///     let tag: String = handle
+    ///         .evaluate("function() { return this.tagName; }")
+    ///         .await?
+    ///         .into_value()?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn evaluate(&self, function: impl Into<String>) -> Result<EvaluationResult> {
+        self.evaluate_with_args(function, []).await
+    }
+
+    /// Calls the function with `this` bound to the object and the `args` as
+    /// arguments and returns the result by value.
+    pub async fn evaluate_with_args(
+        &self,
+        function: impl Into<String>,
+        args: impl IntoIterator<Item = JsArg>,
+    ) -> Result<EvaluationResult> {
+        let object = self.call_function(function, args, true).await?;
+        Ok(EvaluationResult::new(object))
+    }
+
+    /// Calls the function with `this` bound to the object and returns a
+    /// handle to the result.
+    pub async fn evaluate_handle(&self, function: impl Into<String>) -> Result<JsHandle> {
+        self.evaluate_handle_with_args(function, []).await
+    }
+
+    /// Calls the function with `this` bound to the object and the `args` as
+    /// arguments and returns a handle to the result.
+    pub async fn evaluate_handle_with_args(
+        &self,
+        function: impl Into<String>,
+        args: impl IntoIterator<Item = JsArg>,
+    ) -> Result<JsHandle> {
+        let object = self.call_function(function, args, false).await?;
+        Ok(JsHandle::new(
+            Arc::clone(&self.inner),
+            object,
+            self.group.clone(),
+        ))
+    }
+
+    /// Returns a JSON representation of the object
+    pub async fn json_value(&self) -> Result<serde_json::Value> {
+        self.evaluate("function() { return this; }")
+            .await?
+            .value()
+            .cloned()
+            .ok_or(CdpError::NotFound)
+    }
+
+    /// Attempts to deserialize the JSON representation of the object into the
+    /// given type
+    pub async fn into_value<T: DeserializeOwned>(self) -> Result<T> {
+        Ok(serde_json::from_value(self.json_value().await?)?)
+    }
+
+    async fn call_function(
+        &self,
+        function: impl Into<String>,
+        args: impl IntoIterator<Item = JsArg>,
+        return_by_value: bool,
+    ) -> Result<RemoteObject> {
+        let mut call = CallFunctionOnParams::builder()
+            .function_declaration(function)
+            .arguments(args.into_iter().map(|arg| arg.0))
+            .await_promise(true)
+            .return_by_value(return_by_value);
+        // primitives have no object to call the function on, so they are
+        // passed as `this` by value in the default execution context instead
+        call = match self.object_id() {
+            Some(id) => call.object_id(id.clone()),
+            None => match self.inner.execution_context().await? {
+                Some(context) => call.execution_context_id(context),
+                None => return Err(CdpError::NotFound),
+            },
+        };
+        if let Some(group) = &self.group {
+            call = call.object_group(group.clone());
+        }
+        let mut call = call.build().map_err(CdpError::msg)?;
+        if self.object_id().is_none() {
+            let this = JsArg::from(self).0;
+            call.function_declaration = format!(
+                "function(...args) {{ return ({}).apply(args.shift(), args); }}",
+                call.function_declaration
+            );
+            call.arguments.get_or_insert_with(Vec::new).insert(0, this);
+        }
+
+        let resp = self.inner.execute(call).await?.result;
+        if let Some(exception) = resp.exception_details {
+            return Err(CdpError::JavascriptException(Box::new(exception)));
+        }
+        Ok(resp.result)
+    }
+}
+
+impl Drop for JsHandle {
+    fn drop(&mut self) {
+        if self.group.is_none() {
+            if let Some(id) = self.object.object_id.take() {
+                self.inner.execute_detached(ReleaseObjectParams::new(id));
+            }
+        }
+    }
+}
+
+/// An argument of a function called via `Page::evaluate_with_args` or
+/// `JsHandle::evaluate_with_args`.
+///
+/// # Example
+///
+/// ```no_run
+/// # use chromiumoxide::page::Page;
+/// # use chromiumoxide::error::Result;
+/// # use chromiumoxide::js::JsArg;
+/// # async fn demo(page: Page) -> Result<()> {
+///     let body = page.evaluate_handle("document.body").await?;
+///     let count: usize = page
+///         .evaluate_with_args(
+///             "(root, selector) => root.querySelectorAll(selector).length",
+///             [JsArg::from(&body), JsArg::value("a")?],
+///         )
+///         .await?
+///         .into_value()?;
+///     # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct JsArg(CallArgument);
+
+impl JsArg {
+    /// Passes the value serialized as JSON
+    pub fn value(value: impl Serialize) -> Result<Self> {
+        Ok(Self(
+            CallArgument::builder()
+                .value(serde_json::to_value(value)?)
+                .build(),
+        ))
+    }
+
+    /// Passes `undefined`
+    pub fn undefined() -> Self {
+        Self(CallArgument::default())
+    }
+}
+
+impl From<&JsHandle> for JsArg {
+    fn from(handle: &JsHandle) -> Self {
+        let object = handle.object();
+        let arg = match &object.object_id {
+            Some(id) => CallArgument::builder().object_id(id.clone()),
+            // primitives are not held by the browser
+            None => match (&object.value, &object.unserializable_value) {
+                (Some(value), _) => CallArgument::builder().value(value.clone()),
+                (None, Some(value)) => CallArgument::builder().unserializable_value(value.clone()),
+                (None, None) => CallArgument::builder(),
+            },
+        };
+        Self(arg.build())
+    }
+}
+
+impl From<CallArgument> for JsArg {
+    fn from(arg: CallArgument) -> Self {
+        Self(arg)
+    }
+}
+
+impl From<JsArg> for CallArgument {
+    fn from(arg: JsArg) -> Self {
+        arg.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chromiumoxide_cdp::cdp::browser_protocol::target::{SessionId, TargetId};
+
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::handler::target::TargetMessage;
+    use crate::handler::PageHandle;
+
+    fn arg(page: &PageHandle, object: serde_json::Value) -> CallArgument {
+        let object = serde_json::from_value(object).unwrap();
+        let handle = JsHandle::new(Arc::clone(page.inner()), object, None);
+        JsArg::from(&handle).into()
+    }
+
+    fn page() -> PageHandle {
+        PageHandle::new(TargetId::new("target"), SessionId::new("session"), None)
+    }
+
+    #[test]
+    fn passes_primitive_handles_by_value() {
+        let page = page();
+        let arg = arg(&page, serde_json::json!({ "type": "string", "value": "a" }));
+        assert_eq!(arg.value, Some(serde_json::json!("a")));
+        assert_eq!(arg.unserializable_value, None);
+        assert_eq!(arg.object_id, None);
+    }
+
+    #[test]
+    fn passes_unserializable_primitive_handles() {
+        let page = page();
+        let infinity = arg(
+            &page,
+            serde_json::json!({ "type": "number", "unserializableValue": "-Infinity" }),
+        );
+        assert_eq!(infinity.value, None);
+        assert_eq!(infinity.unserializable_value.unwrap().inner(), "-Infinity");
+        assert_eq!(infinity.object_id, None);
+
+        let undefined = arg(&page, serde_json::json!({ "type": "undefined" }));
+        assert_eq!(undefined, CallArgument::default());
+    }
+
+    #[test]
+    fn passes_object_handles_by_id() {
+        let page = page();
+        let arg = arg(
+            &page,
+            serde_json::json!({
+                "type": "object",
+                "className": "HTMLBodyElement",
+                "objectId": "1.2.3",
+                "value": {},
+            }),
+        );
+        assert_eq!(arg.object_id.unwrap().inner(), "1.2.3");
+        assert_eq!(arg.value, None);
+        assert_eq!(arg.unserializable_value, None);
+    }
+
+    #[tokio::test]
+    async fn releases_ungrouped_objects_when_dropped() {
+        let mut page = page();
+        let object = |id: &str| {
+            serde_json::from_value(serde_json::json!({ "type": "object", "objectId": id })).unwrap()
+        };
+        // objects of a group are released with their group
+        let grouped = JsHandle::new(
+            Arc::clone(page.inner()),
+            object("1.1.1"),
+            Some("group".to_string()),
+        );
+        drop(grouped);
+        drop(JsHandle::new(
+            Arc::clone(page.inner()),
+            object("1.1.2"),
+            None,
+        ));
+
+        match page.rx.next().await {
+            Some(TargetMessage::Command(msg)) => {
+                assert_eq!(msg.method, "Runtime.releaseObject");
+                assert_eq!(msg.params["objectId"], "1.1.2");
+            }
+            _ => panic!("expected Runtime.releaseObject"),
+        }
+    }
+
+    #[test]
+    fn passes_values_as_json() {
+        let arg = CallArgument::from(JsArg::value([1, 2]).unwrap());
+        assert_eq!(arg.value, Some(serde_json::json!([1, 2])));
+        assert_eq!(
+            CallArgument::from(JsArg::undefined()),
+            CallArgument::default()
+        );
+    }
+}
@@ -76,10 +76,14 @@ pub use crate::fetcher::{BrowserFetcher, BrowserFetcherOptions};
 pub use crate::handler::Handler;
 pub use crate::page::Page;
 
+pub mod adblock;
 pub mod auth;
+pub(crate) mod bitmap;
 pub mod browser;
 pub mod cmd;
 pub mod conn;
+pub mod console;
+pub mod cookies;
 pub mod detection;
 pub mod element;
 pub mod error;
@@ -89,11 +93,19 @@ pub mod fetcher {
 }
 pub mod async_process;
 pub mod handler;
+pub mod har;
 pub mod js;
 pub mod keys;
 pub mod layout;
 pub mod listeners;
 pub mod page;
+pub mod pattern;
+pub mod pdf;
+pub mod performance;
+pub mod snapshot;
+pub mod storage;
+pub mod stream;
 pub(crate) mod utils;
+pub mod visual;
 
 pub type ArcHttpRequest = Option<Arc<HttpRequest>>;
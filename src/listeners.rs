@@ -76,6 +76,14 @@ impl EventListeners {
         Ok(())
     }
 
+    /// Sends all queued events right away, so that they are received before
+    /// anything that is sent afterwards, like the response to a command
+    pub fn flush(&mut self) {
+        for subscriptions in self.listeners.values_mut() {
+            subscriptions.retain_mut(EventListener::flush);
+        }
+    }
+
     /// Drains all queued events and does the housekeeping when the receiver
     /// part of a subscription is dropped
     pub fn poll(&mut self, cx: &mut Context<'_>) {
@@ -136,6 +144,16 @@ impl EventListener {
         self.queued_events.push_back(event)
     }
 
+    /// Sends all queued events, returns `false` if the receiver was dropped
+    pub fn flush(&mut self) -> bool {
+        while let Some(event) = self.queued_events.pop_front() {
+            if self.listener.unbounded_send(event).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+
     /// Drains all queued events and begins the process of sending them to the
     /// sink.
     pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SendError>> {
@@ -209,7 +227,7 @@ impl<T: IntoEventKind + Unpin> Stream for EventStream<T> {
 
 #[cfg(test)]
 mod tests {
-    use futures::{SinkExt, StreamExt};
+    use futures::{FutureExt, SinkExt, StreamExt};
 
     use chromiumoxide_cdp::cdp::browser_protocol::animation::EventAnimationCanceled;
     use chromiumoxide_cdp::cdp::CustomEvent;
@@ -291,4 +309,33 @@ mod tests {
         let next = stream.next().await.unwrap();
         assert_eq!(&*next, &event);
     }
+
+    #[test]
+    fn flushes_queued_events() {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let (dropped_tx, _) = futures::channel::mpsc::unbounded();
+        let mut listeners = EventListeners::default();
+        for listener in [tx, dropped_tx] {
+            listeners.add_listener(EventListenerRequest {
+                method: EventAnimationCanceled::method_id(),
+                kind: EventAnimationCanceled::event_kind(),
+                listener,
+            });
+        }
+
+        let event = EventAnimationCanceled {
+            id: "id".to_string(),
+        };
+        listeners.start_send(event.clone());
+        listeners.flush();
+
+        // received without polling the listeners
+        let mut stream = EventStream::<EventAnimationCanceled>::new(rx);
+        let next = stream.next().now_or_never().unwrap().unwrap();
+        assert_eq!(&*next, &event);
+        assert_eq!(
+            listeners.listeners[&EventAnimationCanceled::method_id()].len(),
+            1
+        );
+    }
 }
@@ -1,42 +1,68 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use futures::channel::mpsc::unbounded;
 use futures::channel::oneshot::channel as oneshot_channel;
-use futures::{stream, SinkExt, StreamExt};
+use futures::{stream, AsyncWrite, AsyncWriteExt, FutureExt, SinkExt, Stream, StreamExt};
+use serde::Serialize;
 
 use chromiumoxide_cdp::cdp::browser_protocol::dom::*;
 use chromiumoxide_cdp::cdp::browser_protocol::emulation::{
-    MediaFeature, SetEmulatedMediaParams, SetGeolocationOverrideParams, SetLocaleOverrideParams,
-    SetTimezoneOverrideParams,
+    MediaFeature, SetCpuThrottlingRateParams, SetEmulatedMediaParams, SetGeolocationOverrideParams,
+    SetLocaleOverrideParams, SetTimezoneOverrideParams,
 };
+use chromiumoxide_cdp::cdp::browser_protocol::fetch::{self, TakeResponseBodyAsStreamParams};
 use chromiumoxide_cdp::cdp::browser_protocol::network::{
-    Cookie, CookieParam, DeleteCookiesParams, GetCookiesParams, SetCookiesParams,
+    Cookie, CookieParam, DeleteCookiesParams, EventEventSourceMessageReceived, GetCookiesParams,
+    GetResponseBodyParams, GetResponseBodyReturns, RequestId, SetCookiesParams,
     SetUserAgentOverrideParams,
 };
 use chromiumoxide_cdp::cdp::browser_protocol::page::*;
 use chromiumoxide_cdp::cdp::browser_protocol::performance::{GetMetricsParams, Metric};
+use chromiumoxide_cdp::cdp::browser_protocol::storage::{ClearDataForOriginParams, StorageType};
 use chromiumoxide_cdp::cdp::browser_protocol::target::{SessionId, TargetId};
 use chromiumoxide_cdp::cdp::js_protocol;
 use chromiumoxide_cdp::cdp::js_protocol::debugger::GetScriptSourceParams;
+use chromiumoxide_cdp::cdp::js_protocol::heap_profiler::{
+    self, EventAddHeapSnapshotChunk, EventReportHeapSnapshotProgress, SamplingHeapProfile,
+    TakeHeapSnapshotParams,
+};
+use chromiumoxide_cdp::cdp::js_protocol::profiler;
 use chromiumoxide_cdp::cdp::js_protocol::runtime::{
-    AddBindingParams, CallArgument, CallFunctionOnParams, EvaluateParams, ExecutionContextId,
-    RemoteObjectType, ScriptId,
+    AddBindingParams, CallArgument, CallFunctionOnParams, EvaluateParams, EventConsoleApiCalled,
+    EventExceptionThrown, ExecutionContextId, ReleaseObjectGroupParams, RemoteObjectType, ScriptId,
 };
 use chromiumoxide_cdp::cdp::{browser_protocol, IntoEventKind};
 use chromiumoxide_types::*;
 
+use crate::adblock::AdblockOptions;
 use crate::auth::Credentials;
+use crate::console::{ConsoleMessage, PageError};
 use crate::element::Element;
 use crate::error::{CdpError, Result};
 use crate::handler::commandfuture::CommandFuture;
 use crate::handler::domworld::DOMWorldKind;
+use crate::handler::http::HttpRequest;
 use crate::handler::httpfuture::HttpFuture;
-use crate::handler::target::{GetName, GetParent, GetUrl, TargetMessage};
+use crate::handler::network::HeaderRewrite;
+use crate::handler::target::{
+    BindingCall, ExposeBinding, GetName, GetParent, GetUrl, TargetMessage, WaitForRequest,
+    WaitForUrl,
+};
 use crate::handler::PageInner;
-use crate::js::{Evaluation, EvaluationResult};
+use crate::har::{self, Har, HarReplayOptions, HarRoute};
+use crate::js::{Evaluation, EvaluationResult, JsArg, JsHandle};
 use crate::layout::Point;
 use crate::listeners::{EventListenerRequest, EventStream};
+use crate::pattern::UrlPattern;
+use crate::performance::{CpuProfile, PerformanceMetrics, WebVitalsCollector};
+use crate::snapshot::DomSnapshot;
+use crate::storage::{self, IndexedDb, WebStorage};
+use crate::stream::IoStream;
 use crate::{utils, ArcHttpRequest};
 
 #[derive(Debug, Clone)]
@@ -294,6 +320,184 @@ impl Page {
         Ok(())
     }
 
+    /// Exposes a Rust function to the page as `window[name]`.
+    ///
+    /// Calling the function in the page returns a `Promise` that resolves with
+    /// the value returned by `function`, serialized to JSON. `function`
+    /// receives the arguments of the call as JSON array. The function is
+    /// available in every frame of the page, including out-of-process iframes,
+    /// and remains available after navigations.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     page.expose_rust_fn("add", |args: serde_json::Value| async move {
+    ///         args.as_array()
+    ///             .map(|args| args.iter().filter_map(|arg| arg.as_i64()).sum::<i64>())
+    ///             .unwrap_or_default()
+    ///     })
+    ///     .await?;
+    ///     let sum: i64 = page.evaluate("add(1, 2)").await?.into_value()?;
+    ///     assert_eq!(sum, 3);
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn expose_rust_fn<F, Fut, T>(
+        &self,
+        name: impl Into<String>,
+        function: F,
+    ) -> Result<&Self>
+    where
+        F: Fn(serde_json::Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Serialize,
+    {
+        let name = name.into();
+        let source = utils::evaluation_string(RUST_BINDING_SOURCE, &[name.as_str()]);
+        let (tx, mut rx) = unbounded::<BindingCall>();
+
+        // answer the calls of the binding
+        let inner = Arc::clone(&self.inner);
+        let function = Arc::new(function);
+        utils::spawn(async move {
+            while let Some(call) = rx.next().await {
+                let inner = Arc::clone(&inner);
+                let function = Arc::clone(&function);
+                // the runtime is available, spawning the listener succeeded
+                let _ = utils::spawn(async move {
+                    let (value, error) = match serde_json::to_value(function(call.args).await) {
+                        Ok(value) => (value, serde_json::Value::Null),
+                        Err(err) => (serde_json::Value::Null, err.to_string().into()),
+                    };
+                    let deliver = CallFunctionOnParams::builder()
+                        .function_declaration(RUST_BINDING_DELIVER)
+                        .execution_context_id(call.execution_context_id)
+                        .argument(CallArgument::builder().value(call.name).build())
+                        .argument(CallArgument::builder().value(call.seq).build())
+                        .argument(CallArgument::builder().value(value).build())
+                        .argument(CallArgument::builder().value(error).build())
+                        .build()
+                        .unwrap();
+                    // the calling document may be gone by now
+                    let _ = inner.execute_in_session(deliver, call.session_id).await;
+                });
+            }
+        })?;
+
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::ExposeBinding(ExposeBinding {
+                name: name.clone(),
+                source: source.clone(),
+                tx,
+            }))
+            .await?;
+        self.execute(AddBindingParams::new(name)).await?;
+        self.execute(AddScriptToEvaluateOnNewDocumentParams::new(source.clone()))
+            .await?;
+
+        // install the binding in the documents that are already loaded
+        for frame_id in self.frames().await? {
+            if let Some(context_id) = self.frame_execution_context(frame_id).await? {
+                let install = EvaluateParams::builder()
+                    .expression(source.clone())
+                    .context_id(context_id)
+                    .build()
+                    .unwrap();
+                // the document may have been replaced in the meantime
+                let _ = self.execute(install).await;
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Returns a stream of the messages logged via the page's `console` API.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use futures::StreamExt;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let mut messages = page.console_messages().await?;
+    ///     while let Some(message) = messages.next().await {
+    ///         println!("[{:?}] {}", message.level, message.text);
+    ///     }
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn console_messages(
+        &self,
+    ) -> Result<impl Stream<Item = ConsoleMessage> + Send + Unpin> {
+        Ok(self
+            .event_listener::<EventConsoleApiCalled>()
+            .await?
+            .map(|event| ConsoleMessage::from(&*event)))
+    }
+
+    /// Returns a stream of the uncaught exceptions thrown in the page.
+    pub async fn page_errors(&self) -> Result<impl Stream<Item = PageError> + Send + Unpin> {
+        Ok(self
+            .event_listener::<EventExceptionThrown>()
+            .await?
+            .map(|event| PageError::from(&*event)))
+    }
+
+    /// Returns a stream of the websocket connections the page opens from now
+    /// on.
+    ///
+    /// Each connection is itself a stream of the frames sent and received
+    /// over it, which ends once the connection is closed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::{Page, WebSocketPayload};
+    /// # use chromiumoxide::error::Result;
+    /// # use futures::StreamExt;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let mut websockets = page.websockets().await?;
+    ///     page.goto("https://example.com/chat").await?;
+    ///     if let Some(mut connection) = websockets.next().await {
+    ///         while let Some(frame) = connection.next().await {
+    ///             if let WebSocketPayload::Text(text) = &frame.payload {
+    ///                 println!("{:?}: {text}", frame.direction);
+    ///             }
+    ///         }
+    ///         println!("closed: {:?}", connection.close_reason());
+    ///     }
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn websockets(
+        &self,
+    ) -> Result<impl Stream<Item = WebSocketConnection> + Send + Unpin> {
+        let (tx, rx) = unbounded();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::WebSockets(tx))
+            .await?;
+        Ok(rx)
+    }
+
+    /// Returns a stream of the messages that `EventSource`s of the page
+    /// receive from now on.
+    pub async fn event_source_messages(
+        &self,
+    ) -> Result<impl Stream<Item = EventSourceMessage> + Send + Unpin> {
+        Ok(self
+            .event_listener::<EventEventSourceMessageReceived>()
+            .await?
+            .map(|event| EventSourceMessage::from(&*event)))
+    }
+
     /// This resolves once the navigation finished and the page is loaded.
     ///
     /// This is necessary after an interaction with the page that may trigger a
@@ -309,6 +513,267 @@ impl Page {
         Ok(self)
     }
 
+    /// Resolves once the main frame reached `options.wait_until`, with the
+    /// response of the main frame.
+    ///
+    /// Fails with [`CdpError::Timeout`] if that doesn't happen within
+    /// `options.timeout`.
+    pub async fn wait_for_navigation_with(
+        &self,
+        options: NavigationOptions,
+    ) -> Result<ArcHttpRequest> {
+        let navigation = self.inner.wait_for_navigation_until(options.wait_until);
+        match options.timeout {
+            Some(timeout) => utils::timeout(timeout, navigation).await?,
+            None => navigation.await,
+        }
+    }
+
+    /// Resolves with the next request of the page for which `predicate`
+    /// returns `true`.
+    ///
+    /// The request is matched once it is about to be sent, so it doesn't
+    /// include a response yet. To not miss the request, start waiting before
+    /// triggering it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let request = page.wait_for_request(|req| {
+    ///         req.url.as_deref().is_some_and(|url| url.ends_with("/api/save"))
+    ///     });
+    ///     let click = async {
+    ///         page.find_element("#save").await?.click().await?;
+    ///         Ok(())
+    ///     };
+    ///     let (request, _) = futures::try_join!(request, click)?;
+    ///     assert_eq!(request.method.as_deref(), Some("POST"));
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for_request(
+        &self,
+        predicate: impl Fn(&HttpRequest) -> bool + Send + 'static,
+    ) -> Result<HttpRequest> {
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::WaitForRequest(WaitForRequest::new(
+                predicate, tx,
+            )))
+            .await?;
+        Ok(rx.await?)
+    }
+
+    /// Same as [`Page::wait_for_request`] but fails with
+    /// [`CdpError::Timeout`] if no matching request is sent within `timeout`
+    pub async fn wait_for_request_with(
+        &self,
+        predicate: impl Fn(&HttpRequest) -> bool + Send + 'static,
+        timeout: Duration,
+    ) -> Result<HttpRequest> {
+        utils::timeout(timeout, self.wait_for_request(predicate)).await?
+    }
+
+    /// Resolves with the next request of the page for which `predicate`
+    /// returns `true` once its response is received.
+    ///
+    /// The `response` of the returned request is always set. To not miss the
+    /// response, start waiting before triggering the request.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let response = page.wait_for_response(|req| {
+    ///         req.url.as_deref().is_some_and(|url| url.ends_with("/api/save"))
+    ///     });
+    ///     let click = async {
+    ///         page.find_element("#save").await?.click().await?;
+    ///         Ok(())
+    ///     };
+    ///     let (request, _) = futures::try_join!(response, click)?;
+    ///     assert_eq!(request.response.map(|res| res.status), Some(200));
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for_response(
+        &self,
+        predicate: impl Fn(&HttpRequest) -> bool + Send + 'static,
+    ) -> Result<HttpRequest> {
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::WaitForResponse(WaitForRequest::new(
+                predicate, tx,
+            )))
+            .await?;
+        Ok(rx.await?)
+    }
+
+    /// Same as [`Page::wait_for_response`] but fails with
+    /// [`CdpError::Timeout`] if no matching response is received within
+    /// `timeout`
+    pub async fn wait_for_response_with(
+        &self,
+        predicate: impl Fn(&HttpRequest) -> bool + Send + 'static,
+        timeout: Duration,
+    ) -> Result<HttpRequest> {
+        utils::timeout(timeout, self.wait_for_response(predicate)).await?
+    }
+
+    /// Resolves with the url of the main frame once it matches `pattern` and
+    /// the page is loaded.
+    ///
+    /// Resolves right away if the current url already matches. Same-document
+    /// navigations, like `history.pushState` or changes of the fragment, are
+    /// taken into account as well. See [`UrlPattern`] for the syntax of glob
+    /// patterns.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     page.find_element("#login").await?.click().await?;
+    ///     page.wait_for_url("**/dashboard").await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for_url(&self, pattern: impl Into<UrlPattern>) -> Result<String> {
+        self.wait_for_url_with(pattern, NavigationOptions::default())
+            .await
+    }
+
+    /// Resolves with the url of the main frame once it matches `pattern` and
+    /// the main frame reached `options.wait_until`.
+    ///
+    /// Fails with [`CdpError::Timeout`] if that doesn't happen within
+    /// `options.timeout`.
+    pub async fn wait_for_url_with(
+        &self,
+        pattern: impl Into<UrlPattern>,
+        options: NavigationOptions,
+    ) -> Result<String> {
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::WaitForUrl(WaitForUrl {
+                pattern: pattern.into(),
+                wait_until: options.wait_until,
+                tx,
+            }))
+            .await?;
+        match options.timeout {
+            Some(timeout) => Ok(utils::timeout(timeout, rx).await??),
+            None => Ok(rx.await?),
+        }
+    }
+
+    /// Resolves with the value of `js` once it is truthy.
+    ///
+    /// `js` is either an expression or a function, which may be async, and
+    /// is reevaluated as often as `polling` dictates. If the page navigates
+    /// in the meantime, polling continues in the new document.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::{Page, Polling};
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     page.wait_for_function("window.appReady === true", Polling::Raf).await?;
+    ///     let count: usize = page
+    ///         .wait_for_function(
+    ///             "() => document.querySelectorAll('li').length",
+    ///             Polling::Mutation,
+    ///         )
+    ///         .await?
+    ///         .into_value()?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for_function(
+        &self,
+        js: impl AsRef<str>,
+        polling: Polling,
+    ) -> Result<EvaluationResult> {
+        self.poll_function(js.as_ref(), polling, None).await
+    }
+
+    /// Same as [`Page::wait_for_function`] but fails with
+    /// [`CdpError::Timeout`] if `js` isn't truthy within `timeout`
+    pub async fn wait_for_function_with(
+        &self,
+        js: impl AsRef<str>,
+        polling: Polling,
+        timeout: Duration,
+    ) -> Result<EvaluationResult> {
+        utils::timeout(
+            timeout,
+            self.poll_function(js.as_ref(), polling, Some(timeout)),
+        )
+        .await?
+    }
+
+    /// Polls `js` in the page until it is truthy, the page itself stops
+    /// polling after `timeout`
+    async fn poll_function(
+        &self,
+        js: &str,
+        polling: Polling,
+        timeout: Option<Duration>,
+    ) -> Result<EvaluationResult> {
+        let (polling, interval) = match polling {
+            Polling::Raf => ("raf", 0),
+            Polling::Mutation => ("mutation", 0),
+            Polling::Interval(interval) => ("interval", interval.as_millis()),
+        };
+        let timeout = timeout.map(|t| t.as_millis()).unwrap_or_default();
+        let expression =
+            format!("({WAIT_FOR_FUNCTION})(() => ({js}), '{polling}', {interval}, {timeout})");
+        loop {
+            // the evaluation resolves once `js` is truthy, which may take longer
+            // than the request timeout, the page's own timeout bounds it instead
+            let res = self
+                .inner
+                .evaluate_expression_with_timeout(expression.as_str(), Some(Duration::MAX))
+                .await;
+            match res {
+                Err(CdpError::Chrome(err)) if is_context_destroyed(&err.message) => {
+                    // the page navigated, evaluate again once the new document
+                    // is ready
+                    self.wait_for_navigation_until(WaitUntil::DomContentLoaded)
+                        .await?;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Resolves once the main frame reached `wait_until`
+    async fn wait_for_navigation_until(&self, wait_until: WaitUntil) -> Result<ArcHttpRequest> {
+        self.inner.wait_for_navigation_until(wait_until).await
+    }
+
+    /// The response of the request that loaded the current document of the
+    /// main frame
+    async fn main_frame_response(&self) -> Result<ArcHttpRequest> {
+        // the main frame of a page is always committed
+        self.inner
+            .wait_for_navigation_until(WaitUntil::Commit)
+            .await
+    }
+
     /// Navigate directly to the given URL.
     ///
     /// This resolves directly after the requested URL is fully loaded.
@@ -321,6 +786,46 @@ impl Page {
         Ok(self)
     }
 
+    /// Navigate to the given URL and resolve once the navigation reached
+    /// `options.wait_until`, with the response of the main frame.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::{GotoOptions, Page, WaitUntil};
+    /// # use chromiumoxide::error::Result;
+    /// # use std::time::Duration;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let response = page
+    ///         .goto_with(
+    ///             "https://example.com",
+    ///             GotoOptions {
+    ///                 wait_until: WaitUntil::NetworkIdle0,
+    ///                 timeout: Some(Duration::from_secs(10)),
+    ///                 ..Default::default()
+    ///             },
+    ///         )
+    ///         .await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn goto_with(
+        &self,
+        url: impl Into<String>,
+        options: GotoOptions,
+    ) -> Result<ArcHttpRequest> {
+        let mut params = NavigateParams::new(url.into());
+        params.referrer = options.referer;
+        let res = self
+            .inner
+            .navigate(params, options.wait_until, options.timeout)
+            .await?;
+        if let Some(err) = res.result.error_text {
+            return Err(CdpError::ChromeMessage(err));
+        }
+        self.main_frame_response().await
+    }
+
     /// The identifier of the `Target` this page belongs to
     pub fn target_id(&self) -> &TargetId {
         self.inner.target_id()
@@ -427,6 +932,342 @@ impl Page {
         Ok(self)
     }
 
+    /// Sends the given HTTP headers with every request of the page,
+    /// replacing previously set extra headers and the defaults of the
+    /// `BrowserConfig`.
+    pub async fn set_extra_http_headers(
+        &self,
+        headers: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Result<&Self> {
+        let headers = headers
+            .into_iter()
+            .map(|(name, value)| (name.into(), value.into()))
+            .collect();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::SetExtraHeaders(headers))
+            .await?;
+        Ok(self)
+    }
+
+    /// Adds, removes or modifies the headers of all requests of the page whose
+    /// url matches `pattern`.
+    ///
+    /// The headers are rewritten by intercepting the requests, so this has no
+    /// effect on requests that are intercepted by the user, see
+    /// `BrowserConfigBuilder::enable_request_intercept`. Header names are
+    /// matched case-sensitively. Headers the browser adds after interception,
+    /// like `Cookie`, are not part of the rewritten headers.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     page.rewrite_headers("https://api.example.com/**", |headers| {
+    ///         headers.insert("x-trace-id".to_string(), "1234".to_string());
+    ///         headers.remove("Authorization");
+    ///     })
+    ///     .await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn rewrite_headers(
+        &self,
+        pattern: impl Into<UrlPattern>,
+        rewrite: impl Fn(&mut HashMap<String, String>) + Send + Sync + 'static,
+    ) -> Result<&Self> {
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::RewriteHeaders(HeaderRewrite::new(
+                pattern, rewrite,
+            )))
+            .await?;
+        Ok(self)
+    }
+
+    /// Blocks all requests of the page whose url matches any of the patterns,
+    /// in which `*` is a wildcard, replacing previously blocked patterns.
+    ///
+    /// See https://chromedevtools.github.io/devtools-protocol/tot/Network#method-setBlockedURLs
+    pub async fn block_urls(
+        &self,
+        patterns: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<&Self> {
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::BlockUrls(
+                patterns.into_iter().map(Into::into).collect(),
+            ))
+            .await?;
+        Ok(self)
+    }
+
+    /// Blocks all requests of the page that match any of the filters,
+    /// replacing previously set filters and the defaults of the
+    /// `BrowserConfig`.
+    ///
+    /// The requests are blocked by intercepting them, so the filters have no
+    /// effect on requests that are intercepted by the user, see
+    /// `BrowserConfigBuilder::enable_request_intercept`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::{Page, ResourceFilter};
+    /// # use chromiumoxide::cdp::browser_protocol::network::ResourceType;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     page.set_resource_filters([
+    ///         ResourceFilter::ResourceType(ResourceType::Image),
+    ///         ResourceFilter::ResourceType(ResourceType::Font),
+    ///         ResourceFilter::Glob("**/analytics/**".to_string()),
+    ///     ])
+    ///     .await?;
+    ///     page.goto("https://example.com").await?;
+    ///     println!("blocked {} requests", page.blocked_requests().await?.total);
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_resource_filters(
+        &self,
+        filters: impl IntoIterator<Item = impl Into<ResourceFilter>>,
+    ) -> Result<&Self> {
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::SetResourceFilters(
+                filters.into_iter().map(Into::into).collect(),
+            ))
+            .await?;
+        Ok(self)
+    }
+
+    /// Aborts all requests of the page that the adblock engine blocks.
+    ///
+    /// With cosmetic filtering enabled, elements matched by the element hiding
+    /// filters are hidden in documents loaded from now on.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide::adblock::AdblockEngine;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let engine = AdblockEngine::from_file("easylist.txt")?;
+    ///     page.enable_adblock(engine.into_options().cosmetic_filtering(true))
+    ///         .await?;
+    ///     page.goto("https://example.com").await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn enable_adblock(&self, adblock: impl Into<AdblockOptions>) -> Result<&Self> {
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::SetAdblock(Some(adblock.into())))
+            .await?;
+        Ok(self)
+    }
+
+    /// Stops blocking requests with the adblock engine
+    pub async fn disable_adblock(&self) -> Result<&Self> {
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::SetAdblock(None))
+            .await?;
+        Ok(self)
+    }
+
+    /// Serves the requests of the page from the HTTP archive (HAR) at the
+    /// `path`.
+    ///
+    /// Requests are matched to the entries of the archive by their method,
+    /// url and post data. Requests without an entry are handled according to
+    /// [`HarReplayOptions::not_found`], unless [`HarReplayOptions::update`] is
+    /// set, in which case they are sent to the network and recorded into the
+    /// archive. The recorded requests are written to the archive, which is
+    /// created if it doesn't exist yet, once the page was closed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide::har::HarReplayOptions;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let options = HarReplayOptions {
+    ///         url_filter: Some("**/api/**".into()),
+    ///         update: std::env::var("UPDATE_HAR").is_ok(),
+    ///         ..Default::default()
+    ///     };
+    ///     page.route_from_har("tests/fixtures/api.har", options).await?;
+    ///     page.goto("https://example.com").await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn route_from_har(
+        &self,
+        path: impl AsRef<Path>,
+        options: HarReplayOptions,
+    ) -> Result<&Self> {
+        let path = path.as_ref().to_path_buf();
+        let har = match Har::from_file(&path).await {
+            Ok(har) => har,
+            Err(CdpError::Io(err)) if options.update && err.kind() == io::ErrorKind::NotFound => {
+                Har::default()
+            }
+            Err(err) => return Err(err),
+        };
+        let har = Arc::new(Mutex::new(har));
+        let recorder = if options.update {
+            let (tx, mut rx) = unbounded::<HttpRequest>();
+            let inner = Arc::clone(&self.inner);
+            let har = Arc::clone(&har);
+            utils::spawn(async move {
+                let mut recorded = false;
+                // the requests are recorded until the route is dropped with the page
+                while let Some(request) = rx.next().await {
+                    let body = inner
+                        .execute(GetResponseBodyParams::new(request.request_id().clone()))
+                        .await
+                        .ok()
+                        .and_then(|res| decode_response_body(res.result).ok());
+                    recorded |= har::record(&har, &request, body);
+                }
+                if recorded {
+                    if let Err(err) = har::save(&har, &path).await {
+                        tracing::warn!("Failed to write the archive {}: {err}", path.display());
+                    }
+                }
+            })?;
+            Some(tx)
+        } else {
+            None
+        };
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::RouteFromHar(HarRoute::new(
+                har, options, recorder,
+            )))
+            .await?;
+        Ok(self)
+    }
+
+    /// Returns the counters of the requests of the page that were blocked so
+    /// far, by resource filters, the adblock engine or blocked url patterns
+    pub async fn blocked_requests(&self) -> Result<BlockedRequests> {
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::BlockedRequests(tx))
+            .await?;
+        Ok(rx.await?)
+    }
+
+    /// Returns the body of the response to the request with the `request_id`.
+    ///
+    /// The body is available once the request finished loading, for instance
+    /// after [`Page::wait_for_response`] resolved, and as long as chromium
+    /// keeps it in its buffers, whose size is set with
+    /// `BrowserConfigBuilder::response_buffer_size`. Use
+    /// [`Page::response_body_stream`] for large bodies.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let (response, _) = futures::try_join!(
+    ///         page.wait_for_response(|req| {
+    ///             req.url.as_deref().map_or(false, |url| url.ends_with("/api/items"))
+    ///         }),
+    ///         page.goto("https://example.com"),
+    ///     )?;
+    ///     let body = page.response_body(response.request_id().clone()).await?;
+    ///     let items: serde_json::Value = serde_json::from_slice(&body)?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn response_body(&self, request_id: impl Into<RequestId>) -> Result<Vec<u8>> {
+        let res = self.execute(GetResponseBodyParams::new(request_id)).await?;
+        decode_response_body(res.result)
+    }
+
+    /// Pauses the responses of all requests of the page whose url matches
+    /// any of the patterns, in which `*` is a wildcard, replacing previously
+    /// set patterns. An empty list stops pausing responses.
+    ///
+    /// Each paused response is reported as `EventRequestPaused` with its
+    /// `response_status_code` set and must be continued, fulfilled or failed
+    /// via the `Fetch` domain, see [`Page::response_body_stream`].
+    pub async fn intercept_responses(
+        &self,
+        patterns: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<&Self> {
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::InterceptResponses(
+                patterns.into_iter().map(Into::into).collect(),
+            ))
+            .await?;
+        Ok(self)
+    }
+
+    /// Streams the body of a response that is paused in the response stage,
+    /// see [`Page::intercept_responses`].
+    ///
+    /// The body is read chunk by chunk with `IO.read` instead of being
+    /// buffered entirely. Once the stream was taken, the response can no
+    /// longer be continued as is, it must be fulfilled or failed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide_cdp::cdp::browser_protocol::fetch::{EventRequestPaused, FailRequestParams};
+    /// # use chromiumoxide_cdp::cdp::browser_protocol::network::ErrorReason;
+    /// # use futures::StreamExt;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let mut paused = page.event_listener::<EventRequestPaused>().await?;
+    ///     page.intercept_responses(["*.zip"]).await?;
+    ///     while let Some(event) = paused.next().await {
+    ///         if event.response_status_code.is_none() {
+    ///             continue;
+    ///         }
+    ///         let mut body = page.response_body_stream(event.request_id.clone()).await?;
+    ///         let mut len = 0;
+    ///         while let Some(chunk) = body.next().await {
+    ///             len += chunk?.len();
+    ///         }
+    ///         println!("{} has {len} bytes", event.request.url);
+    ///         page.execute(FailRequestParams::new(event.request_id.clone(), ErrorReason::Aborted))
+    ///             .await?;
+    ///     }
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn response_body_stream(
+        &self,
+        request_id: impl Into<fetch::RequestId>,
+    ) -> Result<IoStream> {
+        let res = self
+            .execute(TakeResponseBodyAsStreamParams::new(request_id))
+            .await?;
+        Ok(IoStream::new(Arc::clone(&self.inner), res.result.stream))
+    }
+
     /// Returns the user agent of the browser
     pub async fn user_agent(&self) -> Result<String> {
         Ok(self.inner.version().await?.user_agent)
@@ -495,6 +1336,44 @@ impl Page {
         Ok(())
     }
 
+    /// Whether the page was closed, either via `Page::close` or externally.
+    pub fn is_closed(&self) -> bool {
+        self.inner.sender().is_closed()
+    }
+
+    /// Resolves once the page was closed, either via `Page::close` or
+    /// externally.
+    pub async fn on_close(&self) {
+        let (tx, rx) = oneshot_channel();
+        if self
+            .inner
+            .sender()
+            .clone()
+            .send(TargetMessage::WaitForClose(tx))
+            .await
+            .is_ok()
+        {
+            // the sender is dropped if the target is gone without notice
+            let _ = rx.await;
+        }
+    }
+
+    /// Resolves once the renderer of the page crashed.
+    ///
+    /// Fails with [`CdpError::TargetClosed`] if the page is closed first.
+    /// Once crashed, all commands of the page fail with
+    /// [`CdpError::TargetCrashed`].
+    pub async fn on_crash(&self) -> Result<()> {
+        let (tx, rx) = oneshot_channel();
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::WaitForCrash(tx))
+            .await
+            .map_err(|_| CdpError::TargetClosed)?;
+        rx.await.map_err(|_| CdpError::TargetClosed)
+    }
+
     /// Performs a single mouse click event at the point's location.
     ///
     /// This scrolls the point into view first, then executes a
@@ -609,10 +1488,12 @@ impl Page {
 
     /// Print the current page as pdf.
     ///
-    /// See [`PrintToPdfParams`]
+    /// See [`PdfOptions`](crate::pdf::PdfOptions) and [`PrintToPdfParams`]
     ///
     /// # Note Generating a pdf is currently only supported in Chrome headless.
-    pub async fn pdf(&self, params: PrintToPdfParams) -> Result<Vec<u8>> {
+    pub async fn pdf(&self, params: impl Into<PrintToPdfParams>) -> Result<Vec<u8>> {
+        let mut params = params.into();
+        params.transfer_mode = Some(PrintToPdfTransferMode::ReturnAsBase64);
         let res = self.execute(params).await?;
         Ok(utils::base64::decode(&res.data)?)
     }
@@ -623,7 +1504,7 @@ impl Page {
     /// # Note Generating a pdf is currently only supported in Chrome headless.
     pub async fn save_pdf(
         &self,
-        opts: PrintToPdfParams,
+        opts: impl Into<PrintToPdfParams>,
         output: impl AsRef<Path>,
     ) -> Result<Vec<u8>> {
         let pdf = self.pdf(opts).await?;
@@ -631,6 +1512,113 @@ impl Page {
         Ok(pdf)
     }
 
+    /// Print the current page as pdf and return a stream of its data.
+    ///
+    /// In contrast to `Page::pdf`, the pdf is read chunk by chunk, so large
+    /// documents are never held in memory as a whole.
+    ///
+    /// # Note Generating a pdf is currently only supported in Chrome headless.
+    pub async fn pdf_stream(&self, params: impl Into<PrintToPdfParams>) -> Result<IoStream> {
+        let mut params = params.into();
+        params.transfer_mode = Some(PrintToPdfTransferMode::ReturnAsStream);
+        let res = self.execute(params).await?;
+        let handle = res
+            .result
+            .stream
+            .ok_or_else(|| CdpError::msg("No stream returned for the pdf"))?;
+        Ok(IoStream::new(Arc::clone(&self.inner), handle))
+    }
+
+    /// Print the current page as pdf and write it to the `writer` chunk by
+    /// chunk, returning the number of bytes written.
+    ///
+    /// # Example stream a pdf into a file
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide::pdf::{PaperFormat, PdfOptions};
+    /// # async fn demo(page: Page, file: impl futures::AsyncWrite + Unpin) -> Result<()> {
+    ///     let options = PdfOptions::builder().format(PaperFormat::A4).build();
+    ///     page.pdf_to_writer(options, file).await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Note Generating a pdf is currently only supported in Chrome headless.
+    pub async fn pdf_to_writer<W: AsyncWrite + Unpin>(
+        &self,
+        params: impl Into<PrintToPdfParams>,
+        mut writer: W,
+    ) -> Result<u64> {
+        let mut stream = self.pdf_stream(params).await?;
+        let mut written = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            writer.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    /// Returns the current page with all of its resources as MHTML archive.
+    pub async fn mhtml(&self) -> Result<String> {
+        let res = self
+            .execute(
+                CaptureSnapshotParams::builder()
+                    .format(CaptureSnapshotFormat::Mhtml)
+                    .build(),
+            )
+            .await?;
+        Ok(res.result.data)
+    }
+
+    /// Save the current page as MHTML archive to the `output` path and return
+    /// the archive.
+    pub async fn save_mhtml(&self, output: impl AsRef<Path>) -> Result<String> {
+        let mhtml = self.mhtml().await?;
+        utils::write(output.as_ref(), &mhtml).await?;
+        Ok(mhtml)
+    }
+
+    /// Captures the DOM of all documents of the page, including iframes and
+    /// shadow trees, with the layout of the rendered nodes and the values of
+    /// the given `computed_styles`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let snapshot = page.dom_snapshot(["display", "color"]).await?;
+    ///     for node in &snapshot.documents[0].nodes {
+    ///         if let Some(layout) = &node.layout {
+    ///             println!("{} {:?} {:?}", node.name, layout.bounds, layout.styles);
+    ///         }
+    ///     }
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn dom_snapshot(
+        &self,
+        computed_styles: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<DomSnapshot> {
+        let computed_styles: Vec<String> = computed_styles.into_iter().map(Into::into).collect();
+        let res = self
+            .execute(
+                browser_protocol::dom_snapshot::CaptureSnapshotParams::builder()
+                    .computed_styles(computed_styles.clone())
+                    .include_dom_rects(true)
+                    .include_paint_order(true)
+                    .build()
+                    .map_err(CdpError::msg)?,
+            )
+            .await?;
+        Ok(DomSnapshot::new(res.result, &computed_styles))
+    }
+
     /// Brings page to front (activates tab)
     pub async fn bring_to_front(&self) -> Result<&Self> {
         self.execute(BringToFrontParams::default()).await?;
@@ -686,6 +1674,45 @@ impl Page {
         Ok(self)
     }
 
+    /// Emulates the latency, throughput and connectivity of the network for
+    /// the page, including its out-of-process iframes.
+    ///
+    /// Pass `NetworkConditions::default()` to disable the emulation again.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use chromiumoxide::page::{NetworkConditions, Page};
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     page.emulate_network_conditions(NetworkConditions::slow_3g()).await?;
+    ///     // a custom flaky connection
+    ///     page.emulate_network_conditions(
+    ///         NetworkConditions::new(Duration::from_millis(300), 250_000., 100_000.)
+    ///             .packet_loss(5.),
+    ///     )
+    ///     .await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn emulate_network_conditions(&self, conditions: NetworkConditions) -> Result<&Self> {
+        self.execute(conditions.params()).await?;
+        self.inner
+            .sender()
+            .clone()
+            .send(TargetMessage::EmulateNetworkConditions(conditions))
+            .await?;
+        Ok(self)
+    }
+
+    /// Slows down the CPU of the page by the factor `rate`, where `1.` means
+    /// no throttling and `4.` a 4x slowdown
+    pub async fn emulate_cpu_throttling(&self, rate: f64) -> Result<&Self> {
+        self.execute(SetCpuThrottlingRateParams::new(rate)).await?;
+        Ok(self)
+    }
+
     /// Reloads given page
     ///
     /// To reload ignoring cache run:
@@ -704,6 +1731,94 @@ impl Page {
         self.wait_for_navigation().await
     }
 
+    /// Reloads the page and resolves once the reload reached
+    /// `options.wait_until`, with the response of the main frame.
+    pub async fn reload_with(&self, options: NavigationOptions) -> Result<ArcHttpRequest> {
+        self.inner
+            .navigate(ReloadParams::default(), options.wait_until, options.timeout)
+            .await?;
+        self.main_frame_response().await
+    }
+
+    /// Returns the navigation history of the page and the index of the
+    /// current entry in it.
+    pub async fn navigation_history(&self) -> Result<GetNavigationHistoryReturns> {
+        Ok(self
+            .execute(GetNavigationHistoryParams::default())
+            .await?
+            .result)
+    }
+
+    /// Navigates to the previous entry in the history of the page.
+    ///
+    /// Returns `None` if there is no previous entry. Pages restored from the
+    /// back/forward cache are not requested again and resolve with `None` as
+    /// well, whereas same-document entries resolve with the response of the
+    /// current document.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     page.goto("https://example.com").await?;
+    ///     page.goto("https://example.com/other").await?;
+    ///     page.go_back().await?;
+    ///     assert_eq!(page.url().await?.as_deref(), Some("https://example.com/"));
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn go_back(&self) -> Result<ArcHttpRequest> {
+        self.go_back_with(NavigationOptions::default()).await
+    }
+
+    /// Navigates to the previous entry in the history of the page and resolves
+    /// once the navigation reached `options.wait_until`.
+    pub async fn go_back_with(&self, options: NavigationOptions) -> Result<ArcHttpRequest> {
+        self.navigate_history(-1, options).await
+    }
+
+    /// Navigates to the next entry in the history of the page.
+    ///
+    /// See [`Page::go_back`] for the returned response.
+    pub async fn go_forward(&self) -> Result<ArcHttpRequest> {
+        self.go_forward_with(NavigationOptions::default()).await
+    }
+
+    /// Navigates to the next entry in the history of the page and resolves
+    /// once the navigation reached `options.wait_until`.
+    pub async fn go_forward_with(&self, options: NavigationOptions) -> Result<ArcHttpRequest> {
+        self.navigate_history(1, options).await
+    }
+
+    /// Navigates `delta` entries away from the current history entry
+    async fn navigate_history(
+        &self,
+        delta: i64,
+        options: NavigationOptions,
+    ) -> Result<ArcHttpRequest> {
+        let history = self.navigation_history().await?;
+        let index = history.current_index + delta;
+        let entry = if index < 0 {
+            None
+        } else {
+            history.entries.get(index as usize)
+        };
+        if let Some(entry) = entry {
+            self.inner
+                .navigate(
+                    NavigateToHistoryEntryParams::new(entry.id),
+                    options.wait_until,
+                    options.timeout,
+                )
+                .await?;
+            self.main_frame_response().await
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Enables log domain. Enabled by default.
     ///
     /// Sends the entries collected so far to the client by means of the
@@ -915,6 +2030,57 @@ impl Page {
         Ok(self)
     }
 
+    /// Returns the `localStorage` of the `origin`, e.g. `https://example.com`
+    ///
+    /// The page needs a loaded frame of the `origin` to access its storage.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let storage = page.local_storage("https://example.com");
+    ///     storage.set("theme", "dark").await?;
+    ///     assert_eq!(storage.get("theme").await?.as_deref(), Some("dark"));
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub fn local_storage(&self, origin: impl Into<String>) -> WebStorage {
+        WebStorage::new(Arc::clone(&self.inner), origin.into(), true)
+    }
+
+    /// Returns the `sessionStorage` of the `origin`, e.g. `https://example.com`
+    pub fn session_storage(&self, origin: impl Into<String>) -> WebStorage {
+        WebStorage::new(Arc::clone(&self.inner), origin.into(), false)
+    }
+
+    /// Returns the IndexedDB databases of the `origin`, e.g.
+    /// `https://example.com`
+    pub async fn indexed_db(&self, origin: impl Into<String>) -> Result<IndexedDb> {
+        IndexedDb::new(Arc::clone(&self.inner), origin.into()).await
+    }
+
+    /// Clears the given types of data, like cookies or the local storage, of
+    /// the `origin`
+    pub async fn clear_data_for_origin(
+        &self,
+        origin: impl Into<String>,
+        storage_types: impl IntoIterator<Item = StorageType>,
+    ) -> Result<&Self> {
+        self.execute(ClearDataForOriginParams::new(
+            origin,
+            storage::storage_types_param(storage_types),
+        ))
+        .await?;
+        Ok(self)
+    }
+
+    /// Clears all data of the `origin`
+    pub async fn clear_all_data_for_origin(&self, origin: impl Into<String>) -> Result<&Self> {
+        self.clear_data_for_origin(origin, [StorageType::All]).await
+    }
+
     /// Returns the title of the document.
     pub async fn get_title(&self) -> Result<Option<String>> {
         let result = self.evaluate("document.title").await?;
@@ -937,6 +2103,213 @@ impl Page {
             .metrics)
     }
 
+    /// Retrieve current values of run-time metrics as [`PerformanceMetrics`].
+    pub async fn performance_metrics(&self) -> Result<PerformanceMetrics> {
+        Ok(self.metrics().await?.into())
+    }
+
+    /// Starts collecting the [Core Web Vitals](https://web.dev/articles/vitals)
+    /// of the page's navigations.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use futures::StreamExt;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let mut collector = page.web_vitals().await?;
+    ///     page.goto("https://example.com").await?;
+    ///     let vitals = collector.current().await?;
+    ///     println!("{}: LCP {:?}ms, CLS {}", vitals.url, vitals.lcp, vitals.cls);
+    ///
+    ///     // the final vitals of a navigation are reported once it is left
+    ///     page.goto("https://example.org").await?;
+    ///     if let Some(vitals) = collector.next().await {
+    ///         println!("{}: INP {:?}ms, CLS {}", vitals.url, vitals.inp, vitals.cls);
+    ///     }
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn web_vitals(&self) -> Result<WebVitalsCollector> {
+        WebVitalsCollector::new(Arc::clone(&self.inner)).await
+    }
+
+    /// Takes a heap snapshot of the page's JavaScript heap and writes it to
+    /// the `writer` in the `.heapsnapshot` format of the DevTools memory
+    /// panel, returning the number of bytes written.
+    ///
+    /// The snapshot is written chunk by chunk as the browser reports it, so
+    /// it is never held in memory as a whole.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let mut snapshot = Vec::new();
+    ///     page.take_heap_snapshot(&mut snapshot).await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn take_heap_snapshot<W: AsyncWrite + Unpin>(&self, writer: W) -> Result<u64> {
+        self.take_heap_snapshot_with_progress(writer, |_| {}).await
+    }
+
+    /// Same as `Page::take_heap_snapshot`, but calls `on_progress` with the
+    /// progress of the snapshot, which can take a while for large heaps.
+    pub async fn take_heap_snapshot_with_progress<W, F>(
+        &self,
+        mut writer: W,
+        mut on_progress: F,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(&EventReportHeapSnapshotProgress),
+    {
+        self.execute(heap_profiler::EnableParams::default()).await?;
+        let mut chunks = self
+            .event_listener::<EventAddHeapSnapshotChunk>()
+            .await?
+            .fuse();
+        let mut progress = self
+            .event_listener::<EventReportHeapSnapshotProgress>()
+            .await?
+            .fuse();
+        // large heaps take longer than the request timeout, the snapshot
+        // fails anyway if the page goes away
+        let take = self
+            .inner
+            .execute_with_timeout(
+                TakeHeapSnapshotParams::builder()
+                    .report_progress(true)
+                    .build(),
+                Duration::MAX,
+            )
+            .fuse();
+        futures::pin_mut!(take);
+
+        let mut written = 0;
+        loop {
+            futures::select! {
+                chunk = chunks.next() => {
+                    if let Some(chunk) = chunk {
+                        writer.write_all(chunk.chunk.as_bytes()).await?;
+                        written += chunk.chunk.len() as u64;
+                    }
+                }
+                ev = progress.next() => {
+                    if let Some(ev) = ev {
+                        on_progress(&ev);
+                    }
+                }
+                res = take => {
+                    res?;
+                    break;
+                }
+            }
+        }
+        // all chunks are received before the response, but may not have been
+        // polled yet
+        while let Some(Some(chunk)) = chunks.next().now_or_never() {
+            writer.write_all(chunk.chunk.as_bytes()).await?;
+            written += chunk.chunk.len() as u64;
+        }
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    /// Starts sampling the allocations of the page's JavaScript heap, until
+    /// `Page::stop_sampling_heap_profile` is called.
+    pub async fn start_sampling_heap_profile(&self) -> Result<&Self> {
+        self.execute(heap_profiler::EnableParams::default()).await?;
+        self.execute(heap_profiler::StartSamplingParams::default())
+            .await?;
+        Ok(self)
+    }
+
+    /// Stops sampling the allocations and returns the profile of the objects
+    /// that were allocated since `Page::start_sampling_heap_profile` and are
+    /// still alive.
+    pub async fn stop_sampling_heap_profile(&self) -> Result<SamplingHeapProfile> {
+        Ok(self
+            .execute(heap_profiler::StopSamplingParams::default())
+            .await?
+            .result
+            .profile)
+    }
+
+    /// Starts the sampling CPU profiler of the page's JavaScript, until
+    /// `Page::stop_cpu_profile` is called.
+    ///
+    /// The `sampling_interval` defaults to the browser's interval of 1ms if
+    /// `None`.
+    pub async fn start_cpu_profile(
+        &self,
+        sampling_interval: impl Into<Option<Duration>>,
+    ) -> Result<&Self> {
+        self.execute(profiler::EnableParams::default()).await?;
+        if let Some(interval) = sampling_interval.into() {
+            self.execute(profiler::SetSamplingIntervalParams::new(
+                interval.as_micros() as i64,
+            ))
+            .await?;
+        }
+        self.execute(profiler::StartParams::default()).await?;
+        Ok(self)
+    }
+
+    /// Stops the CPU profiler started with `Page::start_cpu_profile` and
+    /// returns the recorded profile.
+    pub async fn stop_cpu_profile(&self) -> Result<CpuProfile> {
+        let profile = self
+            .execute(profiler::StopParams::default())
+            .await?
+            .result
+            .profile;
+        Ok(profile.into())
+    }
+
+    /// Records a CPU profile of the page while the future returned by `f`
+    /// runs.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let (res, profile) = page
+    ///         .profile(|| async {
+    ///             page.find_element("button").await?.click().await?;
+    ///             page.wait_for_navigation().await?;
+    ///             Ok::<_, chromiumoxide::error::CdpError>(())
+    ///         })
+    ///         .await?;
+    ///     res?;
+    ///     profile.save("click.cpuprofile").await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn profile<F, Fut, T>(&self, f: F) -> Result<(T, CpuProfile)>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.start_cpu_profile(None).await?;
+        let output = f().await;
+        let profile = self.stop_cpu_profile().await?;
+        Ok((output, profile))
+    }
+
+    /// Forces a garbage collection of the page's JavaScript heap
+    pub async fn collect_garbage(&self) -> Result<&Self> {
+        self.execute(heap_profiler::CollectGarbageParams::default())
+            .await?;
+        Ok(self)
+    }
+
     /// Returns metrics relating to the layout of the page
     pub async fn layout_metrics(&self) -> Result<GetLayoutMetricsReturns> {
         self.inner.layout_metrics().await
@@ -1117,6 +2490,87 @@ impl Page {
         self.inner.evaluate_function(evaluate).await
     }
 
+    /// Calls the function in the page's context with the `args` as arguments
+    /// and returns the result.
+    ///
+    /// Arguments are either values that are serialized as JSON or handles to
+    /// objects in the page, see [`JsArg`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # use chromiumoxide::js::JsArg;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let sum: usize = page
+    ///         .evaluate_with_args("(a, b) => a + b", [JsArg::value(1)?, JsArg::value(2)?])
+    ///         .await?
+    ///         .into_value()?;
+    ///     assert_eq!(sum, 3);
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn evaluate_with_args(
+        &self,
+        function: impl Into<String>,
+        args: impl IntoIterator<Item = JsArg>,
+    ) -> Result<EvaluationResult> {
+        let call = CallFunctionOnParams::builder()
+            .function_declaration(function)
+            .arguments(args.into_iter().map(CallArgument::from))
+            .build()
+            .map_err(CdpError::msg)?;
+        self.evaluate_function(call).await
+    }
+
+    /// Evaluates an expression or function in the page's context like
+    /// `Page::evaluate`, but returns a handle to the result instead of its
+    /// value.
+    ///
+    /// If the evaluation sets an object group, the object is only released
+    /// with the group via `Page::release_object_group`, otherwise it is
+    /// released once the handle is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use chromiumoxide::page::Page;
+    /// # use chromiumoxide::error::Result;
+    /// # async fn demo(page: Page) -> Result<()> {
+    ///     let window = page.evaluate_handle("window").await?;
+    ///     let location = window.get_property("location").await?;
+    ///     let href: String = location.get_property("href").await?.into_value().await?;
+    ///     # Ok(())
+    /// # }
+    /// ```
+    pub async fn evaluate_handle(&self, evaluate: impl Into<Evaluation>) -> Result<JsHandle> {
+        let (result, group) = match evaluate.into() {
+            Evaluation::Expression(mut expr) => {
+                expr.return_by_value = Some(false);
+                let group = expr.object_group.clone();
+                (self.evaluate_expression(expr).await?, group)
+            }
+            Evaluation::Function(mut fun) => {
+                fun.return_by_value = Some(false);
+                let group = fun.object_group.clone();
+                (self.evaluate_function(fun).await?, group)
+            }
+        };
+        Ok(JsHandle::new(
+            Arc::clone(&self.inner),
+            result.object().clone(),
+            group,
+        ))
+    }
+
+    /// Releases all objects of the object group, which invalidates the
+    /// [`JsHandle`]s of the group.
+    pub async fn release_object_group(&self, group: impl Into<String>) -> Result<&Self> {
+        self.execute(ReleaseObjectGroupParams::new(group)).await?;
+        Ok(self)
+    }
+
     /// Returns the default execution context identifier of this page that
     /// represents the context for JavaScript execution.
     pub async fn execution_context(&self) -> Result<Option<ExecutionContextId>> {
@@ -1169,6 +2623,16 @@ impl Page {
     /// # }
     /// ```
     pub async fn set_content(&self, html: impl AsRef<str>) -> Result<&Self> {
+        self.set_content_with(html, Default::default()).await
+    }
+
+    /// Set the content of the frame and resolve once it reached
+    /// `options.wait_until`.
+    pub async fn set_content_with(
+        &self,
+        html: impl AsRef<str>,
+        options: NavigationOptions,
+    ) -> Result<&Self> {
         let mut call = CallFunctionOnParams::builder()
             .function_declaration(
                 "(html) => {
@@ -1193,7 +2657,8 @@ impl Page {
         self.evaluate_function(call).await?;
         // relying that document.open() will reset frame lifecycle with "init"
         // lifecycle event. @see https://crrev.com/608658
-        self.wait_for_navigation().await
+        self.wait_for_navigation_with(options).await?;
+        Ok(self)
     }
 
     /// Returns the HTML content of the page
@@ -1265,7 +2730,166 @@ pub(crate) fn validate_cookie_url(url: &str) -> Result<()> {
     }
 }
 
+/// Replaces the binding `name` with a function that passes its arguments to
+/// the binding and returns a promise for the result of the call.
+const RUST_BINDING_SOURCE: &str = r#"(name) => {
+    const binding = globalThis[name];
+    if (typeof binding !== "function" || binding.callbacks) {
+        return;
+    }
+    const callbacks = new Map();
+    let lastSeq = 0;
+    const exposed = (...args) => {
+        const seq = ++lastSeq;
+        const promise = new Promise((resolve, reject) => callbacks.set(seq, { resolve, reject }));
+        binding(JSON.stringify({ seq, args }));
+        return promise;
+    };
+    exposed.callbacks = callbacks;
+    globalThis[name] = exposed;
+}"#;
+
+/// Settles the promise of a call of a binding installed by
+/// `RUST_BINDING_SOURCE`.
+const RUST_BINDING_DELIVER: &str = r#"(name, seq, result, error) => {
+    const callbacks = globalThis[name] && globalThis[name].callbacks;
+    const callback = callbacks && callbacks.get(seq);
+    if (!callback) {
+        return;
+    }
+    callbacks.delete(seq);
+    if (error !== null) {
+        callback.reject(new Error(error));
+    } else {
+        callback.resolve(result);
+    }
+}"#;
+
+/// Waits until the function returned by `predicate` is truthy, which is
+/// checked at every animation frame, on every mutation of the document or in
+/// an interval.
+const WAIT_FOR_FUNCTION: &str = r#"async (predicate, polling, interval, timeout) => {
+    const check = async () => {
+        const value = predicate();
+        return await (typeof value === 'function' ? value() : value);
+    };
+    const initial = await check();
+    if (initial) {
+        return initial;
+    }
+    return await new Promise((resolve, reject) => {
+        let done = false;
+        let cleanup = () => {};
+        const finish = (value) => {
+            done = true;
+            cleanup();
+            resolve(value);
+        };
+        const poll = async () => {
+            if (done) {
+                return;
+            }
+            try {
+                const value = await check();
+                if (value && !done) {
+                    finish(value);
+                }
+            } catch (err) {
+                done = true;
+                cleanup();
+                reject(err);
+            }
+        };
+        if (timeout > 0) {
+            setTimeout(() => {
+                if (!done) {
+                    done = true;
+                    cleanup();
+                    reject(new Error(`waiting for function failed: timeout ${timeout}ms exceeded`));
+                }
+            }, timeout);
+        }
+        if (polling === 'raf') {
+            const onFrame = async () => {
+                await poll();
+                if (!done) {
+                    requestAnimationFrame(onFrame);
+                }
+            };
+            requestAnimationFrame(onFrame);
+        } else if (polling === 'mutation') {
+            const observer = new MutationObserver(poll);
+            cleanup = () => observer.disconnect();
+            observer.observe(document, { childList: true, subtree: true, attributes: true, characterData: true });
+        } else {
+            const id = setInterval(poll, interval);
+            cleanup = () => clearInterval(id);
+        }
+    });
+}"#;
+
+/// Whether the error returned by chromium means the execution context was
+/// destroyed, because the page navigated
+fn is_context_destroyed(message: &str) -> bool {
+    message.contains("Execution context was destroyed")
+        || message.contains("Cannot find context with specified id")
+        || message.contains("Inspected target navigated or closed")
+}
+
+/// The bytes of a response body, which chromium returns base64 encoded if it
+/// isn't text
+fn decode_response_body(res: GetResponseBodyReturns) -> Result<Vec<u8>> {
+    if res.base64_encoded {
+        Ok(utils::base64::decode(&res.body)?)
+    } else {
+        Ok(res.body.into_bytes())
+    }
+}
+
+pub use crate::handler::frame::WaitUntil;
+pub use crate::handler::network::{BlockedRequests, NetworkConditions, ResourceFilter};
+pub use crate::handler::websocket::{
+    EventSourceMessage, FrameDirection, WebSocketClose, WebSocketConnection, WebSocketFrame,
+    WebSocketPayload,
+};
+
+/// How often [`Page::wait_for_function`] checks its function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Polling {
+    /// Check at every animation frame
+    #[default]
+    Raf,
+    /// Check every time the document is mutated
+    Mutation,
+    /// Check in a fixed interval
+    Interval(Duration),
+}
+
+/// Options for [`Page::goto_with`]
+#[derive(Debug, Clone, Default)]
+pub struct GotoOptions {
+    /// When the navigation is considered complete
+    pub wait_until: WaitUntil,
+    /// The value of the `Referer` header of the navigation request
+    pub referer: Option<String>,
+    /// Overrides the default navigation timeout
+    pub timeout: Option<Duration>,
+}
+
+/// Options for [`Page::reload_with`], [`Page::set_content_with`] and
+/// [`Page::wait_for_navigation_with`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NavigationOptions {
+    /// When the navigation is considered complete
+    pub wait_until: WaitUntil,
+    /// Overrides the default navigation timeout
+    pub timeout: Option<Duration>,
+}
+
 /// Page screenshot parameters with extra options.
+///
+/// Prefer [`ScreenshotParams::builder`] over struct literals, as options are
+/// added over time.
 #[derive(Debug, Default)]
 pub struct ScreenshotParams {
     /// Chrome DevTools Protocol screenshot options.
@@ -1274,6 +2898,17 @@ pub struct ScreenshotParams {
     pub full_page: Option<bool>,
     /// Make the background transparent (png only).
     pub omit_background: Option<bool>,
+    /// Selectors of elements that are covered by boxes of the `mask_color`.
+    pub mask: Vec<String>,
+    /// CSS color of the mask boxes, defaults to `#FF00FF`.
+    pub mask_color: Option<String>,
+    /// Pause CSS animations and disable transitions while capturing.
+    pub pause_animations: Option<bool>,
+    /// Hide the text caret while capturing.
+    pub hide_caret: Option<bool>,
+    /// Capture full page screenshots in tiles that are stitched together
+    /// (png only).
+    pub tiled: Option<bool>,
 }
 
 impl ScreenshotParams {
@@ -1281,6 +2916,18 @@ impl ScreenshotParams {
         Default::default()
     }
 
+    pub(crate) fn pause_animations(&self) -> bool {
+        self.pause_animations.unwrap_or(false)
+    }
+
+    pub(crate) fn hide_caret(&self) -> bool {
+        self.hide_caret.unwrap_or(false)
+    }
+
+    pub(crate) fn tiled(&self) -> bool {
+        self.tiled.unwrap_or(false)
+    }
+
     pub(crate) fn full_page(&self) -> bool {
         self.full_page.unwrap_or(false)
     }
@@ -1301,6 +2948,11 @@ pub struct ScreenshotParamsBuilder {
     cdp_params: CaptureScreenshotParams,
     full_page: Option<bool>,
     omit_background: Option<bool>,
+    mask: Vec<String>,
+    mask_color: Option<String>,
+    pause_animations: Option<bool>,
+    hide_caret: Option<bool>,
+    tiled: Option<bool>,
 }
 
 impl ScreenshotParamsBuilder {
@@ -1346,11 +2998,48 @@ impl ScreenshotParamsBuilder {
         self
     }
 
+    /// Cover all elements matching the selector with a box of the mask color
+    pub fn mask(mut self, selector: impl Into<String>) -> Self {
+        self.mask.push(selector.into());
+        self
+    }
+
+    /// CSS color of the mask boxes (defaults to `#FF00FF`)
+    pub fn mask_color(mut self, color: impl Into<String>) -> Self {
+        self.mask_color = Some(color.into());
+        self
+    }
+
+    /// Pause CSS animations and disable transitions while capturing (defaults
+    /// to false)
+    pub fn pause_animations(mut self, pause_animations: impl Into<bool>) -> Self {
+        self.pause_animations = Some(pause_animations.into());
+        self
+    }
+
+    /// Hide the text caret while capturing (defaults to false)
+    pub fn hide_caret(mut self, hide_caret: impl Into<bool>) -> Self {
+        self.hide_caret = Some(hide_caret.into());
+        self
+    }
+
+    /// Capture full page screenshots in tiles that are stitched together,
+    /// for pages that exceed the maximum texture size (png only)
+    pub fn tiled(mut self, tiled: impl Into<bool>) -> Self {
+        self.tiled = Some(tiled.into());
+        self
+    }
+
     pub fn build(self) -> ScreenshotParams {
         ScreenshotParams {
             cdp_params: self.cdp_params,
             full_page: self.full_page,
             omit_background: self.omit_background,
+            mask: self.mask,
+            mask_color: self.mask_color,
+            pause_animations: self.pause_animations,
+            hide_caret: self.hide_caret,
+            tiled: self.tiled,
         }
     }
 }
@@ -0,0 +1,154 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// A pattern urls are matched against, like in `Page::wait_for_url`.
+///
+/// Strings are treated as glob patterns: `*` matches any sequence of
+/// characters except `/`, `**` matches any sequence of characters including
+/// `/` and `\` escapes the following character. A pattern without wildcards
+/// only matches the exact url.
+#[derive(Clone)]
+pub enum UrlPattern {
+    /// A glob pattern
+    Glob(String),
+    /// An arbitrary predicate
+    Predicate(Arc<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl UrlPattern {
+    /// Creates a new pattern that matches all urls for which `predicate`
+    /// returns `true`
+    pub fn predicate(predicate: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        UrlPattern::Predicate(Arc::new(predicate))
+    }
+
+    /// Whether the `url` matches this pattern
+    pub fn matches(&self, url: &str) -> bool {
+        match self {
+            UrlPattern::Glob(glob) => glob_match(glob, url),
+            UrlPattern::Predicate(predicate) => predicate(url),
+        }
+    }
+}
+
+impl fmt::Debug for UrlPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UrlPattern::Glob(glob) => f.debug_tuple("Glob").field(glob).finish(),
+            UrlPattern::Predicate(_) => f.debug_tuple("Predicate").finish(),
+        }
+    }
+}
+
+impl From<&str> for UrlPattern {
+    fn from(glob: &str) -> Self {
+        UrlPattern::Glob(glob.to_string())
+    }
+}
+
+impl From<String> for UrlPattern {
+    fn from(glob: String) -> Self {
+        UrlPattern::Glob(glob)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GlobToken {
+    Char(char),
+    /// `*`
+    Star,
+    /// `**`
+    DoubleStar,
+}
+
+fn glob_tokens(glob: &str) -> Vec<GlobToken> {
+    let mut tokens = Vec::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    tokens.push(GlobToken::Char(c));
+                }
+            }
+            '*' => {
+                if chars.peek() == Some(&'*') {
+                    chars.next();
+                    while chars.peek() == Some(&'*') {
+                        chars.next();
+                    }
+                    tokens.push(GlobToken::DoubleStar);
+                } else {
+                    tokens.push(GlobToken::Star);
+                }
+            }
+            c => tokens.push(GlobToken::Char(c)),
+        }
+    }
+    tokens
+}
+
+/// Whether the entire `text` matches the `glob` pattern
+pub fn glob_match(glob: &str, text: &str) -> bool {
+    let text = text.chars().collect::<Vec<_>>();
+    // `matched[i]` is whether the tokens so far match the first `i` characters
+    let mut matched = vec![false; text.len() + 1];
+    matched[0] = true;
+    for token in glob_tokens(glob) {
+        let mut next = vec![false; text.len() + 1];
+        for i in 0..=text.len() {
+            next[i] = match token {
+                GlobToken::Char(c) => i > 0 && matched[i - 1] && text[i - 1] == c,
+                GlobToken::Star => matched[i] || (i > 0 && next[i - 1] && text[i - 1] != '/'),
+                GlobToken::DoubleStar => matched[i] || (i > 0 && next[i - 1]),
+            };
+        }
+        matched = next;
+    }
+    matched[text.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_exact() {
+        assert!(glob_match("https://example.com/", "https://example.com/"));
+        assert!(!glob_match(
+            "https://example.com/",
+            "https://example.com/index.html"
+        ));
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob_match("**/api/save", "https://example.com/api/save"));
+        assert!(glob_match("**/api/*", "https://example.com/api/save"));
+        assert!(!glob_match("**/api/*", "https://example.com/api/v1/save"));
+        assert!(glob_match("**/api/**", "https://example.com/api/v1/save"));
+        assert!(glob_match(
+            "https://*.example.com/**",
+            "https://www.example.com/a/b"
+        ));
+        assert!(!glob_match(
+            "https://*.example.com/**",
+            "https://example.com/a/b"
+        ));
+        assert!(glob_match("**/*.png", "https://example.com/img/logo.png"));
+        assert!(glob_match("**", ""));
+    }
+
+    #[test]
+    fn glob_escapes() {
+        assert!(glob_match("**/a\\*b", "https://example.com/a*b"));
+        assert!(!glob_match("**/a\\*b", "https://example.com/axb"));
+    }
+
+    #[test]
+    fn url_pattern_predicate() {
+        let pattern = UrlPattern::predicate(|url| url.contains("dashboard"));
+        assert!(pattern.matches("https://example.com/dashboard"));
+        assert!(!pattern.matches("https://example.com/login"));
+    }
+}
@@ -0,0 +1,397 @@
+//! Typed options for printing pages as pdf via `Page::pdf`.
+//!
+//! The protocol expects all sizes in inches, [`PdfOptions`] converts paper
+//! formats and lengths in other units to it.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chromiumoxide_cdp::cdp::browser_protocol::page::PrintToPdfParams;
+
+use crate::error::CdpError;
+
+/// The template used for the header or footer if only the other one is set,
+/// since chromium would print its default template instead.
+const EMPTY_TEMPLATE: &str = "<span></span>";
+
+/// A paper format with a well known size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaperFormat {
+    Letter,
+    Legal,
+    Tabloid,
+    Ledger,
+    A0,
+    A1,
+    A2,
+    A3,
+    A4,
+    A5,
+    A6,
+}
+
+impl PaperFormat {
+    /// The width and height of the format in portrait orientation
+    pub fn size(&self) -> (Length, Length) {
+        let (width, height) = match self {
+            PaperFormat::Letter => (8.5, 11.),
+            PaperFormat::Legal => (8.5, 14.),
+            PaperFormat::Tabloid => (11., 17.),
+            PaperFormat::Ledger => (17., 11.),
+            PaperFormat::A0 => (33.1, 46.8),
+            PaperFormat::A1 => (23.4, 33.1),
+            PaperFormat::A2 => (16.54, 23.4),
+            PaperFormat::A3 => (11.7, 16.54),
+            PaperFormat::A4 => (8.27, 11.7),
+            PaperFormat::A5 => (5.83, 8.27),
+            PaperFormat::A6 => (4.13, 5.83),
+        };
+        (Length::In(width), Length::In(height))
+    }
+}
+
+impl FromStr for PaperFormat {
+    type Err = CdpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format = match s.to_ascii_lowercase().as_str() {
+            "letter" => PaperFormat::Letter,
+            "legal" => PaperFormat::Legal,
+            "tabloid" => PaperFormat::Tabloid,
+            "ledger" => PaperFormat::Ledger,
+            "a0" => PaperFormat::A0,
+            "a1" => PaperFormat::A1,
+            "a2" => PaperFormat::A2,
+            "a3" => PaperFormat::A3,
+            "a4" => PaperFormat::A4,
+            "a5" => PaperFormat::A5,
+            "a6" => PaperFormat::A6,
+            _ => return Err(CdpError::msg(format!("Unknown paper format `{s}`"))),
+        };
+        Ok(format)
+    }
+}
+
+/// A length of the paper or its margins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    /// Inches
+    In(f64),
+    /// Centimeters
+    Cm(f64),
+    /// Millimeters
+    Mm(f64),
+    /// CSS pixels, 96 per inch
+    Px(f64),
+}
+
+impl Length {
+    /// The length in inches, as expected by the protocol
+    pub fn to_inches(&self) -> f64 {
+        match *self {
+            Length::In(inches) => inches,
+            Length::Cm(cm) => cm / 2.54,
+            Length::Mm(mm) => mm / 25.4,
+            Length::Px(px) => px / 96.,
+        }
+    }
+}
+
+impl Default for Length {
+    fn default() -> Self {
+        Length::In(0.)
+    }
+}
+
+/// Parses lengths like `1.5cm`, `10mm`, `0.5in` or `20px`, plain numbers are
+/// CSS pixels.
+impl FromStr for Length {
+    type Err = CdpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let value = value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| CdpError::msg(format!("Invalid length `{s}`")))?;
+        match unit.to_ascii_lowercase().as_str() {
+            "in" => Ok(Length::In(value)),
+            "cm" => Ok(Length::Cm(value)),
+            "mm" => Ok(Length::Mm(value)),
+            "px" | "" => Ok(Length::Px(value)),
+            _ => Err(CdpError::msg(format!("Unknown unit of length `{s}`"))),
+        }
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Length::In(value) => write!(f, "{value}in"),
+            Length::Cm(value) => write!(f, "{value}cm"),
+            Length::Mm(value) => write!(f, "{value}mm"),
+            Length::Px(value) => write!(f, "{value}px"),
+        }
+    }
+}
+
+/// The margins of the printed pages
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Margins {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+}
+
+impl Margins {
+    /// The same margin on all sides
+    pub fn uniform(margin: Length) -> Self {
+        Self {
+            top: margin,
+            right: margin,
+            bottom: margin,
+            left: margin,
+        }
+    }
+
+    /// The `vertical` margin at the top and bottom and the `horizontal`
+    /// margin on the left and right
+    pub fn symmetric(vertical: Length, horizontal: Length) -> Self {
+        Self {
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+            left: horizontal,
+        }
+    }
+}
+
+/// Options for printing a page as pdf.
+///
+/// Unset options fall back to the defaults of chromium, which prints on
+/// `Letter` paper with margins of 1cm.
+///
+/// # Example
+///
+/// ```no_run
+/// # use chromiumoxide::page::Page;
+/// # use chromiumoxide::error::Result;
+/// # use chromiumoxide::pdf::{Length, Margins, PaperFormat, PdfOptions};
+/// # async fn demo(page: Page) -> Result<()> {
+///     let options = PdfOptions::builder()
+///         .format(PaperFormat::A4)
+///         .margins(Margins::uniform(Length::Mm(15.)))
+///         .footer_template(
+///             r#"<div style="font-size: 8px; margin: auto">
+///                 <span class="pageNumber"></span> / <span class="totalPages"></span>
+///             </div>"#,
+///         )
+///         .build();
+///     let pdf = page.pdf(options).await?;
+///     # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfOptions {
+    /// The paper format, overridden by `width` and `height`
+    pub format: Option<PaperFormat>,
+    pub width: Option<Length>,
+    pub height: Option<Length>,
+    pub margins: Option<Margins>,
+    pub landscape: Option<bool>,
+    pub print_background: Option<bool>,
+    /// The scale of the rendering, between 0.1 and 2
+    pub scale: Option<f64>,
+    /// One based ranges of the pages to print, like `1-5, 8, 11-13`
+    pub page_ranges: Option<String>,
+    /// HTML template for the header of each page.
+    ///
+    /// Elements with the classes `date`, `title`, `url`, `pageNumber` and
+    /// `totalPages` have the respective values injected.
+    pub header_template: Option<String>,
+    /// HTML template for the footer of each page, like `header_template`
+    pub footer_template: Option<String>,
+    /// Whether the page size defined by css via `@page` takes precedence
+    pub prefer_css_page_size: Option<bool>,
+    /// Whether to generate a tagged (accessible) pdf
+    pub tagged: Option<bool>,
+    /// Whether to embed the document outline
+    pub outline: Option<bool>,
+}
+
+impl PdfOptions {
+    pub fn builder() -> PdfOptionsBuilder {
+        Default::default()
+    }
+}
+
+impl From<PdfOptions> for PrintToPdfParams {
+    fn from(opts: PdfOptions) -> Self {
+        let (width, height) = match opts.format.map(|format| format.size()) {
+            Some((width, height)) => (opts.width.or(Some(width)), opts.height.or(Some(height))),
+            None => (opts.width, opts.height),
+        };
+        let margins = opts.margins;
+        let display_header_footer =
+            opts.header_template.is_some() || opts.footer_template.is_some();
+        let (header_template, footer_template) = if display_header_footer {
+            (
+                opts.header_template
+                    .or_else(|| Some(EMPTY_TEMPLATE.to_string())),
+                opts.footer_template
+                    .or_else(|| Some(EMPTY_TEMPLATE.to_string())),
+            )
+        } else {
+            (None, None)
+        };
+        PrintToPdfParams {
+            landscape: opts.landscape,
+            display_header_footer: display_header_footer.then_some(true),
+            print_background: opts.print_background,
+            scale: opts.scale,
+            paper_width: width.map(|width| width.to_inches()),
+            paper_height: height.map(|height| height.to_inches()),
+            margin_top: margins.map(|margins| margins.top.to_inches()),
+            margin_bottom: margins.map(|margins| margins.bottom.to_inches()),
+            margin_left: margins.map(|margins| margins.left.to_inches()),
+            margin_right: margins.map(|margins| margins.right.to_inches()),
+            page_ranges: opts.page_ranges,
+            header_template,
+            footer_template,
+            prefer_css_page_size: opts.prefer_css_page_size,
+            generate_tagged_pdf: opts.tagged,
+            generate_document_outline: opts.outline,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PdfOptionsBuilder {
+    opts: PdfOptions,
+}
+
+impl PdfOptionsBuilder {
+    /// The paper format, overridden by `paper_size`
+    pub fn format(mut self, format: PaperFormat) -> Self {
+        self.opts.format = Some(format);
+        self
+    }
+
+    /// A custom paper size
+    pub fn paper_size(mut self, width: Length, height: Length) -> Self {
+        self.opts.width = Some(width);
+        self.opts.height = Some(height);
+        self
+    }
+
+    pub fn margins(mut self, margins: Margins) -> Self {
+        self.opts.margins = Some(margins);
+        self
+    }
+
+    pub fn landscape(mut self, landscape: bool) -> Self {
+        self.opts.landscape = Some(landscape);
+        self
+    }
+
+    pub fn print_background(mut self, print_background: bool) -> Self {
+        self.opts.print_background = Some(print_background);
+        self
+    }
+
+    /// The scale of the rendering, between 0.1 and 2
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.opts.scale = Some(scale);
+        self
+    }
+
+    /// One based ranges of the pages to print, like `1-5, 8, 11-13`
+    pub fn page_ranges(mut self, page_ranges: impl Into<String>) -> Self {
+        self.opts.page_ranges = Some(page_ranges.into());
+        self
+    }
+
+    /// HTML template for the header of each page, see
+    /// [`PdfOptions::header_template`]
+    pub fn header_template(mut self, template: impl Into<String>) -> Self {
+        self.opts.header_template = Some(template.into());
+        self
+    }
+
+    /// HTML template for the footer of each page, see
+    /// [`PdfOptions::header_template`]
+    pub fn footer_template(mut self, template: impl Into<String>) -> Self {
+        self.opts.footer_template = Some(template.into());
+        self
+    }
+
+    pub fn prefer_css_page_size(mut self, prefer_css_page_size: bool) -> Self {
+        self.opts.prefer_css_page_size = Some(prefer_css_page_size);
+        self
+    }
+
+    /// Whether to generate a tagged (accessible) pdf
+    pub fn tagged(mut self, tagged: bool) -> Self {
+        self.opts.tagged = Some(tagged);
+        self
+    }
+
+    /// Whether to embed the document outline
+    pub fn outline(mut self, outline: bool) -> Self {
+        self.opts.outline = Some(outline);
+        self
+    }
+
+    pub fn build(self) -> PdfOptions {
+        self.opts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lengths() {
+        assert_eq!("1.5cm".parse::<Length>().unwrap(), Length::Cm(1.5));
+        assert_eq!(" 10 mm".parse::<Length>().unwrap(), Length::Mm(10.));
+        assert_eq!("0.5IN".parse::<Length>().unwrap(), Length::In(0.5));
+        assert_eq!("20".parse::<Length>().unwrap(), Length::Px(20.));
+        assert!("1pt".parse::<Length>().is_err());
+        assert!("cm".parse::<Length>().is_err());
+        assert_eq!(Length::Px(48.).to_inches(), 0.5);
+        assert_eq!(Length::Mm(25.4).to_inches(), 1.);
+    }
+
+    #[test]
+    fn converts_to_params() {
+        let params = PrintToPdfParams::from(
+            PdfOptions::builder()
+                .format(PaperFormat::A4)
+                .margins(Margins::symmetric(Length::Cm(2.54), Length::Px(96.)))
+                .footer_template("<span class=pageNumber></span>")
+                .build(),
+        );
+        assert_eq!(params.paper_width, Some(8.27));
+        assert_eq!(params.paper_height, Some(11.7));
+        assert_eq!(params.margin_top, Some(1.));
+        assert_eq!(params.margin_left, Some(1.));
+        assert_eq!(params.display_header_footer, Some(true));
+        assert_eq!(params.header_template.as_deref(), Some(EMPTY_TEMPLATE));
+
+        let params = PrintToPdfParams::from(
+            PdfOptions::builder()
+                .format(PaperFormat::A4)
+                .paper_size(Length::In(4.), Length::In(6.))
+                .build(),
+        );
+        assert_eq!(params.paper_width, Some(4.));
+        assert_eq!(params.paper_height, Some(6.));
+        assert_eq!(params.display_header_footer, None);
+        assert_eq!(params.margin_top, None);
+    }
+}
@@ -0,0 +1,496 @@
+//! Typed performance metrics of a page and the collection of its
+//! [Core Web Vitals](https://web.dev/articles/vitals).
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+use futures::channel::oneshot::channel as oneshot_channel;
+use futures::{SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use chromiumoxide_cdp::cdp::browser_protocol::page::{
+    AddScriptToEvaluateOnNewDocumentParams, RemoveScriptToEvaluateOnNewDocumentParams,
+    ScriptIdentifier,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::performance::Metric;
+use chromiumoxide_cdp::cdp::js_protocol::profiler::Profile;
+use chromiumoxide_cdp::cdp::js_protocol::runtime::{AddBindingParams, RemoveBindingParams};
+
+use crate::error::{CdpError, Result};
+use crate::handler::target::{BindingCall, ExposeBinding, RemoveBinding, TargetMessage};
+use crate::handler::PageInner;
+use crate::utils;
+
+/// The binding the vitals of a top-level document are reported with once it
+/// is hidden or unloaded
+const WEB_VITALS_BINDING: &str = "__chromiumoxideReportWebVitals";
+
+/// Installs performance observers in a document that record its web vitals
+/// in `window.__chromiumoxideWebVitals`
+const WEB_VITALS_SOURCE: &str = r#"(() => {
+    if (window.__chromiumoxideWebVitals) {
+        return;
+    }
+    const vitals = { lcp: null, cls: 0, inp: null, fid: null, ttfb: null, fcp: null };
+    window.__chromiumoxideWebVitals = vitals;
+    // the final vitals are reported once per navigation, when the document is
+    // hidden or unloaded, whichever comes first
+    let reported = false;
+    const report = () => {
+        const binding = globalThis.__chromiumoxideReportWebVitals;
+        if (reported || window !== window.top || typeof binding !== 'function') {
+            return;
+        }
+        reported = true;
+        binding(JSON.stringify({ seq: 0, args: [{ url: location.href, ...vitals }] }));
+    };
+    addEventListener('visibilitychange', () => {
+        if (document.visibilityState === 'hidden') {
+            report();
+        }
+    }, true);
+    addEventListener('pagehide', report, true);
+    const observe = (type, callback, options = {}) => {
+        try {
+            new PerformanceObserver(list => list.getEntries().forEach(callback))
+                .observe({ type, buffered: true, ...options });
+        } catch (e) {
+            // the entry type is not supported
+        }
+    };
+    observe('largest-contentful-paint', entry => {
+        vitals.lcp = entry.startTime;
+    });
+    // CLS is the sum of the largest session window of layout shifts
+    let session = [];
+    let sessionValue = 0;
+    observe('layout-shift', entry => {
+        if (entry.hadRecentInput) {
+            return;
+        }
+        const first = session[0];
+        const last = session[session.length - 1];
+        if (last && entry.startTime - last.startTime < 1000 && entry.startTime - first.startTime < 5000) {
+            session.push(entry);
+            sessionValue += entry.value;
+        } else {
+            session = [entry];
+            sessionValue = entry.value;
+        }
+        vitals.cls = Math.max(vitals.cls, sessionValue);
+    });
+    observe('first-input', entry => {
+        vitals.fid = entry.processingStart - entry.startTime;
+    });
+    // INP is the 98th percentile of the latencies of all interactions
+    const interactions = new Map();
+    observe('event', entry => {
+        if (!entry.interactionId) {
+            return;
+        }
+        const latency = Math.max(interactions.get(entry.interactionId) || 0, entry.duration);
+        interactions.set(entry.interactionId, latency);
+        const latencies = [...interactions.values()].sort((a, b) => b - a);
+        vitals.inp = latencies[Math.min(latencies.length - 1, Math.floor(latencies.length / 50))];
+    }, { durationThreshold: 16 });
+    observe('paint', entry => {
+        if (entry.name === 'first-contentful-paint') {
+            vitals.fcp = entry.startTime;
+        }
+    });
+    observe('navigation', entry => {
+        vitals.ttfb = entry.responseStart;
+    });
+})()"#;
+
+const COLLECT_WEB_VITALS: &str = r#"(() => {
+    const vitals = window.__chromiumoxideWebVitals;
+    return vitals ? { url: location.href, ...vitals } : null;
+})()"#;
+
+/// The run-time metrics of a page as reported by `Performance.getMetrics`.
+///
+/// Durations are in seconds, sizes in bytes. Metrics that are not reported by
+/// the browser are zero.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PerformanceMetrics {
+    /// The time the metrics were taken at, in seconds since an arbitrary point
+    pub timestamp: f64,
+    pub documents: u64,
+    pub frames: u64,
+    pub js_event_listeners: u64,
+    pub nodes: u64,
+    pub layout_objects: u64,
+    /// Number of full or partial page layouts
+    pub layout_count: u64,
+    /// Number of style recalculations
+    pub recalc_style_count: u64,
+    pub layout_duration: f64,
+    pub recalc_style_duration: f64,
+    pub script_duration: f64,
+    pub task_duration: f64,
+    pub js_heap_used_size: u64,
+    pub js_heap_total_size: u64,
+    /// All other metrics, keyed by their protocol name
+    pub other: HashMap<String, f64>,
+}
+
+impl FromIterator<Metric> for PerformanceMetrics {
+    fn from_iter<T: IntoIterator<Item = Metric>>(iter: T) -> Self {
+        let mut metrics = PerformanceMetrics::default();
+        for Metric { name, value } in iter {
+            let count = value as u64;
+            match name.as_str() {
+                "Timestamp" => metrics.timestamp = value,
+                "Documents" => metrics.documents = count,
+                "Frames" => metrics.frames = count,
+                "JSEventListeners" => metrics.js_event_listeners = count,
+                "Nodes" => metrics.nodes = count,
+                "LayoutObjects" => metrics.layout_objects = count,
+                "LayoutCount" => metrics.layout_count = count,
+                "RecalcStyleCount" => metrics.recalc_style_count = count,
+                "LayoutDuration" => metrics.layout_duration = value,
+                "RecalcStyleDuration" => metrics.recalc_style_duration = value,
+                "ScriptDuration" => metrics.script_duration = value,
+                "TaskDuration" => metrics.task_duration = value,
+                "JSHeapUsedSize" => metrics.js_heap_used_size = count,
+                "JSHeapTotalSize" => metrics.js_heap_total_size = count,
+                _ => {
+                    metrics.other.insert(name, value);
+                }
+            }
+        }
+        metrics
+    }
+}
+
+impl From<Vec<Metric>> for PerformanceMetrics {
+    fn from(metrics: Vec<Metric>) -> Self {
+        metrics.into_iter().collect()
+    }
+}
+
+/// The [Core Web Vitals](https://web.dev/articles/vitals) of a navigation.
+///
+/// Times are in milliseconds since the start of the navigation, durations in
+/// milliseconds. Vitals that were not observed (yet) are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebVitals {
+    /// The url of the document the vitals were collected for
+    pub url: String,
+    /// Largest Contentful Paint
+    pub lcp: Option<f64>,
+    /// Cumulative Layout Shift, unitless
+    pub cls: f64,
+    /// Interaction to Next Paint
+    pub inp: Option<f64>,
+    /// First Input Delay
+    pub fid: Option<f64>,
+    /// Time To First Byte
+    pub ttfb: Option<f64>,
+    /// First Contentful Paint
+    pub fcp: Option<f64>,
+}
+
+/// Collects the web vitals of the documents of a page, created with
+/// `Page::web_vitals`.
+///
+/// The observers are installed in every document once it is created, so each
+/// navigation starts from fresh vitals. The vitals of the current document
+/// are read with [`WebVitalsCollector::current`].
+///
+/// The collector is also a stream of the final vitals of every navigation of
+/// the page's main frame, which are reported when its document is hidden or
+/// unloaded, for instance by navigating away. Only the most recently created
+/// collector of a page receives these reports.
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct WebVitalsCollector {
+    inner: Arc<PageInner>,
+    script: ScriptIdentifier,
+    reports: UnboundedReceiver<BindingCall>,
+}
+
+impl WebVitalsCollector {
+    pub(crate) async fn new(inner: Arc<PageInner>) -> Result<Self> {
+        let (tx, reports) = unbounded();
+        inner
+            .sender()
+            .clone()
+            .send(TargetMessage::ExposeBinding(ExposeBinding {
+                name: WEB_VITALS_BINDING.to_string(),
+                source: WEB_VITALS_SOURCE.to_string(),
+                tx,
+            }))
+            .await?;
+        inner
+            .execute(AddBindingParams::new(WEB_VITALS_BINDING))
+            .await?;
+        let script = inner
+            .execute(AddScriptToEvaluateOnNewDocumentParams::new(
+                WEB_VITALS_SOURCE,
+            ))
+            .await?
+            .result
+            .identifier;
+        // the observers are buffered, so they also pick up the entries of the
+        // document that is already loaded
+        inner.evaluate_expression(WEB_VITALS_SOURCE).await?;
+        Ok(Self {
+            inner,
+            script,
+            reports,
+        })
+    }
+
+    /// The web vitals of the current document so far.
+    ///
+    /// LCP and CLS may still change while the page is in use, INP and FID
+    /// require user input.
+    pub async fn current(&self) -> Result<WebVitals> {
+        if let Some(vitals) = self.read().await? {
+            return Ok(vitals);
+        }
+        // the document was created by a navigation that started before the
+        // observers were installed
+        self.inner.evaluate_expression(WEB_VITALS_SOURCE).await?;
+        self.read().await?.ok_or(CdpError::NotFound)
+    }
+
+    async fn read(&self) -> Result<Option<WebVitals>> {
+        let res = self.inner.evaluate_expression(COLLECT_WEB_VITALS).await?;
+        match res.value() {
+            Some(vitals) if !vitals.is_null() => Ok(Some(serde_json::from_value(vitals.clone())?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Stops installing the observers in new documents and reporting the
+    /// final vitals of navigations
+    pub async fn stop(self) -> Result<()> {
+        let Self {
+            inner,
+            script,
+            reports,
+        } = self;
+        inner
+            .execute(RemoveScriptToEvaluateOnNewDocumentParams::new(script))
+            .await?;
+        // the binding is kept if a newer collector of the page reports through
+        // it
+        drop(reports);
+        let (tx, rx) = oneshot_channel();
+        inner
+            .sender()
+            .clone()
+            .send(TargetMessage::RemoveBinding(RemoveBinding {
+                name: WEB_VITALS_BINDING.to_string(),
+                tx,
+            }))
+            .await?;
+        if rx.await? {
+            inner
+                .execute(RemoveBindingParams::new(WEB_VITALS_BINDING))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl Stream for WebVitalsCollector {
+    type Item = WebVitals;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match futures::ready!(this.reports.poll_next_unpin(cx)) {
+                Some(call) => {
+                    // the binding is reachable by the page, so its calls may
+                    // carry anything
+                    if let Some(vitals) = web_vitals_of_report(call.args) {
+                        return Poll::Ready(Some(vitals));
+                    }
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// The vitals reported as the single argument of a call of the binding
+fn web_vitals_of_report(args: serde_json::Value) -> Option<WebVitals> {
+    match args {
+        serde_json::Value::Array(mut args) if args.len() == 1 => {
+            serde_json::from_value(args.remove(0)).ok()
+        }
+        _ => None,
+    }
+}
+
+/// A CPU profile recorded with `Page::start_cpu_profile` or `Page::profile`.
+///
+/// Serialized as JSON, this is the `.cpuprofile` format that can be loaded in
+/// the performance panel of DevTools.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CpuProfile {
+    pub profile: Profile,
+}
+
+impl CpuProfile {
+    /// The time between the start and the end of the profile
+    pub fn duration(&self) -> Duration {
+        // the timestamps are in microseconds
+        Duration::from_micros((self.profile.end_time - self.profile.start_time).max(0.) as u64)
+    }
+
+    /// The profile in the `.cpuprofile` format
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Writes the profile as `.cpuprofile` file to the `output` path
+    pub async fn save(&self, output: impl AsRef<Path>) -> Result<()> {
+        utils::write(output.as_ref(), self.to_json()?).await?;
+        Ok(())
+    }
+}
+
+impl From<Profile> for CpuProfile {
+    fn from(profile: Profile) -> Self {
+        Self { profile }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chromiumoxide_cdp::cdp::browser_protocol::target::{SessionId, TargetId};
+    use chromiumoxide_types::{CallId, Response};
+    use futures::future::{select, Either};
+
+    use super::*;
+    use crate::handler::PageHandle;
+
+    /// Stops a collector and returns the commands that were sent, the
+    /// binding is still in use by another collector unless `removed`
+    async fn stop(removed: bool) -> Vec<String> {
+        let mut handle = PageHandle::new(TargetId::new("target"), SessionId::new("session"), None);
+        let (_tx, reports) = unbounded();
+        let collector = WebVitalsCollector {
+            inner: Arc::clone(handle.inner()),
+            script: ScriptIdentifier::new("1"),
+            reports,
+        };
+        let mut methods = Vec::new();
+        let server = async {
+            while let Some(msg) = handle.rx.next().await {
+                match msg {
+                    TargetMessage::Command(msg) => {
+                        methods.push(msg.method.to_string());
+                        let _ = msg.sender.send(Ok(Response {
+                            id: CallId::new(methods.len()),
+                            result: Some(serde_json::json!({})),
+                            error: None,
+                        }));
+                    }
+                    TargetMessage::RemoveBinding(req) => {
+                        assert_eq!(req.name, WEB_VITALS_BINDING);
+                        let _ = req.tx.send(removed);
+                    }
+                    _ => panic!("unexpected message"),
+                }
+            }
+        };
+        match select(Box::pin(collector.stop()), Box::pin(server)).await {
+            Either::Left((res, _)) => res.unwrap(),
+            Either::Right(_) => panic!("the page handle was dropped"),
+        }
+        methods
+    }
+
+    #[tokio::test]
+    async fn stop_removes_the_script_and_the_binding() {
+        assert_eq!(
+            stop(true).await,
+            [
+                "Page.removeScriptToEvaluateOnNewDocument",
+                "Runtime.removeBinding"
+            ]
+        );
+        assert_eq!(
+            stop(false).await,
+            ["Page.removeScriptToEvaluateOnNewDocument"]
+        );
+    }
+
+    #[test]
+    fn collects_metrics() {
+        let metrics: PerformanceMetrics = vec![
+            Metric::new("Timestamp", 12.5),
+            Metric::new("Nodes", 42.),
+            Metric::new("ScriptDuration", 0.25),
+            Metric::new("JSHeapUsedSize", 1024.),
+            Metric::new("FirstMeaningfulPaint", 3.),
+        ]
+        .into();
+        assert_eq!(metrics.timestamp, 12.5);
+        assert_eq!(metrics.nodes, 42);
+        assert_eq!(metrics.script_duration, 0.25);
+        assert_eq!(metrics.js_heap_used_size, 1024);
+        assert_eq!(metrics.documents, 0);
+        assert_eq!(metrics.other.get("FirstMeaningfulPaint"), Some(&3.));
+    }
+
+    #[test]
+    fn deserializes_web_vitals() {
+        let vitals: WebVitals = serde_json::from_value(serde_json::json!({
+            "url": "https://example.com/",
+            "lcp": 812.4,
+            "cls": 0.05,
+            "inp": null,
+            "fid": null,
+            "ttfb": 120.,
+            "fcp": 400.1
+        }))
+        .unwrap();
+        assert_eq!(vitals.lcp, Some(812.4));
+        assert_eq!(vitals.inp, None);
+        assert_eq!(vitals.cls, 0.05);
+    }
+
+    #[test]
+    fn parses_web_vitals_reports() {
+        let vitals = web_vitals_of_report(serde_json::json!([{
+            "url": "https://example.com/",
+            "lcp": 812.4,
+            "cls": 0.,
+            "inp": 24.,
+            "fid": 3.,
+            "ttfb": 120.,
+            "fcp": 400.1
+        }]))
+        .unwrap();
+        assert_eq!(vitals.url, "https://example.com/");
+        assert_eq!(vitals.inp, Some(24.));
+        assert!(web_vitals_of_report(serde_json::json!([])).is_none());
+        assert!(web_vitals_of_report(serde_json::json!([{ "lcp": "fast" }])).is_none());
+        assert!(web_vitals_of_report(serde_json::json!({ "url": "" })).is_none());
+    }
+
+    #[test]
+    fn cpu_profile_round_trip() {
+        let json = r#"{"nodes":[{"id":1,"callFrame":{"functionName":"(root)","scriptId":"0","url":"","lineNumber":-1,"columnNumber":-1},"hitCount":0,"children":[2]},{"id":2,"callFrame":{"functionName":"work","scriptId":"3","url":"https://example.com/app.js","lineNumber":10,"columnNumber":4},"hitCount":5}],"startTime":1000,"endTime":251000,"samples":[2,2],"timeDeltas":[100,120]}"#;
+        let profile: CpuProfile = serde_json::from_str(json).unwrap();
+        assert_eq!(profile.profile.nodes.len(), 2);
+        assert_eq!(profile.duration(), Duration::from_millis(250));
+        let reparsed: CpuProfile = serde_json::from_str(&profile.to_json().unwrap()).unwrap();
+        assert_eq!(reparsed, profile);
+    }
+}
@@ -0,0 +1,410 @@
+//! Typed DOM snapshots captured with `Page::dom_snapshot`.
+//!
+//! `DOMSnapshot.captureSnapshot` returns the nodes of all documents of a page,
+//! including iframes and flattened shadow DOM, as parallel arrays that
+//! reference a shared table of strings. [`DomSnapshot`] resolves them into one
+//! struct per node.
+
+use std::collections::{HashMap, HashSet};
+
+use chromiumoxide_cdp::cdp::browser_protocol::dom::BackendNodeId;
+use chromiumoxide_cdp::cdp::browser_protocol::dom_snapshot::{
+    CaptureSnapshotReturns, DocumentSnapshot, RareBooleanData, RareIntegerData, RareStringData,
+    Rectangle, StringIndex,
+};
+
+use crate::layout::BoundingBox;
+
+/// The documents of a page with their nodes and layout
+#[derive(Debug, Clone)]
+pub struct DomSnapshot {
+    /// The documents of the page, the main document first
+    pub documents: Vec<SnapshotDocument>,
+}
+
+impl DomSnapshot {
+    /// Resolves the snapshot, the `computed_styles` are the names of the
+    /// styles the snapshot was captured with
+    pub fn new(snapshot: CaptureSnapshotReturns, computed_styles: &[String]) -> Self {
+        let strings = Strings(&snapshot.strings);
+        Self {
+            documents: snapshot
+                .documents
+                .iter()
+                .map(|doc| SnapshotDocument::new(doc, strings, computed_styles))
+                .collect(),
+        }
+    }
+}
+
+/// A document of a [`DomSnapshot`]
+#[derive(Debug, Clone)]
+pub struct SnapshotDocument {
+    pub url: String,
+    pub title: String,
+    pub base_url: String,
+    pub frame_id: String,
+    pub content_width: Option<f64>,
+    pub content_height: Option<f64>,
+    pub scroll_offset_x: Option<f64>,
+    pub scroll_offset_y: Option<f64>,
+    /// The nodes of the document in document order, the root first
+    pub nodes: Vec<SnapshotNode>,
+}
+
+impl SnapshotDocument {
+    fn new(doc: &DocumentSnapshot, strings: Strings<'_>, computed_styles: &[String]) -> Self {
+        let tree = &doc.nodes;
+        let len = tree
+            .node_type
+            .as_ref()
+            .or(tree.parent_index.as_ref())
+            .map(Vec::len)
+            .unwrap_or_default();
+
+        let rare_strings = |data: &Option<RareStringData>| {
+            let mut values = vec![None; len];
+            if let Some(data) = data {
+                for (idx, value) in data.index.iter().zip(&data.value) {
+                    if let Some(slot) = values.get_mut(*idx as usize) {
+                        *slot = Some(strings.get(value));
+                    }
+                }
+            }
+            values
+        };
+        let rare_booleans = |data: &Option<RareBooleanData>| {
+            let mut values = vec![false; len];
+            if let Some(data) = data {
+                for idx in &data.index {
+                    if let Some(slot) = values.get_mut(*idx as usize) {
+                        *slot = true;
+                    }
+                }
+            }
+            values
+        };
+        let rare_integers = |data: &Option<RareIntegerData>| {
+            let mut values = vec![None; len];
+            if let Some(data) = data {
+                for (idx, value) in data.index.iter().zip(&data.value) {
+                    if let Some(slot) = values.get_mut(*idx as usize) {
+                        *slot = Some(*value);
+                    }
+                }
+            }
+            values
+        };
+
+        let mut shadow_root_types = rare_strings(&tree.shadow_root_type);
+        let mut text_values = rare_strings(&tree.text_value);
+        let mut input_values = rare_strings(&tree.input_value);
+        let mut pseudo_types = rare_strings(&tree.pseudo_type);
+        let mut current_source_urls = rare_strings(&tree.current_source_url);
+        let input_checked = rare_booleans(&tree.input_checked);
+        let option_selected = rare_booleans(&tree.option_selected);
+        let clickable = rare_booleans(&tree.is_clickable);
+        let content_documents = rare_integers(&tree.content_document_index);
+        let mut layouts = layouts(doc, strings, computed_styles, len);
+
+        let nodes = (0..len)
+            .map(|idx| SnapshotNode {
+                parent: tree
+                    .parent_index
+                    .as_ref()
+                    .and_then(|parents| parents.get(idx))
+                    .and_then(|&parent| usize::try_from(parent).ok()),
+                node_type: tree
+                    .node_type
+                    .as_ref()
+                    .and_then(|types| types.get(idx))
+                    .copied()
+                    .unwrap_or_default(),
+                name: strings.get_at(tree.node_name.as_deref(), idx),
+                value: strings.get_at(tree.node_value.as_deref(), idx),
+                backend_node_id: tree
+                    .backend_node_id
+                    .as_ref()
+                    .and_then(|ids| ids.get(idx))
+                    .copied(),
+                attributes: tree
+                    .attributes
+                    .as_ref()
+                    .and_then(|attributes| attributes.get(idx))
+                    .map(|attributes| {
+                        attributes
+                            .inner()
+                            .chunks_exact(2)
+                            .map(|pair| (strings.get(&pair[0]), strings.get(&pair[1])))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                shadow_root_type: shadow_root_types[idx].take(),
+                text_value: text_values[idx].take(),
+                input_value: input_values[idx].take(),
+                input_checked: input_checked[idx],
+                option_selected: option_selected[idx],
+                is_clickable: clickable[idx],
+                content_document: content_documents[idx].and_then(|doc| usize::try_from(doc).ok()),
+                pseudo_type: pseudo_types[idx].take(),
+                current_source_url: current_source_urls[idx].take(),
+                layout: layouts[idx].take(),
+            })
+            .collect();
+
+        Self {
+            url: strings.get(&doc.document_url),
+            title: strings.get(&doc.title),
+            base_url: strings.get(&doc.base_url),
+            frame_id: strings.get(&doc.frame_id),
+            content_width: doc.content_width,
+            content_height: doc.content_height,
+            scroll_offset_x: doc.scroll_offset_x,
+            scroll_offset_y: doc.scroll_offset_y,
+            nodes,
+        }
+    }
+}
+
+/// The layout of each of the `len` nodes of the document, `None` for nodes
+/// that are not rendered
+fn layouts(
+    doc: &DocumentSnapshot,
+    strings: Strings<'_>,
+    computed_styles: &[String],
+    len: usize,
+) -> Vec<Option<SnapshotLayout>> {
+    let tree = &doc.layout;
+    let stacking_contexts: HashSet<i64> = tree.stacking_contexts.index.iter().copied().collect();
+    let mut layouts: Vec<Option<SnapshotLayout>> = vec![None; len];
+    let mut layout_nodes = Vec::with_capacity(tree.node_index.len());
+    for (layout_idx, &node_idx) in tree.node_index.iter().enumerate() {
+        let node_idx = node_idx as usize;
+        layout_nodes.push(node_idx);
+        // a node may have multiple layout objects, the first one is its box
+        let slot = match layouts.get_mut(node_idx) {
+            Some(slot) if slot.is_none() => slot,
+            _ => continue,
+        };
+        let styles = tree
+            .styles
+            .get(layout_idx)
+            .map(|styles| {
+                computed_styles
+                    .iter()
+                    .cloned()
+                    .zip(styles.inner().iter().map(|value| strings.get(value)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        *slot = Some(SnapshotLayout {
+            bounds: tree
+                .bounds
+                .get(layout_idx)
+                .map(bounding_box)
+                .unwrap_or_else(|| bounding_box(&Rectangle::default())),
+            text: strings.get_at(Some(&tree.text), layout_idx),
+            styles,
+            paint_order: tree
+                .paint_orders
+                .as_ref()
+                .and_then(|orders| orders.get(layout_idx))
+                .copied(),
+            stacking_context: stacking_contexts.contains(&(layout_idx as i64)),
+            text_boxes: Vec::new(),
+        });
+    }
+
+    let boxes = &doc.text_boxes;
+    for (idx, layout_idx) in boxes.layout_index.iter().enumerate() {
+        let layout = layout_nodes
+            .get(*layout_idx as usize)
+            .and_then(|&node_idx| layouts.get_mut(node_idx))
+            .and_then(Option::as_mut);
+        if let Some(layout) = layout {
+            layout.text_boxes.push(TextBox {
+                bounds: boxes
+                    .bounds
+                    .get(idx)
+                    .map(bounding_box)
+                    .unwrap_or_else(|| bounding_box(&Rectangle::default())),
+                start: boxes.start.get(idx).copied().unwrap_or_default(),
+                length: boxes.length.get(idx).copied().unwrap_or_default(),
+            });
+        }
+    }
+    layouts
+}
+
+/// A node of a [`SnapshotDocument`]
+#[derive(Debug, Clone)]
+pub struct SnapshotNode {
+    /// The index of the parent node in the document, `None` for the root
+    pub parent: Option<usize>,
+    /// The [`Node.nodeType`](https://developer.mozilla.org/en-US/docs/Web/API/Node/nodeType)
+    pub node_type: i64,
+    pub name: String,
+    pub value: String,
+    pub backend_node_id: Option<BackendNodeId>,
+    /// The attributes of elements as name-value pairs
+    pub attributes: Vec<(String, String)>,
+    /// The type of the shadow root, if the node is one
+    pub shadow_root_type: Option<String>,
+    /// The text of `textarea` elements
+    pub text_value: Option<String>,
+    /// The value of `input` elements
+    pub input_value: Option<String>,
+    pub input_checked: bool,
+    pub option_selected: bool,
+    /// Whether the node has a click event listener
+    pub is_clickable: bool,
+    /// The index of the content document of an `iframe` in
+    /// [`DomSnapshot::documents`]
+    pub content_document: Option<usize>,
+    /// The type of pseudo elements, like `before`
+    pub pseudo_type: Option<String>,
+    /// The selected source url of `img` elements
+    pub current_source_url: Option<String>,
+    /// The layout of the node, `None` if it isn't rendered
+    pub layout: Option<SnapshotLayout>,
+}
+
+impl SnapshotNode {
+    /// The value of the attribute of an element
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attr, _)| attr == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The layout of a rendered [`SnapshotNode`]
+#[derive(Debug, Clone)]
+pub struct SnapshotLayout {
+    /// The border box in document coordinates
+    pub bounds: BoundingBox,
+    /// The text of text nodes
+    pub text: String,
+    /// The computed styles the snapshot was captured with
+    pub styles: HashMap<String, String>,
+    pub paint_order: Option<i64>,
+    /// Whether the node establishes a stacking context
+    pub stacking_context: bool,
+    /// The boxes of the lines of text nodes
+    pub text_boxes: Vec<TextBox>,
+}
+
+/// A line of text of a text node
+#[derive(Debug, Clone)]
+pub struct TextBox {
+    pub bounds: BoundingBox,
+    /// The index of the first character of the line in the text of the node
+    pub start: i64,
+    /// The number of characters of the line
+    pub length: i64,
+}
+
+fn bounding_box(rect: &Rectangle) -> BoundingBox {
+    let value = |idx: usize| rect.inner().get(idx).copied().unwrap_or_default();
+    BoundingBox {
+        x: value(0),
+        y: value(1),
+        width: value(2),
+        height: value(3),
+    }
+}
+
+/// The string table of a snapshot
+#[derive(Clone, Copy)]
+struct Strings<'a>(&'a [String]);
+
+impl Strings<'_> {
+    /// The string at the index, empty for `-1` which stands for no string
+    fn get(&self, idx: &StringIndex) -> String {
+        usize::try_from(*idx.inner())
+            .ok()
+            .and_then(|idx| self.0.get(idx))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn get_at(&self, indices: Option<&[StringIndex]>, idx: usize) -> String {
+        indices
+            .and_then(|indices| indices.get(idx))
+            .map(|idx| self.get(idx))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_snapshot() {
+        let snapshot: CaptureSnapshotReturns = serde_json::from_value(serde_json::json!({
+            "strings": ["https://example.com/", "Example", "#document", "HTML", "BODY", "#text",
+                        "Hello", "id", "main", "block", "INPUT", "hi"],
+            "documents": [{
+                "documentURL": 0, "title": 1, "baseURL": 0, "contentLanguage": -1,
+                "encodingName": -1, "publicId": -1, "systemId": -1, "frameId": -1,
+                "nodes": {
+                    "parentIndex": [-1, 0, 1, 2, 2],
+                    "nodeType": [9, 1, 1, 3, 1],
+                    "nodeName": [2, 3, 4, 5, 10],
+                    "nodeValue": [-1, -1, -1, 6, -1],
+                    "backendNodeId": [1, 2, 3, 4, 5],
+                    "attributes": [[], [], [7, 8], [], []],
+                    "inputValue": {"index": [4], "value": [11]},
+                    "isClickable": {"index": [2]}
+                },
+                "layout": {
+                    "nodeIndex": [2, 3],
+                    "styles": [[9], [9]],
+                    "bounds": [[0, 0, 800, 600], [8, 8, 40, 18]],
+                    "text": [-1, 6],
+                    "stackingContexts": {"index": [0]}
+                },
+                "textBoxes": {
+                    "layoutIndex": [1],
+                    "bounds": [[8, 8, 40, 18]],
+                    "start": [0],
+                    "length": [5]
+                }
+            }]
+        }))
+        .unwrap();
+
+        let snapshot = DomSnapshot::new(snapshot, &["display".to_string()]);
+        let doc = &snapshot.documents[0];
+        assert_eq!(doc.url, "https://example.com/");
+        assert_eq!(doc.title, "Example");
+        assert_eq!(doc.frame_id, "");
+        assert_eq!(doc.nodes.len(), 5);
+
+        let body = &doc.nodes[2];
+        assert_eq!(body.name, "BODY");
+        assert_eq!(body.parent, Some(1));
+        assert_eq!(body.attribute("id"), Some("main"));
+        assert!(body.is_clickable);
+        let layout = body.layout.as_ref().unwrap();
+        assert_eq!(layout.bounds.width, 800.);
+        assert_eq!(
+            layout.styles.get("display").map(String::as_str),
+            Some("block")
+        );
+        assert!(layout.stacking_context);
+
+        let text = &doc.nodes[3];
+        assert_eq!(text.value, "Hello");
+        let layout = text.layout.as_ref().unwrap();
+        assert_eq!(layout.text, "Hello");
+        assert_eq!(layout.text_boxes[0].length, 5);
+
+        let input = &doc.nodes[4];
+        assert_eq!(input.input_value.as_deref(), Some("hi"));
+        assert!(input.layout.is_none());
+        assert_eq!(doc.nodes[0].parent, None);
+    }
+}
@@ -0,0 +1,272 @@
+//! Inspection of the web storage and IndexedDB databases of an origin.
+//!
+//! In contrast to `evaluate` calls, these go through the `DOMStorage` and
+//! `IndexedDB` domains, so they are not limited to the origin of the main
+//! document. The page still needs a loaded frame of the origin, e.g. an
+//! iframe, otherwise the storage of the origin can't be found.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chromiumoxide_cdp::cdp::browser_protocol::dom_storage::{
+    ClearParams, GetDomStorageItemsParams, Item, RemoveDomStorageItemParams,
+    SetDomStorageItemParams, StorageId,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::indexed_db::{
+    self, ClearObjectStoreParams, DataEntry, DatabaseWithObjectStores, DeleteDatabaseParams,
+    ObjectStore, RequestDataParams, RequestDatabaseNamesParams, RequestDatabaseParams,
+};
+use chromiumoxide_cdp::cdp::browser_protocol::storage::StorageType;
+
+use crate::error::{CdpError, Result};
+use crate::handler::PageInner;
+use crate::js::JsHandle;
+
+/// The `localStorage` or `sessionStorage` of an origin, created with
+/// `Page::local_storage` or `Page::session_storage`.
+#[derive(Debug, Clone)]
+pub struct WebStorage {
+    inner: Arc<PageInner>,
+    id: StorageId,
+}
+
+impl WebStorage {
+    pub(crate) fn new(inner: Arc<PageInner>, origin: String, is_local_storage: bool) -> Self {
+        let id = StorageId {
+            security_origin: Some(origin),
+            storage_key: None,
+            is_local_storage,
+        };
+        Self { inner, id }
+    }
+
+    /// The origin of the storage
+    pub fn origin(&self) -> &str {
+        self.id.security_origin.as_deref().unwrap_or_default()
+    }
+
+    /// Whether this is the `localStorage` and not the `sessionStorage`
+    pub fn is_local_storage(&self) -> bool {
+        self.id.is_local_storage
+    }
+
+    /// All items of the storage
+    pub async fn items(&self) -> Result<HashMap<String, String>> {
+        let entries = self
+            .inner
+            .execute(GetDomStorageItemsParams::new(self.id.clone()))
+            .await?
+            .result
+            .entries;
+        Ok(items_to_map(entries))
+    }
+
+    /// The value of the item with the `key`, if there is one
+    pub async fn get(&self, key: impl AsRef<str>) -> Result<Option<String>> {
+        Ok(self.items().await?.remove(key.as_ref()))
+    }
+
+    /// Sets the value of the item with the `key`
+    pub async fn set(&self, key: impl Into<String>, value: impl Into<String>) -> Result<()> {
+        self.inner
+            .execute(SetDomStorageItemParams::new(self.id.clone(), key, value))
+            .await?;
+        Ok(())
+    }
+
+    /// Removes the item with the `key`
+    pub async fn remove(&self, key: impl Into<String>) -> Result<()> {
+        self.inner
+            .execute(RemoveDomStorageItemParams::new(self.id.clone(), key))
+            .await?;
+        Ok(())
+    }
+
+    /// Removes all items of the storage
+    pub async fn clear(&self) -> Result<()> {
+        self.inner
+            .execute(ClearParams::new(self.id.clone()))
+            .await?;
+        Ok(())
+    }
+}
+
+/// The items of a storage are reported as `[key, value]` arrays
+fn items_to_map(entries: Vec<Item>) -> HashMap<String, String> {
+    entries
+        .into_iter()
+        .filter_map(|item| {
+            let mut item = item.inner().iter().cloned();
+            Some((item.next()?, item.next().unwrap_or_default()))
+        })
+        .collect()
+}
+
+/// The IndexedDB databases of an origin, created with `Page::indexed_db`.
+#[derive(Debug, Clone)]
+pub struct IndexedDb {
+    inner: Arc<PageInner>,
+    origin: String,
+}
+
+impl IndexedDb {
+    pub(crate) async fn new(inner: Arc<PageInner>, origin: String) -> Result<Self> {
+        inner.execute(indexed_db::EnableParams::default()).await?;
+        Ok(Self { inner, origin })
+    }
+
+    /// The origin of the databases
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// The names of all databases of the origin
+    pub async fn database_names(&self) -> Result<Vec<String>> {
+        let params = RequestDatabaseNamesParams::builder()
+            .security_origin(self.origin.clone())
+            .build();
+        Ok(self.inner.execute(params).await?.result.database_names)
+    }
+
+    /// The version and object stores of the database
+    pub async fn database(&self, database: impl Into<String>) -> Result<DatabaseWithObjectStores> {
+        let params = RequestDatabaseParams::builder()
+            .security_origin(self.origin.clone())
+            .database_name(database)
+            .build()
+            .map_err(CdpError::msg)?;
+        Ok(self
+            .inner
+            .execute(params)
+            .await?
+            .result
+            .database_with_object_stores)
+    }
+
+    /// The object stores of the database
+    pub async fn object_stores(&self, database: impl Into<String>) -> Result<Vec<ObjectStore>> {
+        Ok(self.database(database).await?.object_stores)
+    }
+
+    /// Reads up to `limit` records of the object store, skipping the first
+    /// `skip` ones, in the order of their keys.
+    pub async fn records(
+        &self,
+        database: impl Into<String>,
+        object_store: impl Into<String>,
+        skip: usize,
+        limit: usize,
+    ) -> Result<IndexedDbRecords> {
+        let params = RequestDataParams::builder()
+            .security_origin(self.origin.clone())
+            .database_name(database)
+            .object_store_name(object_store)
+            .skip_count(skip as i64)
+            .page_size(limit as i64)
+            .build()
+            .map_err(CdpError::msg)?;
+        let res = self.inner.execute(params).await?.result;
+        Ok(IndexedDbRecords {
+            records: res
+                .object_store_data_entries
+                .into_iter()
+                .map(|entry| IndexedDbRecord::new(&self.inner, entry))
+                .collect(),
+            has_more: res.has_more,
+        })
+    }
+
+    /// Deletes all records of the object store
+    pub async fn clear_object_store(
+        &self,
+        database: impl Into<String>,
+        object_store: impl Into<String>,
+    ) -> Result<()> {
+        let params = ClearObjectStoreParams::builder()
+            .security_origin(self.origin.clone())
+            .database_name(database)
+            .object_store_name(object_store)
+            .build()
+            .map_err(CdpError::msg)?;
+        self.inner.execute(params).await?;
+        Ok(())
+    }
+
+    /// Deletes the database
+    pub async fn delete_database(&self, database: impl Into<String>) -> Result<()> {
+        let params = DeleteDatabaseParams::builder()
+            .security_origin(self.origin.clone())
+            .database_name(database)
+            .build()
+            .map_err(CdpError::msg)?;
+        self.inner.execute(params).await?;
+        Ok(())
+    }
+}
+
+/// A page of records read with [`IndexedDb::records`]
+#[derive(Debug)]
+pub struct IndexedDbRecords {
+    pub records: Vec<IndexedDbRecord>,
+    /// Whether there are records after this page
+    pub has_more: bool,
+}
+
+/// A record of an object store.
+///
+/// The key, primary key and value are handles to mirrors of the stored
+/// objects, use [`JsHandle::json_value`] to read them.
+#[derive(Debug)]
+pub struct IndexedDbRecord {
+    /// The key of the record in the index, the primary key when reading from
+    /// the object store itself
+    pub key: JsHandle,
+    pub primary_key: JsHandle,
+    pub value: JsHandle,
+}
+
+impl IndexedDbRecord {
+    fn new(inner: &Arc<PageInner>, entry: DataEntry) -> Self {
+        Self {
+            key: JsHandle::new(Arc::clone(inner), entry.key, None),
+            primary_key: JsHandle::new(Arc::clone(inner), entry.primary_key, None),
+            value: JsHandle::new(Arc::clone(inner), entry.value, None),
+        }
+    }
+}
+
+/// Joins the storage types to the comma separated list expected by
+/// `Storage.clearDataForOrigin`
+pub(crate) fn storage_types_param(types: impl IntoIterator<Item = StorageType>) -> String {
+    types
+        .into_iter()
+        .map(|ty| ty.as_ref().to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_items() {
+        let items = items_to_map(vec![
+            Item::new(vec!["theme".to_string(), "dark".to_string()]),
+            Item::new(vec!["empty".to_string()]),
+            Item::new(Vec::<String>::new()),
+        ]);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items["theme"], "dark");
+        assert_eq!(items["empty"], "");
+    }
+
+    #[test]
+    fn joins_storage_types() {
+        assert_eq!(
+            storage_types_param([StorageType::LocalStorage, StorageType::Indexeddb]),
+            "local_storage,indexeddb"
+        );
+        assert_eq!(storage_types_param([]), "");
+    }
+}
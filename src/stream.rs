@@ -0,0 +1,260 @@
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use futures::{FutureExt, Stream, TryStreamExt};
+
+use chromiumoxide_cdp::cdp::browser_protocol::io::{
+    CloseParams, ReadParams, ReadReturns, StreamHandle,
+};
+
+use crate::error::Result;
+use crate::handler::PageInner;
+use crate::utils;
+
+/// The maximum number of bytes requested with a single `IO.read` by default
+const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The data of a protocol stream, like a response body taken with
+/// `Fetch.takeResponseBodyAsStream`, read chunk by chunk via `IO.read`.
+///
+/// Only a single chunk is held in memory at a time, so this is suitable for
+/// large bodies. The protocol stream is closed once all data was read or when
+/// the `IoStream` is dropped.
+#[must_use = "streams do nothing unless polled"]
+pub struct IoStream {
+    inner: Arc<PageInner>,
+    handle: StreamHandle,
+    chunk_size: usize,
+    read: Option<BoxFuture<'static, Result<ReadReturns>>>,
+    /// Whether all data was read or reading failed
+    done: bool,
+}
+
+impl IoStream {
+    pub(crate) fn new(inner: Arc<PageInner>, handle: StreamHandle) -> Self {
+        Self {
+            inner,
+            handle,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            read: None,
+            done: false,
+        }
+    }
+
+    /// Sets the maximum number of bytes requested with a single `IO.read`
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// The handle of the protocol stream
+    pub fn handle(&self) -> &StreamHandle {
+        &self.handle
+    }
+
+    /// Reads all remaining data into a single buffer
+    pub async fn read_to_end(self) -> Result<Vec<u8>> {
+        self.try_concat().await
+    }
+
+    fn read_next(&self) -> BoxFuture<'static, Result<ReadReturns>> {
+        let inner = Arc::clone(&self.inner);
+        let read = ReadParams::builder()
+            .handle(self.handle.clone())
+            .size(self.chunk_size as i64)
+            .build()
+            .unwrap();
+        async move { Ok(inner.execute(read).await?.result) }.boxed()
+    }
+
+    /// Releases the protocol stream
+    fn close(&mut self) {
+        if !self.done {
+            self.done = true;
+            self.inner
+                .execute_detached(CloseParams::new(self.handle.clone()));
+        }
+    }
+}
+
+impl Stream for IoStream {
+    type Item = Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if this.read.is_none() {
+                this.read = Some(this.read_next());
+            }
+            let res = match this.read.as_mut().map(|read| read.poll_unpin(cx)) {
+                Some(Poll::Ready(res)) => res,
+                _ => return Poll::Pending,
+            };
+            this.read = None;
+            let chunk = match res {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    this.close();
+                    return Poll::Ready(Some(Err(err)));
+                }
+            };
+            if chunk.eof {
+                this.close();
+            }
+            let data = if chunk.base64_encoded.unwrap_or_default() {
+                match utils::base64::decode(&chunk.data) {
+                    Ok(data) => data,
+                    Err(err) => {
+                        this.close();
+                        return Poll::Ready(Some(Err(err.into())));
+                    }
+                }
+            } else {
+                chunk.data.into_bytes()
+            };
+            if !data.is_empty() {
+                return Poll::Ready(Some(Ok(data)));
+            }
+        }
+    }
+}
+
+impl Drop for IoStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl fmt::Debug for IoStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IoStream")
+            .field("handle", &self.handle)
+            .field("chunk_size", &self.chunk_size)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chromiumoxide_cdp::cdp::browser_protocol::target::{SessionId, TargetId};
+    use chromiumoxide_types::{CallId, Error, Response};
+    use futures::channel::oneshot::channel as oneshot_channel;
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::handler::target::TargetMessage;
+    use crate::handler::PageHandle;
+
+    /// Answers the `IO.read` calls of the stream with the `chunks` and
+    /// returns the requested sizes of the reads, up to the `IO.close` call
+    async fn serve(
+        handle: &mut PageHandle,
+        chunks: Vec<std::result::Result<serde_json::Value, Error>>,
+    ) -> Vec<i64> {
+        let mut chunks = chunks.into_iter();
+        let mut sizes = Vec::new();
+        while let Some(TargetMessage::Command(msg)) = handle.rx.next().await {
+            assert_eq!(msg.params["handle"], "stream");
+            if msg.method == "IO.close" {
+                break;
+            }
+            assert_eq!(msg.method, "IO.read");
+            sizes.push(msg.params["size"].as_i64().unwrap());
+            let (result, error) = match chunks.next().unwrap() {
+                Ok(result) => (Some(result), None),
+                Err(err) => (None, Some(err)),
+            };
+            let _ = msg.sender.send(Ok(Response {
+                id: CallId::new(sizes.len()),
+                result,
+                error,
+            }));
+        }
+        sizes
+    }
+
+    fn page() -> PageHandle {
+        PageHandle::new(TargetId::new("target"), SessionId::new("session"), None)
+    }
+
+    #[tokio::test]
+    async fn reads_and_decodes_chunks_until_eof() {
+        let mut handle = page();
+        let stream =
+            IoStream::new(Arc::clone(handle.inner()), StreamHandle::new("stream")).chunk_size(4);
+        let chunks = vec![
+            Ok(serde_json::json!({ "data": "ab", "eof": false })),
+            Ok(serde_json::json!({ "data": "", "eof": false })),
+            Ok(serde_json::json!({ "data": "Y2Q=", "base64Encoded": true, "eof": false })),
+            Ok(serde_json::json!({ "data": "e", "eof": true })),
+        ];
+        let (data, sizes) = futures::join!(stream.collect::<Vec<_>>(), serve(&mut handle, chunks));
+        let data = data.into_iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(data, vec![b"ab".to_vec(), b"cd".to_vec(), b"e".to_vec()]);
+        assert_eq!(sizes, vec![4; 4]);
+    }
+
+    #[tokio::test]
+    async fn ends_after_a_failed_read() {
+        let mut handle = page();
+        let stream = IoStream::new(Arc::clone(handle.inner()), StreamHandle::new("stream"));
+        let chunks = vec![
+            Ok(serde_json::json!({ "data": "ab", "eof": false })),
+            Err(Error {
+                code: -32000,
+                message: "Invalid stream handle".to_string(),
+            }),
+        ];
+        let (data, sizes) = futures::join!(stream.collect::<Vec<_>>(), serve(&mut handle, chunks));
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0].as_ref().unwrap(), b"ab");
+        assert!(data[1].is_err());
+        assert_eq!(sizes, vec![DEFAULT_CHUNK_SIZE as i64; 2]);
+    }
+
+    #[tokio::test]
+    async fn closes_the_protocol_stream_when_the_channel_is_full() {
+        let mut handle = page();
+        let mut sender = handle.inner().sender().clone();
+        let mut queued = 0;
+        while !sender
+            .try_send(TargetMessage::MainFrame(oneshot_channel().0))
+            .is_err_and(|err| err.is_full())
+        {
+            queued += 1;
+        }
+        let stream = IoStream::new(Arc::clone(handle.inner()), StreamHandle::new("stream"));
+        drop(stream);
+        for _ in 0..queued {
+            assert!(matches!(
+                handle.rx.next().await,
+                Some(TargetMessage::MainFrame(_))
+            ));
+        }
+        match handle.rx.next().await {
+            Some(TargetMessage::Command(msg)) => assert_eq!(msg.method, "IO.close"),
+            _ => panic!("expected IO.close"),
+        }
+    }
+
+    #[tokio::test]
+    async fn closes_the_protocol_stream_when_dropped() {
+        let mut handle = page();
+        let stream = IoStream::new(Arc::clone(handle.inner()), StreamHandle::new("stream"));
+        drop(stream);
+        match handle.rx.next().await {
+            Some(TargetMessage::Command(msg)) => {
+                assert_eq!(msg.method, "IO.close");
+                assert_eq!(msg.params["handle"], "stream");
+            }
+            _ => panic!("expected IO.close"),
+        }
+    }
+}
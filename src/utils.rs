@@ -14,6 +14,64 @@ pub(crate) async fn write<P: AsRef<Path> + Unpin, C: AsRef<[u8]>>(
     }
 }
 
+/// Read a file with configured runtime
+pub(crate) async fn read<P: AsRef<Path> + Unpin>(path: P) -> std::io::Result<Vec<u8>> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "async-std-runtime")] {
+            async_std::fs::read(path.as_ref()).await
+        } else if #[cfg(feature = "tokio-runtime")] {
+            tokio::fs::read(path.as_ref()).await
+        }
+    }
+}
+
+/// Recursively create a directory with configured runtime
+pub(crate) async fn create_dir_all<P: AsRef<Path> + Unpin>(path: P) -> std::io::Result<()> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "async-std-runtime")] {
+            async_std::fs::create_dir_all(path.as_ref()).await
+        } else if #[cfg(feature = "tokio-runtime")] {
+            tokio::fs::create_dir_all(path.as_ref()).await
+        }
+    }
+}
+
+/// Spawn a future on the configured runtime, fails if no runtime feature is
+/// enabled
+pub(crate) fn spawn<F>(future: F) -> crate::error::Result<()>
+where
+    F: std::future::Future<Output = ()> + Send + 'static,
+{
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "async-std-runtime")] {
+            async_std::task::spawn(future);
+            Ok(())
+        } else if #[cfg(feature = "tokio-runtime")] {
+            tokio::spawn(future);
+            Ok(())
+        } else {
+            drop(future);
+            Err(crate::error::CdpError::msg(
+                "missing chromiumoxide runtime: enable `async-std-runtime` or `tokio-runtime`",
+            ))
+        }
+    }
+}
+
+/// Fails with `CdpError::Timeout` if `future` doesn't complete within
+/// `duration`
+pub(crate) async fn timeout<F: std::future::Future>(
+    duration: std::time::Duration,
+    future: F,
+) -> crate::error::Result<F::Output> {
+    use futures::future::{select, Either};
+    futures::pin_mut!(future);
+    match select(future, futures_timer::Delay::new(duration)).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(crate::error::CdpError::Timeout),
+    }
+}
+
 /// Canonicalize path
 ///
 /// Chromium sandboxing does not support Window UNC paths which are used by Rust
@@ -62,6 +120,11 @@ pub(crate) mod base64 {
     pub fn decode<T: AsRef<[u8]>>(input: T) -> Result<Vec<u8>, DecodeError> {
         STANDARD.decode(input)
     }
+
+    /// Encode base64 using the standard alphabet and padding
+    pub fn encode<T: AsRef<[u8]>>(input: T) -> String {
+        STANDARD.encode(input)
+    }
 }
 
 /// Creates a javascript function string as `(<function>)("<param 1>", "<param
@@ -0,0 +1,465 @@
+//! Visual regression testing by comparing screenshots against baseline
+//! images.
+//!
+//! Pixels are compared by their perceived color difference in the YIQ color
+//! space, and differences caused by anti-aliasing can be tolerated, like
+//! [pixelmatch](https://github.com/mapbox/pixelmatch) does.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chromiumoxide_cdp::cdp::browser_protocol::page::CaptureScreenshotFormat;
+
+use crate::bitmap::Bitmap;
+use crate::element::Element;
+use crate::error::{CdpError, Result};
+use crate::page::{Page, ScreenshotParams};
+use crate::utils;
+
+/// The environment variable that makes [`assert_screenshot_matches`] write
+/// the screenshots as new baselines instead of comparing them
+pub const UPDATE_BASELINES_ENV: &str = "CHROMIUMOXIDE_UPDATE_BASELINES";
+
+/// The maximum possible YIQ difference of two colors
+const MAX_YIQ_DELTA: f64 = 35215.;
+
+/// Colors of the diff image
+const DIFF_COLOR: [u8; 4] = [255, 0, 0, 255];
+const ANTI_ALIASED_COLOR: [u8; 4] = [255, 255, 0, 255];
+
+/// How screenshots are compared to their baseline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffOptions {
+    /// The tolerated color difference of a pixel, from 0 (exact match) to 1,
+    /// defaults to 0.1
+    pub threshold: f64,
+    /// The number of differing pixels that are tolerated, defaults to 0
+    pub max_diff_pixels: usize,
+    /// Whether differences of anti-aliased pixels are tolerated, defaults to
+    /// true
+    pub anti_aliasing: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0.1,
+            max_diff_pixels: 0,
+            anti_aliasing: true,
+        }
+    }
+}
+
+/// The result of comparing two images
+#[derive(Debug, Clone)]
+pub struct ImageDiff {
+    /// The number of pixels that differ
+    pub diff_pixels: usize,
+    /// The number of differing pixels that were detected as anti-aliasing and
+    /// tolerated
+    pub anti_aliased_pixels: usize,
+    /// The image highlighting the differing pixels in red and the tolerated
+    /// anti-aliased pixels in yellow on a faded copy of the expected image
+    diff_image: Bitmap,
+}
+
+impl ImageDiff {
+    /// Whether the images match within the tolerance of the `opts`
+    pub fn matches(&self, opts: &DiffOptions) -> bool {
+        self.diff_pixels <= opts.max_diff_pixels
+    }
+
+    /// The diff image encoded as png
+    pub fn diff_png(&self) -> Result<Vec<u8>> {
+        self.diff_image.to_png()
+    }
+}
+
+/// Compares the two png images pixel by pixel.
+///
+/// Images of different sizes are compared within the bounds of the larger
+/// one, pixels that are missing in either image count as different.
+pub fn compare_png(actual: &[u8], expected: &[u8], opts: &DiffOptions) -> Result<ImageDiff> {
+    Ok(compare(
+        &Bitmap::from_png(actual)?,
+        &Bitmap::from_png(expected)?,
+        opts,
+    ))
+}
+
+fn compare(actual: &Bitmap, expected: &Bitmap, opts: &DiffOptions) -> ImageDiff {
+    let width = actual.width.max(expected.width);
+    let height = actual.height.max(expected.height);
+    let same_size = actual.width == expected.width && actual.height == expected.height;
+    let max_delta = MAX_YIQ_DELTA * opts.threshold * opts.threshold;
+
+    let mut diff_image = Vec::with_capacity(width as usize * height as usize * 4);
+    let mut diff_pixels = 0;
+    let mut anti_aliased_pixels = 0;
+    for y in 0..height {
+        for x in 0..width {
+            let px = match (actual.pixel(x, y), expected.pixel(x, y)) {
+                (Some(a), Some(e)) => {
+                    if color_delta(a, e, false).abs() <= max_delta {
+                        faded(e)
+                    } else if opts.anti_aliasing
+                        && same_size
+                        && (is_anti_aliased(actual, expected, x, y)
+                            || is_anti_aliased(expected, actual, x, y))
+                    {
+                        anti_aliased_pixels += 1;
+                        ANTI_ALIASED_COLOR
+                    } else {
+                        diff_pixels += 1;
+                        DIFF_COLOR
+                    }
+                }
+                _ => {
+                    diff_pixels += 1;
+                    DIFF_COLOR
+                }
+            };
+            diff_image.extend_from_slice(&px);
+        }
+    }
+    ImageDiff {
+        diff_pixels,
+        anti_aliased_pixels,
+        diff_image: Bitmap {
+            width,
+            height,
+            data: diff_image,
+        },
+    }
+}
+
+/// Where the screenshot compared by [`assert_screenshot_matches`] is taken
+#[derive(Debug)]
+pub enum ScreenshotSource<'a> {
+    Page(&'a Page, ScreenshotParams),
+    Element(&'a Element),
+}
+
+impl<'a> From<&'a Page> for ScreenshotSource<'a> {
+    fn from(page: &'a Page) -> Self {
+        ScreenshotSource::Page(page, ScreenshotParams::default())
+    }
+}
+
+impl<'a> From<(&'a Page, ScreenshotParams)> for ScreenshotSource<'a> {
+    fn from((page, params): (&'a Page, ScreenshotParams)) -> Self {
+        ScreenshotSource::Page(page, params)
+    }
+}
+
+impl<'a> From<&'a Element> for ScreenshotSource<'a> {
+    fn from(element: &'a Element) -> Self {
+        ScreenshotSource::Element(element)
+    }
+}
+
+impl ScreenshotSource<'_> {
+    async fn screenshot(self) -> Result<Vec<u8>> {
+        match self {
+            ScreenshotSource::Page(page, mut params) => {
+                params.cdp_params.format = Some(CaptureScreenshotFormat::Png);
+                page.screenshot(params).await
+            }
+            ScreenshotSource::Element(element) => {
+                element.screenshot(CaptureScreenshotFormat::Png).await
+            }
+        }
+    }
+}
+
+/// Takes a png screenshot of the page or element and compares it to the
+/// `baseline` image.
+///
+/// If the screenshot doesn't match, `<baseline>-actual.png` and
+/// `<baseline>-diff.png` are written next to the baseline and
+/// `CdpError::ScreenshotMismatch` is returned. A missing baseline is created
+/// from the screenshot, which is also reported as an error so that it isn't
+/// missed on CI.
+///
+/// If the [`UPDATE_BASELINES_ENV`] environment variable is set, the
+/// screenshot is written as the new baseline instead.
+///
+/// # Example
+///
+/// ```no_run
+/// # use chromiumoxide::page::Page;
+/// # use chromiumoxide::error::Result;
+/// # use chromiumoxide::visual::{assert_screenshot_matches, DiffOptions};
+/// # async fn demo(page: Page) -> Result<()> {
+///     let header = page.find_element("header").await?;
+///     assert_screenshot_matches(
+///         &header,
+///         "tests/baselines/header.png",
+///         DiffOptions {
+///             max_diff_pixels: 10,
+///             ..Default::default()
+///         },
+///     )
+///     .await?;
+///     # Ok(())
+/// # }
+/// ```
+pub async fn assert_screenshot_matches<'a>(
+    source: impl Into<ScreenshotSource<'a>>,
+    baseline: impl AsRef<Path>,
+    opts: DiffOptions,
+) -> Result<()> {
+    let baseline = baseline.as_ref();
+    let actual = source.into().screenshot().await?;
+
+    if update_baselines() {
+        return write_creating_dirs(baseline, &actual).await;
+    }
+
+    let expected = match utils::read(baseline).await {
+        Ok(expected) => expected,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            write_creating_dirs(baseline, &actual).await?;
+            return Err(CdpError::msg(format!(
+                "Created missing baseline {}",
+                baseline.display()
+            )));
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let diff = compare_png(&actual, &expected, &opts)?;
+    if diff.matches(&opts) {
+        return Ok(());
+    }
+    let diff_path = sibling(baseline, "diff");
+    utils::write(sibling(baseline, "actual"), &actual).await?;
+    utils::write(&diff_path, diff.diff_png()?).await?;
+    Err(CdpError::ScreenshotMismatch {
+        diff_pixels: diff.diff_pixels,
+        diff_path,
+    })
+}
+
+fn update_baselines() -> bool {
+    std::env::var(UPDATE_BASELINES_ENV).is_ok_and(|val| !matches!(val.as_str(), "" | "0" | "false"))
+}
+
+async fn write_creating_dirs(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        utils::create_dir_all(dir).await?;
+    }
+    Ok(utils::write(path, contents).await?)
+}
+
+/// `<dir>/<stem>-<suffix>.png` for the `<dir>/<stem>.png` path
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}-{suffix}.png"))
+}
+
+/// The YIQ difference of the colors blended onto white, negative if `a` is
+/// brighter than `b`. Only the brightness difference if `y_only` is set.
+fn color_delta(a: [u8; 4], b: [u8; 4], y_only: bool) -> f64 {
+    if a == b {
+        return 0.;
+    }
+    let [r1, g1, b1] = blend(a);
+    let [r2, g2, b2] = blend(b);
+    let y1 = rgb_to_y(r1, g1, b1);
+    let y2 = rgb_to_y(r2, g2, b2);
+    let y = y1 - y2;
+    if y_only {
+        return y;
+    }
+    let i = rgb_to_i(r1, g1, b1) - rgb_to_i(r2, g2, b2);
+    let q = rgb_to_q(r1, g1, b1) - rgb_to_q(r2, g2, b2);
+    let delta = 0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q;
+    if y1 > y2 {
+        -delta
+    } else {
+        delta
+    }
+}
+
+fn blend([r, g, b, a]: [u8; 4]) -> [f64; 3] {
+    let a = a as f64 / 255.;
+    [r, g, b].map(|c| 255. + (c as f64 - 255.) * a)
+}
+
+fn rgb_to_y(r: f64, g: f64, b: f64) -> f64 {
+    r * 0.29889531 + g * 0.58662247 + b * 0.11448223
+}
+
+fn rgb_to_i(r: f64, g: f64, b: f64) -> f64 {
+    r * 0.59597799 - g * 0.2741761 - b * 0.32180189
+}
+
+fn rgb_to_q(r: f64, g: f64, b: f64) -> f64 {
+    r * 0.21147017 - g * 0.52261711 + b * 0.31114694
+}
+
+/// The pixel as faded grayscale for unchanged areas of the diff image
+fn faded(px: [u8; 4]) -> [u8; 4] {
+    let [r, g, b] = blend(px);
+    let gray = (255. + (rgb_to_y(r, g, b) - 255.) * 0.1) as u8;
+    [gray, gray, gray, 255]
+}
+
+/// The pixels around `(x, y)` within the bounds of the bitmap
+fn neighbours(bitmap: &Bitmap, x: u32, y: u32) -> impl Iterator<Item = (u32, u32)> {
+    let (width, height) = (bitmap.width, bitmap.height);
+    (y.saturating_sub(1)..=(y + 1).min(height - 1))
+        .flat_map(move |ny| (x.saturating_sub(1)..=(x + 1).min(width - 1)).map(move |nx| (nx, ny)))
+        .filter(move |&(nx, ny)| (nx, ny) != (x, y))
+}
+
+fn on_edge(bitmap: &Bitmap, x: u32, y: u32) -> bool {
+    x == 0 || y == 0 || x + 1 == bitmap.width || y + 1 == bitmap.height
+}
+
+/// Whether the pixel at `(x, y)` of `img` is likely anti-aliasing: it lies
+/// between a darkest and brightest neighbour, either of which is part of an
+/// area of equal pixels in both images.
+fn is_anti_aliased(img: &Bitmap, other: &Bitmap, x: u32, y: u32) -> bool {
+    let px = match img.pixel(x, y) {
+        Some(px) => px,
+        None => return false,
+    };
+    let mut zeroes = usize::from(on_edge(img, x, y));
+    let (mut min, mut max) = (0., 0.);
+    let (mut min_at, mut max_at) = ((x, y), (x, y));
+    for (nx, ny) in neighbours(img, x, y) {
+        let neighbour = match img.pixel(nx, ny) {
+            Some(neighbour) => neighbour,
+            None => continue,
+        };
+        let delta = color_delta(px, neighbour, true);
+        if delta == 0. {
+            zeroes += 1;
+            // more than two equal neighbours is not an edge
+            if zeroes > 2 {
+                return false;
+            }
+        } else if delta < min {
+            min = delta;
+            min_at = (nx, ny);
+        } else if delta > max {
+            max = delta;
+            max_at = (nx, ny);
+        }
+    }
+    if min == 0. || max == 0. {
+        return false;
+    }
+    (has_many_siblings(img, min_at) && has_many_siblings(other, min_at))
+        || (has_many_siblings(img, max_at) && has_many_siblings(other, max_at))
+}
+
+/// Whether more than two neighbours of the pixel are equal to it
+fn has_many_siblings(img: &Bitmap, (x, y): (u32, u32)) -> bool {
+    let px = match img.pixel(x, y) {
+        Some(px) => px,
+        None => return false,
+    };
+    let equal = neighbours(img, x, y)
+        .filter(|&(nx, ny)| img.pixel(nx, ny) == Some(px))
+        .count();
+    equal + usize::from(on_edge(img, x, y)) > 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    fn bitmap(width: u32, height: u32, px: [u8; 4]) -> Bitmap {
+        Bitmap {
+            width,
+            height,
+            data: px.repeat((width * height) as usize),
+        }
+    }
+
+    fn set(bitmap: &mut Bitmap, x: u32, y: u32, px: [u8; 4]) {
+        let idx = ((y * bitmap.width + x) * 4) as usize;
+        bitmap.data[idx..idx + 4].copy_from_slice(&px);
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let img = bitmap(4, 4, WHITE);
+        let diff = compare(&img, &img, &DiffOptions::default());
+        assert_eq!(diff.diff_pixels, 0);
+        assert!(diff.matches(&DiffOptions::default()));
+    }
+
+    #[test]
+    fn counts_differing_pixels() {
+        let expected = bitmap(5, 5, WHITE);
+        let mut actual = expected.clone();
+        set(&mut actual, 2, 2, BLACK);
+        // a barely visible change is within the threshold
+        set(&mut actual, 0, 0, [250, 250, 250, 255]);
+
+        let opts = DiffOptions::default();
+        let diff = compare(&actual, &expected, &opts);
+        assert_eq!(diff.diff_pixels, 1);
+        assert!(!diff.matches(&opts));
+        assert_eq!(diff.diff_image.pixel(2, 2), Some(DIFF_COLOR));
+        assert!(diff.matches(&DiffOptions {
+            max_diff_pixels: 1,
+            ..opts
+        }));
+
+        let exact = DiffOptions {
+            threshold: 0.,
+            ..opts
+        };
+        assert_eq!(compare(&actual, &expected, &exact).diff_pixels, 2);
+    }
+
+    #[test]
+    fn tolerates_anti_aliasing() {
+        // a gray pixel on the edge between a black and a white area
+        let mut expected = bitmap(6, 6, WHITE);
+        for y in 0..6 {
+            for x in 0..3 {
+                set(&mut expected, x, y, BLACK);
+            }
+        }
+        let mut actual = expected.clone();
+        set(&mut actual, 3, 3, [128, 128, 128, 255]);
+
+        let diff = compare(&actual, &expected, &DiffOptions::default());
+        assert_eq!(diff.diff_pixels, 0);
+        assert_eq!(diff.anti_aliased_pixels, 1);
+
+        let strict = DiffOptions {
+            anti_aliasing: false,
+            ..Default::default()
+        };
+        assert_eq!(compare(&actual, &expected, &strict).diff_pixels, 1);
+    }
+
+    #[test]
+    fn size_mismatch_differs() {
+        let diff = compare(
+            &bitmap(2, 2, WHITE),
+            &bitmap(3, 2, WHITE),
+            &DiffOptions::default(),
+        );
+        assert_eq!(diff.diff_pixels, 2);
+        assert_eq!(diff.diff_image.width, 3);
+    }
+
+    #[test]
+    fn sibling_paths() {
+        assert_eq!(
+            sibling(Path::new("baselines/home.png"), "diff"),
+            Path::new("baselines/home-diff.png")
+        );
+    }
+}